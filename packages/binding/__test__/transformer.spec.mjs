@@ -1,11 +1,36 @@
 import { promises as fs } from 'node:fs'
+import { tmpdir } from 'node:os'
 import { join } from 'node:path'
 import { fileURLToPath } from 'node:url'
 
 import test from 'ava'
 import { decode } from 'blurhash'
 
-import { BlendMode, Gravity, JsColorType, ResizeFit, Transformer } from '../index.js'
+import {
+  BlendMode,
+  BlurPrecision,
+  Channel,
+  compare,
+  compareSync,
+  ConvolvePreset,
+  CropStrategy,
+  GradientKind,
+  Gravity,
+  hammingDistance,
+  HashAlgorithm,
+  JsColorType,
+  MaskChannel,
+  MaskMode,
+  OutputFormat,
+  packAtlas,
+  packAtlasSync,
+  ResizeFit,
+  ShapeKind,
+  TargetFormat,
+  TextAlign,
+  TileLayout,
+  Transformer,
+} from '../index.js'
 
 const __DIRNAME = join(fileURLToPath(import.meta.url), '..')
 const ROOT_DIR = join(__DIRNAME, '..', '..', '..')
@@ -14,6 +39,8 @@ const PNG = await fs.readFile(join(ROOT_DIR, 'un-optimized.png'))
 const JPEG = await fs.readFile(join(ROOT_DIR, 'un-optimized.jpg'))
 const WITH_EXIF_JPG = await fs.readFile(join(ROOT_DIR, 'with-exif.jpg'))
 const SVG = await fs.readFile(join(ROOT_DIR, 'input-debian.svg'))
+// A synthetic font whose glyphs are 0.6em wide boxes.
+const BOXES_TTF = await fs.readFile(join(__DIRNAME, 'boxes.ttf'))
 
 test('should be able to get metadata from png', async (t) => {
   const decoder = new Transformer(PNG)
@@ -171,6 +198,59 @@ test('metadata() reflects resize Cover (exact dims, #158)', async (t) => {
   t.is(meta.height, 100)
 })
 
+test('metadata() reports the cover crop box for position and strategy', async (t) => {
+  // 1024x681 covered into a square keeps a 681x681 window.
+  const centre = await new Transformer(PNG).resize({ width: 100, height: 100 }).metadata()
+  t.deepEqual(centre.cropBox, { left: 172, top: 0, width: 681, height: 681 })
+  const east = await new Transformer(PNG).resize({ width: 100, height: 100, position: Gravity.East }).metadata()
+  t.is(east.cropBox.left, 1024 - 681)
+  const smart = await new Transformer(PNG)
+    .fastResize({ width: 100, height: 100, strategy: CropStrategy.Attention })
+    .metadata()
+  t.is(smart.width, 100)
+  t.is(smart.cropBox.width, 681)
+  t.true(smart.cropBox.left <= 1024 - 681)
+  t.is((await new Transformer(PNG).metadata()).cropBox, undefined)
+})
+
+test('resize Contain letterboxes and Outside covers without cropping', async (t) => {
+  const contain = await new Transformer(PNG)
+    .resize({ width: 200, height: 200, fit: ResizeFit.Contain, background: 'white' })
+    .metadata()
+  t.is(contain.width, 200)
+  t.is(contain.height, 200)
+  const outside = await new Transformer(PNG)
+    .fastResize({ width: 200, height: 200, fit: ResizeFit.Outside })
+    .metadata()
+  t.is(outside.height, 200)
+  t.is(outside.width, Math.round((1024 / 681) * 200))
+})
+
+test('resize withoutEnlargement leaves a smaller image untouched', async (t) => {
+  const meta = await new Transformer(PNG).resize({ width: 4096, withoutEnlargement: true }).metadata()
+  t.is(meta.width, 1024)
+  t.is(meta.height, 681)
+})
+
+test('shrink-on-load keeps the exact resize dimensions of the full decode', async (t) => {
+  // The encode path may decode these JPEGs at 1/2..1/8 scale; metadata() always decodes in full.
+  for (const [input, build] of [
+    [JPEG, (tr) => tr.resize({ width: 64 })],
+    [JPEG, (tr) => tr.resize({ width: 80, height: 80, fit: ResizeFit.Inside })],
+    [WITH_EXIF_JPG, (tr) => tr.rotate().fastResize({ width: 50, height: 70, fit: ResizeFit.Outside })],
+  ]) {
+    const expected = await build(new Transformer(input)).metadata()
+    const actual = await roundTripMeta(await build(new Transformer(input)).png())
+    t.is(actual.width, expected.width)
+    t.is(actual.height, expected.height)
+  }
+  // libjpeg only warns about a truncated file, so shrink-on-load leaves it to the regular decoder.
+  const truncated = JPEG.subarray(0, JPEG.length >> 1)
+  const outcome = (promise) => promise.then(() => 'decoded', () => 'failed')
+  const full = await outcome(new Transformer(truncated).metadata())
+  t.is(await outcome(new Transformer(truncated).resize({ width: 64 }).png()), full)
+})
+
 test('metadata() reflects resize Inside (aspect-clamped, #158)', async (t) => {
   // 1024x681 clamped Inside a 200x100 box keeps aspect -> NOT 200x100.
   const expected = await roundTripMeta(
@@ -448,6 +528,300 @@ test('opacity keeps a spatial filter (blur) alpha feathering (#42)', async (t) =
   t.true(alpha[0] <= alpha[1] && alpha[1] <= alpha[2] && alpha[2] <= alpha[3], 'monotonic gradient')
 })
 
+test('blur keeps transparent edges from darkening at any precision', async (t) => {
+  // Opaque red next to transparent black: premultiplied blurring keeps the feathered edge red.
+  const edge = Uint8Array.from(Array.from({ length: 16 }, (_, x) => (x < 8 ? [255, 0, 0, 255] : [0, 0, 0, 0])).flat())
+  for (const precision of [BlurPrecision.Exact, BlurPrecision.Approximate, undefined]) {
+    const raw = await Transformer.fromRgbaPixels(edge, 16, 1).blur(4, precision).rawPixels()
+    const pixel = [...raw.subarray(8 * 4, 9 * 4)]
+    t.true(pixel[3] > 0 && pixel[3] < 255, `edge alpha is feathered; got ${pixel}`)
+    t.deepEqual(pixel.slice(0, 3), [255, 0, 0])
+  }
+  t.throws(() => Transformer.fromRgbaPixels(edge, 16, 1).blur(-1))
+})
+
+test('convolve applies NxM kernels and presets without darkening transparent edges', async (t) => {
+  // 4x1 ramp through a 3x1 box: the middle pixels average their neighbours.
+  const ramp = Uint8Array.from([0, 30, 60, 90].flatMap((v) => [v, v, v, 255]))
+  const boxed = await Transformer.fromRgbaPixels(ramp, 4, 1)
+    .convolve({ width: 3, height: 1, kernel: [1, 1, 1] })
+    .rawPixels()
+  t.deepEqual([boxed[4], boxed[8]], [30, 60])
+  const edge = Uint8Array.from(Array.from({ length: 8 }, (_, x) => (x < 4 ? [200, 0, 0, 255] : [0, 0, 0, 0])).flat())
+  const sharpened = await Transformer.fromRgbaPixels(edge, 8, 1).convolve(ConvolvePreset.Sharpen).rawPixels()
+  t.deepEqual([...sharpened.subarray(3 * 4, 4 * 4)], [200, 0, 0, 255])
+  const sobel = await Transformer.fromRgbaPixels(ramp, 4, 1).convolve(ConvolvePreset.Sobel).rawPixels()
+  t.true(sobel[4] > 0 && sobel[7] === 255, 'edges light up and alpha is kept')
+  t.throws(() => Transformer.fromRgbaPixels(ramp, 4, 1).convolve({ width: 2, height: 2, kernel: [1, 1, 1] }))
+})
+
+test('tone adjustments apply in call order and keep alpha', async (t) => {
+  const pixel = Uint8Array.from([100, 100, 100, 77])
+  const warmed = await Transformer.fromRgbaPixels(pixel, 1, 1).temperature(50).rawPixels()
+  t.deepEqual([...warmed], [110, 100, 90, 77])
+  const leveled = await Transformer.fromRgbaPixels(pixel, 1, 1).levels(0, 100).gamma(1).rawPixels()
+  t.deepEqual([...leveled], [255, 255, 255, 77])
+  const inverted = await Transformer.fromRgbaPixels(pixel, 1, 1)
+    .curves({ red: [{ x: 0, y: 255 }, { x: 255, y: 0 }] })
+    .saturation(1)
+    .vibrance(0)
+    .rawPixels()
+  t.deepEqual([...inverted], [155, 100, 100, 77])
+  t.throws(() => Transformer.fromRgbaPixels(pixel, 1, 1).gamma(0))
+  t.throws(() => Transformer.fromRgbaPixels(pixel, 1, 1).curves({ all: [{ x: 0, y: 0 }] }))
+})
+
+test('recomb, tint and sepia mix channels through a color matrix', async (t) => {
+  const pixel = Uint8Array.from([10, 20, 30, 200])
+  const swapped = await Transformer.fromRgbaPixels(pixel, 1, 1)
+    .recomb([
+      [0, 0, 1],
+      [0, 1, 0],
+      [1, 0, 0],
+    ])
+    .rawPixels()
+  t.deepEqual([...swapped], [30, 20, 10, 200])
+  const faded = await Transformer.fromRgbaPixels(pixel, 1, 1)
+    .recomb([
+      [1, 0, 0, 0],
+      [0, 1, 0, 0],
+      [0, 0, 1, 0],
+      [0, 0, 0, 0.5],
+    ])
+    .rawPixels()
+  t.deepEqual([...faded], [10, 20, 30, 100])
+  const sepia = await Transformer.fromRgbaPixels(Uint8Array.from([128, 128, 128, 255]), 1, 1).sepia().rawPixels()
+  t.true(sepia[0] > sepia[1] && sepia[1] > sepia[2], `warm brown; got ${[...sepia]}`)
+  const tinted = await Transformer.fromRgbaPixels(pixel, 1, 1).tint('white').rawPixels()
+  t.true(tinted[0] === tinted[1] && tinted[1] === tinted[2] && tinted[3] === 200)
+  const light = await Transformer.fromRgbaPixels(Uint8Array.from([200, 200, 200, 255]), 1, 1).tint('red').rawPixels()
+  t.true(light[0] === 255 && light[1] === light[2] && light[1] > 150, `light red; got ${[...light]}`)
+  t.throws(() => Transformer.fromRgbaPixels(pixel, 1, 1).recomb([[1, 0], [0, 1]]))
+})
+
+test('stats reports channels, opacity, entropy, sharpness and the dominant color', async (t) => {
+  const flat = Uint8Array.from(Array.from({ length: 16 }, () => [30, 60, 90, 255]).flat())
+  const stats = await Transformer.fromRgbaPixels(flat, 4, 4).stats()
+  t.is(stats.channels.length, 4)
+  t.deepEqual(
+    stats.channels.map((c) => [c.min, c.max, c.mean, c.stdev]),
+    [
+      [30, 30, 30, 0],
+      [60, 60, 60, 0],
+      [90, 90, 90, 0],
+      [255, 255, 255, 0],
+    ],
+  )
+  t.is(stats.channels[0].histogram[30], 16)
+  t.true(stats.isOpaque)
+  t.is(stats.entropy, 0)
+  t.is(stats.sharpness, 0)
+  t.deepEqual(stats.dominant, { r: 30, g: 60, b: 90 })
+  // Staged transforms apply first.
+  const faded = Transformer.fromRgbaPixels(flat, 4, 4).opacity(0.5).statsSync()
+  t.false(faded.isOpaque)
+})
+
+test('fromBlurhash matches the reference decoder and blurhash round-trips', async (t) => {
+  const hash = 'LEHV6nWB2yk8pyo0adR*.7kCMdnj'
+  const expected = decode(hash, 32, 32)
+  const actual = await Transformer.fromBlurhash(hash, 32, 32).rawPixels()
+  t.is(actual.length, expected.length)
+  t.true(actual.every((v, i) => Math.abs(v - expected[i]) <= 1))
+  const encoded = await Transformer.fromBlurhash(hash, 32, 32).blurhash()
+  t.is(encoded.length, hash.length)
+  t.is(encoded[0], hash[0])
+  t.is(Transformer.fromBlurhash(hash, 8, 8).blurhashSync({ componentsX: 1, componentsY: 1 }).length, 6)
+  t.throws(() => Transformer.fromBlurhash('LEHV6n', 8, 8))
+  t.throws(() => Transformer.fromBlurhash(hash, 8, 8).blurhashSync({ componentsX: 10 }))
+})
+
+test('thumbhash hashes the staged image and flags alpha', async (t) => {
+  const red = Uint8Array.from(Array.from({ length: 64 }, () => [255, 0, 0, 255]).flat())
+  const opaque = await Transformer.fromRgbaPixels(red, 8, 8).thumbhash()
+  t.true(Buffer.isBuffer(opaque))
+  t.is(opaque[2] >> 7, 0)
+  const faded = Transformer.fromRgbaPixels(red, 8, 8).opacity(0.5).thumbhashSync()
+  t.is(faded[2] >> 7, 1)
+  const wide = await Transformer.fromRgbaPixels(red, 8, 8).resize(400, 100).thumbhash()
+  t.is(wide[4] >> 7, 1)
+})
+
+test('perceptualHash finds near-duplicates', async (t) => {
+  const hash = await new Transformer(PNG).perceptualHash()
+  t.regex(hash, /^[01]{64}$/)
+  const resized = await new Transformer(PNG).resize(300).perceptualHash()
+  t.true(hammingDistance(hash, resized) <= 6)
+  const inverted = new Transformer(PNG).invert().perceptualHashSync()
+  t.true(hammingDistance(hash, inverted) > 10)
+  for (const algorithm of [HashAlgorithm.Average, HashAlgorithm.Difference]) {
+    const small = new Transformer(PNG).perceptualHashSync({ algorithm, size: 4 })
+    t.is(small.length, 16)
+  }
+  t.throws(() => new Transformer(PNG).perceptualHashSync({ size: 1 }))
+  t.throws(() => hammingDistance('01', '011'))
+})
+
+test('compare measures encode loss and can render a diff', async (t) => {
+  const same = await compare(PNG, new Transformer(PNG))
+  t.is(same.psnr, Infinity)
+  t.is(same.ssim, 1)
+  t.is(same.maxDeltaE, 0)
+  const jpeg = await new Transformer(PNG).jpeg(50)
+  const lossy = await compare(PNG, jpeg, { diff: true })
+  t.true(lossy.psnr > 20 && lossy.psnr < 60, `psnr ${lossy.psnr}`)
+  t.true(lossy.ssim < 1 && lossy.msSsim < 1)
+  t.true(lossy.maxDeltaE > lossy.meanDeltaE)
+  const diff = await new Transformer(lossy.diff).metadata()
+  t.is(diff.width, 1024)
+  t.is(compareSync(PNG, jpeg).diff, undefined)
+  t.throws(() => compareSync(PNG, new Transformer(PNG).resize(100)))
+})
+
+test('encodeToTarget searches the quality for a size or fidelity target', async (t) => {
+  const small = new Transformer(PNG).resize(256)
+  const fit = await small.encodeToTarget({ format: TargetFormat.Jpeg, maxBytes: 12_000 })
+  t.true(fit.targetMet)
+  t.true(fit.buffer.length <= 12_000)
+  t.true(fit.quality >= 1 && fit.quality <= 100)
+  const sharp = small.encodeToTargetSync({ format: TargetFormat.Webp, minSsim: 0.97 })
+  t.true(sharp.targetMet)
+  t.true(sharp.ssim >= 0.97)
+  const check = compareSync(await small.png(), sharp.buffer)
+  t.true(Math.abs(check.ssim - sharp.ssim) < 1e-6)
+  t.throws(() => small.encodeToTargetSync({ format: TargetFormat.Jpeg }))
+})
+
+test('encodeAuto returns the smallest of the candidate formats', async (t) => {
+  const small = new Transformer(PNG).resize(256)
+  const best = await small.encodeAuto({ formats: [OutputFormat.Jpeg, OutputFormat.Webp], quality: 80 })
+  t.false(best.lossless)
+  t.is(best.quality, 80)
+  const jpeg = await small.jpeg(80)
+  const webp = await small.webp(80)
+  t.is(best.buffer.length, Math.min(jpeg.length, webp.length))
+  const lossless = small.encodeAutoSync({ formats: [OutputFormat.Png, OutputFormat.Webp], preferLossless: true })
+  t.true(lossless.lossless)
+  t.is((await new Transformer(lossless.buffer).metadata()).width, 256)
+  t.throws(() => small.encodeAutoSync({ formats: [] }))
+  // An RGBA photo that is fully opaque still races JPEG.
+  const { width, height } = await small.metadata()
+  const raw = await small.rawPixels()
+  const channels = raw.length / (width * height)
+  const rgba = new Uint8Array(width * height * 4).map((_, i) =>
+    i % 4 === 3 ? 255 : raw[Math.floor(i / 4) * channels + Math.min(i % 4, channels - 1)],
+  )
+  const opaque = Transformer.fromRgbaPixels(rgba, width, height)
+  t.is(opaque.encodeAutoSync({ formats: [OutputFormat.Png, OutputFormat.Jpeg] }).format, OutputFormat.Jpeg)
+})
+
+test('encodeMany encodes one render to several formats', async (t) => {
+  const small = new Transformer(PNG).resize(256)
+  const [jpeg, webp, png] = await small.encodeMany([
+    { format: OutputFormat.Jpeg, quality: 80 },
+    { format: OutputFormat.Webp, lossless: true },
+    { format: OutputFormat.Png },
+  ])
+  t.deepEqual(jpeg, await small.jpeg(80))
+  t.deepEqual(webp, await small.webpLossless())
+  t.deepEqual(png, await small.png())
+  const [avif] = small.encodeManySync([{ format: OutputFormat.Avif, quality: 60 }])
+  t.is((await new Transformer(avif).metadata()).width, 256)
+  t.throws(() => small.encodeManySync([]))
+  t.throws(() => small.encodeManySync([{ format: OutputFormat.Jpeg, lossless: true }]))
+})
+
+test('generateVariants encodes every width x format from one render', async (t) => {
+  const source = new Transformer(PNG)
+  const { width, height } = await source.metadata()
+  const variants = await source.generateVariants({
+    widths: [200, 800, 400],
+    formats: [{ format: OutputFormat.Webp }, { format: OutputFormat.Jpeg, quality: 70 }],
+  })
+  t.deepEqual(
+    variants.map((v) => [v.width, v.format]),
+    [
+      [200, OutputFormat.Webp],
+      [200, OutputFormat.Jpeg],
+      [800, OutputFormat.Webp],
+      [800, OutputFormat.Jpeg],
+      [400, OutputFormat.Webp],
+      [400, OutputFormat.Jpeg],
+    ],
+  )
+  for (const variant of variants) {
+    t.is(variant.height, Math.round((height * variant.width) / width))
+    t.is(variant.size, variant.buffer.length)
+    t.is((await new Transformer(variant.buffer).metadata()).width, variant.width)
+  }
+  const squares = source.generateVariantsSync({
+    widths: [100, width * 2],
+    formats: [{ format: OutputFormat.Png }],
+    aspectRatio: 1,
+    withoutEnlargement: true,
+  })
+  t.deepEqual(squares.map((v) => [v.width, v.height]), [[100, 100]])
+  const enlarged = source.generateVariantsSync({
+    widths: [width * 2],
+    formats: [{ format: OutputFormat.Png }],
+    withoutEnlargement: true,
+  })
+  t.deepEqual(enlarged, [])
+  t.throws(() => source.generateVariantsSync({ widths: [], formats: [{ format: OutputFormat.Png }] }))
+  const empty = Transformer.fromRgbaPixels(new Uint8Array(0), 0, 0)
+  t.throws(() => empty.generateVariantsSync({ widths: [100], formats: [{ format: OutputFormat.Png }] }))
+})
+
+test('tile cuts a DeepZoom pyramid and writes other layouts to disk', async (t) => {
+  const source = new Transformer(PNG).resize(600, 300, undefined, ResizeFit.Fill)
+  const dzi = await source.tile({ tileSize: 254, overlap: 1 })
+  t.is(dzi.manifestPath, 'image.dzi')
+  t.true(dzi.manifest.includes('<Size Width="600" Height="300"/>'))
+  t.is(dzi.levels, 11)
+  const top = dzi.tiles.filter((tile) => tile.level === 10)
+  t.is(top.length, 6)
+  t.is(top[1].path, 'image_files/10/1_0.jpg')
+  t.is(top[1].width, 256)
+  t.is((await new Transformer(top[1].buffer).metadata()).width, 256)
+  const directory = await fs.mkdtemp(join(tmpdir(), 'tiles-'))
+  const xyz = source.tileSync({ layout: TileLayout.Xyz, format: { format: OutputFormat.Png }, directory })
+  t.is(xyz.tiles.length, 1 + 2 + 6)
+  t.is(xyz.tiles[0].buffer, undefined)
+  const corner = await fs.readFile(join(directory, 'image/2/2/1.png'))
+  t.is((await new Transformer(corner).metadata()).width, 256)
+  t.true((await fs.readFile(join(directory, 'image/tiles.json'), 'utf8')).includes('"maxZoom": 2'))
+  await fs.rm(directory, { recursive: true })
+  t.throws(() => source.tileSync({ layout: TileLayout.Iiif, overlap: 1 }))
+  t.throws(() => Transformer.fromRgbaPixels(new Uint8Array(0), 0, 0).tileSync())
+})
+
+test('packAtlas packs images into one atlas with a frame map', async (t) => {
+  const icons = [
+    new Transformer(PNG).resize(64, 64, undefined, ResizeFit.Fill),
+    new Transformer(PNG).resize(32, 48, undefined, ResizeFit.Fill),
+    await new Transformer(PNG).resize(40, 20, undefined, ResizeFit.Fill).png(),
+  ]
+  const atlas = await packAtlas(icons, { maxWidth: 100, padding: 2, names: ['a', 'b', 'c'], imageName: 'atlas.png' })
+  t.deepEqual(
+    atlas.frames.map((frame) => [frame.name, frame.width, frame.height]),
+    [
+      ['a', 64, 64],
+      ['b', 32, 48],
+      ['c', 40, 20],
+    ],
+  )
+  const meta = await new Transformer(atlas.image).metadata()
+  t.deepEqual([meta.width, meta.height], [atlas.width, atlas.height])
+  t.true(atlas.width <= 100)
+  const map = JSON.parse(atlas.json)
+  t.deepEqual(map.frames.b.frame, { x: atlas.frames[1].x, y: atlas.frames[1].y, w: 32, h: 48 })
+  t.is(map.meta.image, 'atlas.png')
+  const pot = packAtlasSync(icons, { maxWidth: 100, powerOfTwo: true })
+  t.is(pot.width & (pot.width - 1), 0)
+  t.is(pot.height & (pot.height - 1), 0)
+  t.throws(() => packAtlasSync(icons, { maxWidth: 50 }))
+})
+
 // The hardest case: adjustContrast AND a spatial filter staged together. Contrast
 // mangles alpha, but its change is reverted right after it runs, so the LATER blur
 // still feathers the true alpha and opacity scales that feathered result. The earlier
@@ -667,6 +1041,201 @@ test('composite default placement is the centre of the base (#138)', async (t) =
   t.is(raw[3], 255)
 })
 
+test('composite accepts a Transformer with its staged pipeline', async (t) => {
+  const base = Uint8Array.from(Array.from({ length: 4 * 4 }, () => [0, 0, 0, 255]).flat())
+  const top = Transformer.fromRgbaPixels(Uint8Array.from([10, 20, 30, 255]), 1, 1).resize({
+    width: 2,
+    height: 2,
+    fit: ResizeFit.Fill,
+  })
+  const transformer = Transformer.fromRgbaPixels(base, 4, 4).composite(top, { left: 0, top: 0 })
+  const raw = await transformer.rawPixels()
+  // The 1x1 top was resized to 2x2 before compositing: (1,1) is covered, (2,2) is not.
+  t.deepEqual([...raw.subarray(20, 24)], [10, 20, 30, 255])
+  t.deepEqual([...raw.subarray(40, 44)], [0, 0, 0, 255])
+  // The rendered overlay is cached, and reusing it stays idempotent.
+  t.deepEqual(await transformer.rawPixels(), raw)
+})
+
+test('composite accepts raw RGBA pixels and SVG overlays', async (t) => {
+  const base = Uint8Array.from(Array.from({ length: 4 * 4 }, () => [0, 0, 0, 255]).flat())
+  const raw = await Transformer.fromRgbaPixels(base, 4, 4)
+    .composite({ raw: Uint8Array.from([10, 20, 30, 255]), width: 1, height: 1 }, { gravity: Gravity.SouthEast })
+    .rawPixels()
+  t.deepEqual([...raw.subarray(60, 64)], [10, 20, 30, 255])
+
+  // With no size the SVG is rasterized to fit the base: a full-bleed red rect covers every pixel.
+  const svg = '<svg xmlns="http://www.w3.org/2000/svg" width="2" height="2"><rect width="2" height="2" fill="red"/></svg>'
+  const painted = await Transformer.fromRgbaPixels(base, 4, 4).composite({ svg }).rawPixels()
+  t.deepEqual([...painted.subarray(0, 4)], [255, 0, 0, 255])
+  t.deepEqual([...painted.subarray(60, 64)], [255, 0, 0, 255])
+  const corner = await Transformer.fromRgbaPixels(base, 4, 4)
+    .composite({ svg, width: 2 }, { left: 0, top: 0 })
+    .rawPixels()
+  t.deepEqual([...corner.subarray(20, 24)], [255, 0, 0, 255])
+  t.deepEqual([...corner.subarray(40, 44)], [0, 0, 0, 255])
+})
+
+test('composite rejects malformed raw and SVG overlays', (t) => {
+  const base = Transformer.fromRgbaPixels(Uint8Array.from([0, 0, 0, 255]), 1, 1)
+  t.throws(() => base.composite({ raw: Uint8Array.from([1, 2, 3]), width: 1, height: 1 }))
+  t.throws(() => base.composite({ raw: Uint8Array.from([1, 2, 3, 4]) }))
+  t.throws(() => base.composite({ svg: 'not svg' }))
+  t.throws(() => base.composite({}))
+})
+
+test('fromText renders a transparent layer sized to the text', async (t) => {
+  const layer = Transformer.fromText({ text: 'Hello\nworld', fontSize: 20, color: 'red' })
+  const { width, height } = await layer.metadata()
+  t.true(width > 0)
+  t.is(height, 48, 'two 1.2em line boxes')
+  const raw = await layer.rawPixels()
+  let painted = 0
+  for (let i = 0; i < raw.length; i += 4) {
+    if (raw[i + 3] === 255) {
+      t.deepEqual([...raw.subarray(i, i + 3)], [255, 0, 0])
+      painted++
+    }
+  }
+  t.true(painted > 0, 'glyphs are drawn')
+  t.is(raw[3], 0, 'the background stays transparent')
+  const boxes = Transformer.fromText({ text: 'WW', fontSize: 20, fontFile: BOXES_TTF })
+  t.is((await boxes.metadata()).width, 22, 'two box glyphs from `fontFile`')
+})
+
+test('text draws onto the image with gravity and wrapping', async (t) => {
+  const base = Uint8Array.from(Array.from({ length: 200 * 100 }, () => [0, 0, 0, 255]).flat())
+  const raw = await Transformer.fromRgbaPixels(base, 200, 100)
+    .text(
+      { text: 'wrapped across several lines', fontSize: 16, color: 'white', width: 120, align: TextAlign.Center },
+      { gravity: Gravity.SouthEast, blend: BlendMode.Screen },
+    )
+    .rawPixels()
+  let litLeft = 0
+  let litRight = 0
+  for (let y = 0; y < 100; y++) {
+    for (let x = 0; x < 200; x++) {
+      if (raw[(y * 200 + x) * 4] > 128) {
+        if (x < 80) litLeft++
+        else litRight++
+      }
+    }
+  }
+  t.is(litLeft, 0, 'the 120px wrapped layer stays in the right-hand side')
+  t.true(litRight > 0)
+  t.throws(() => Transformer.fromRgbaPixels(base, 200, 100).text({ text: 'x', color: 'nope' }))
+})
+
+test('draw renders shapes at the size of the resized image', async (t) => {
+  const base = Uint8Array.from(Array.from({ length: 40 * 40 }, () => [0, 0, 0, 255]).flat())
+  const raw = await Transformer.fromRgbaPixels(base, 40, 40)
+    .resize({ width: 20, height: 20, fit: ResizeFit.Fill })
+    .draw([
+      { kind: ShapeKind.Rect, x: 0, y: 0, width: 10, height: 10, fill: 'red' },
+      { kind: ShapeKind.Circle, x: 15, y: 15, radius: 4, fill: 'rgba(0, 0, 255, 1)', stroke: 'white', strokeWidth: 1 },
+      {
+        kind: ShapeKind.Rect,
+        x: 10,
+        y: 0,
+        width: 10,
+        height: 10,
+        gradient: {
+          kind: GradientKind.Linear,
+          x1: 10,
+          y1: 0,
+          x2: 20,
+          y2: 0,
+          stops: [
+            { offset: 0, color: 'black' },
+            { offset: 1, color: 'lime' },
+          ],
+        },
+      },
+    ])
+    .rawPixels()
+  t.is(raw.length, 20 * 20 * 4)
+  t.deepEqual([...raw.subarray(0, 4)], [255, 0, 0, 255])
+  t.deepEqual([...raw.subarray((15 * 20 + 15) * 4, (15 * 20 + 15) * 4 + 4)], [0, 0, 255, 255])
+  t.true(raw[(5 * 20 + 18) * 4 + 1] > raw[(5 * 20 + 11) * 4 + 1], 'the gradient brightens to the right')
+  t.deepEqual([...raw.subarray((19 * 20) * 4, (19 * 20) * 4 + 4)], [0, 0, 0, 255], 'undrawn pixels keep the base')
+})
+
+test('draw rejects invalid shapes', (t) => {
+  const base = Transformer.fromRgbaPixels(Uint8Array.from([0, 0, 0, 255]), 1, 1)
+  t.throws(() => base.draw([{ kind: ShapeKind.Circle, x: 1, y: 1 }]))
+  t.throws(() => base.draw([{ kind: ShapeKind.Path, d: 'M 0 0 Q' }]))
+  t.throws(() => base.draw([{ kind: ShapeKind.Polygon, points: [0, 0, 1] }]))
+})
+
+test('mask takes alpha from an image luminance, stretched over the resized image', async (t) => {
+  const base = Uint8Array.from(Array.from({ length: 4 * 4 }, () => [200, 100, 50, 255]).flat())
+  // A 2x1 opaque mask: white on the left half, black on the right half.
+  const mask = await Transformer.fromRgbaPixels(Uint8Array.from([255, 255, 255, 255, 0, 0, 0, 255]), 2, 1).png()
+  const raw = await Transformer.fromRgbaPixels(base, 4, 4).mask(mask).rawPixels()
+  t.deepEqual([...raw.subarray(0, 4)], [200, 100, 50, 255], 'white keeps the pixel')
+  t.is(raw[(3 * 4 + 3) * 4 + 3], 0, 'black hides the pixel')
+
+  const inverted = await Transformer.fromRgbaPixels(base, 4, 4)
+    .mask(Transformer.fromRgbaPixels(Uint8Array.from([0, 0, 0, 64]), 1, 1), {
+      channel: MaskChannel.Alpha,
+      invert: true,
+      mode: MaskMode.Replace,
+    })
+    .rawPixels()
+  t.is(inverted[3], 191)
+})
+
+test('circle and roundCorners clear the corners of the final frame', async (t) => {
+  const base = Uint8Array.from(Array.from({ length: 40 * 20 }, () => [255, 0, 0, 255]).flat())
+  const avatar = Transformer.fromRgbaPixels(base, 40, 20).crop(0, 0, 20, 20).circle()
+  t.is((await avatar.metadata()).colorType, JsColorType.Rgba8)
+  const raw = await avatar.rawPixels()
+  t.is(raw.length, 20 * 20 * 4)
+  t.is(raw[3], 0, 'corner is transparent')
+  t.deepEqual([...raw.subarray((10 * 20 + 10) * 4, (10 * 20 + 10) * 4 + 4)], [255, 0, 0, 255])
+
+  const rounded = await Transformer.fromRgbaPixels(base, 40, 20).roundCorners(6).rawPixels()
+  t.is(rounded[3], 0)
+  t.is(rounded[(10 * 40) * 4 + 3], 255, 'edges between the corners stay opaque')
+  t.throws(() => Transformer.fromRgbaPixels(base, 40, 20).roundCorners(-1))
+})
+
+test('extractChannel and joinChannel move channels between images', async (t) => {
+  const pixels = Uint8Array.from([10, 20, 30, 40, 50, 60, 70, 80])
+  const green = await Transformer.fromRgbaPixels(pixels, 2, 1).extractChannel(Channel.Green).rawPixels()
+  t.deepEqual([...green], [20, 60])
+
+  const rgb = Transformer.fromRgbaPixels(pixels, 2, 1).extractChannel(Channel.Red)
+  const alpha = Transformer.fromRgbaPixels(Uint8Array.from([0, 0, 0, 255, 255, 255, 255, 255]), 2, 1)
+  const joined = Transformer.fromRgbaPixels(pixels, 2, 1).joinChannel([alpha])
+  await t.throwsAsync(() => joined.rawPixels(), { message: /at most 4 channels/ })
+
+  // The extracted channel is joined with the alpha image's luminance.
+  const grayAlpha = rgb.joinChannel([alpha])
+  t.is((await grayAlpha.metadata()).colorType, JsColorType.La8)
+  t.deepEqual([...(await grayAlpha.rawPixels())], [10, 0, 50, 255])
+})
+
+test('shadow, glow and border grow the canvas behind the image', async (t) => {
+  const pixels = Uint8Array.from(Array.from({ length: 4 * 4 }, () => [255, 0, 0, 255]).flat())
+  const shadowed = Transformer.fromRgbaPixels(pixels, 4, 4).shadow({ offsetX: 2, offsetY: 2, blur: 0, color: 'blue' })
+  const { width, height } = await shadowed.metadata()
+  t.deepEqual([width, height], [6, 6])
+  const raw = await shadowed.rawPixels()
+  t.deepEqual([...raw.subarray(0, 4)], [255, 0, 0, 255], 'the image stays on top at the origin')
+  t.deepEqual([...raw.subarray((5 * 6 + 5) * 4, (5 * 6 + 5) * 4 + 4)], [0, 0, 255, 255])
+  t.is(raw[(5 * 6) * 4 + 3], 0, 'uncovered canvas is transparent')
+
+  const bordered = await Transformer.fromRgbaPixels(pixels, 4, 4).border({ width: 2, color: 'white' }).rawPixels()
+  t.is(bordered.length, 8 * 8 * 4)
+  t.deepEqual([...bordered.subarray((4 * 8) * 4, (4 * 8) * 4 + 4)], [255, 255, 255, 255])
+  t.deepEqual([...bordered.subarray((4 * 8 + 4) * 4, (4 * 8 + 4) * 4 + 4)], [255, 0, 0, 255])
+
+  const glowing = await Transformer.fromRgbaPixels(pixels, 4, 4).glow({ blur: 2 }).metadata()
+  t.deepEqual([glowing.width, glowing.height], [16, 16])
+  t.throws(() => Transformer.fromRgbaPixels(pixels, 4, 4).shadow({ blur: -1 }))
+})
+
 // sharp parity: `left` and `top` must be provided together — supplying exactly one throws.
 test('composite throws when only one of left/top is set (#138)', async (t) => {
   const top = Uint8Array.from([10, 20, 30, 255])
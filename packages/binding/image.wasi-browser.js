@@ -59,19 +59,37 @@ const {
 export default __napiModule.exports
 export const Transformer = __napiModule.exports.Transformer
 export const BlendMode = __napiModule.exports.BlendMode
+export const BlurPrecision = __napiModule.exports.BlurPrecision
+export const Channel = __napiModule.exports.Channel
 export const ChromaSubsampling = __napiModule.exports.ChromaSubsampling
+export const compare = __napiModule.exports.compare
+export const compareSync = __napiModule.exports.compareSync
 export const CompressionType = __napiModule.exports.CompressionType
 export const compressJpeg = __napiModule.exports.compressJpeg
 export const compressJpegSync = __napiModule.exports.compressJpegSync
+export const ConvolvePreset = __napiModule.exports.ConvolvePreset
+export const CropStrategy = __napiModule.exports.CropStrategy
 export const FastResizeFilter = __napiModule.exports.FastResizeFilter
 export const FilterType = __napiModule.exports.FilterType
+export const GradientKind = __napiModule.exports.GradientKind
 export const Gravity = __napiModule.exports.Gravity
+export const hammingDistance = __napiModule.exports.hammingDistance
+export const HashAlgorithm = __napiModule.exports.HashAlgorithm
 export const JsColorType = __napiModule.exports.JsColorType
 export const losslessCompressPng = __napiModule.exports.losslessCompressPng
 export const losslessCompressPngSync = __napiModule.exports.losslessCompressPngSync
+export const MaskChannel = __napiModule.exports.MaskChannel
+export const MaskMode = __napiModule.exports.MaskMode
 export const Orientation = __napiModule.exports.Orientation
+export const OutputFormat = __napiModule.exports.OutputFormat
+export const packAtlas = __napiModule.exports.packAtlas
+export const packAtlasSync = __napiModule.exports.packAtlasSync
 export const pngQuantize = __napiModule.exports.pngQuantize
 export const pngQuantizeSync = __napiModule.exports.pngQuantizeSync
 export const PngRowFilter = __napiModule.exports.PngRowFilter
 export const ResizeFilterType = __napiModule.exports.ResizeFilterType
 export const ResizeFit = __napiModule.exports.ResizeFit
+export const ShapeKind = __napiModule.exports.ShapeKind
+export const TargetFormat = __napiModule.exports.TargetFormat
+export const TextAlign = __napiModule.exports.TextAlign
+export const TileLayout = __napiModule.exports.TileLayout
@@ -110,19 +110,37 @@ const { instance: __napiInstance, module: __wasiModule, napiModule: __napiModule
 module.exports = __napiModule.exports
 module.exports.Transformer = __napiModule.exports.Transformer
 module.exports.BlendMode = __napiModule.exports.BlendMode
+module.exports.BlurPrecision = __napiModule.exports.BlurPrecision
+module.exports.Channel = __napiModule.exports.Channel
 module.exports.ChromaSubsampling = __napiModule.exports.ChromaSubsampling
+module.exports.compare = __napiModule.exports.compare
+module.exports.compareSync = __napiModule.exports.compareSync
 module.exports.CompressionType = __napiModule.exports.CompressionType
 module.exports.compressJpeg = __napiModule.exports.compressJpeg
 module.exports.compressJpegSync = __napiModule.exports.compressJpegSync
+module.exports.ConvolvePreset = __napiModule.exports.ConvolvePreset
+module.exports.CropStrategy = __napiModule.exports.CropStrategy
 module.exports.FastResizeFilter = __napiModule.exports.FastResizeFilter
 module.exports.FilterType = __napiModule.exports.FilterType
+module.exports.GradientKind = __napiModule.exports.GradientKind
 module.exports.Gravity = __napiModule.exports.Gravity
+module.exports.hammingDistance = __napiModule.exports.hammingDistance
+module.exports.HashAlgorithm = __napiModule.exports.HashAlgorithm
 module.exports.JsColorType = __napiModule.exports.JsColorType
 module.exports.losslessCompressPng = __napiModule.exports.losslessCompressPng
 module.exports.losslessCompressPngSync = __napiModule.exports.losslessCompressPngSync
+module.exports.MaskChannel = __napiModule.exports.MaskChannel
+module.exports.MaskMode = __napiModule.exports.MaskMode
 module.exports.Orientation = __napiModule.exports.Orientation
+module.exports.OutputFormat = __napiModule.exports.OutputFormat
+module.exports.packAtlas = __napiModule.exports.packAtlas
+module.exports.packAtlasSync = __napiModule.exports.packAtlasSync
 module.exports.pngQuantize = __napiModule.exports.pngQuantize
 module.exports.pngQuantizeSync = __napiModule.exports.pngQuantizeSync
 module.exports.PngRowFilter = __napiModule.exports.PngRowFilter
 module.exports.ResizeFilterType = __napiModule.exports.ResizeFilterType
 module.exports.ResizeFit = __napiModule.exports.ResizeFit
+module.exports.ShapeKind = __napiModule.exports.ShapeKind
+module.exports.TargetFormat = __napiModule.exports.TargetFormat
+module.exports.TextAlign = __napiModule.exports.TextAlign
+module.exports.TileLayout = __napiModule.exports.TileLayout
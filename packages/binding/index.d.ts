@@ -5,8 +5,44 @@ export declare class Transformer {
   /** Support CSS3 color, e.g. rgba(255, 255, 255, .8) */
   static fromSvg(input: string | Uint8Array, background?: string | undefined | null): Transformer
   static fromRgbaPixels(input: Uint8Array | Uint8ClampedArray, width: number, height: number): Transformer
+  /** Render a [BlurHash](https://blurha.sh) string into an opaque `width`x`height` image. */
+  static fromBlurhash(hash: string, width: number, height: number): Transformer
   metadata(withExif?: boolean | undefined | null, signal?: AbortSignal | undefined | null): Promise<Metadata>
   metadataSync(withExif?: boolean | undefined | null): Metadata
+  /**
+   * Per-channel min/max/mean/stdev and histograms, opacity, luminance entropy, a sharpness
+   * estimate and the dominant color of the image as it would be encoded (staged transforms
+   * applied). Handy for rejecting blank or blurry uploads.
+   */
+  stats(signal?: AbortSignal | undefined | null): Promise<Stats>
+  /** Synchronous version of `stats`. */
+  statsSync(): Stats
+  /**
+   * A [BlurHash](https://blurha.sh) placeholder string for the image as it would be encoded
+   * (staged transforms applied). Alpha is ignored.
+   */
+  blurhash(options?: BlurhashOptions | undefined | null, signal?: AbortSignal | undefined | null): Promise<string>
+  /** Synchronous version of `blurhash`. */
+  blurhashSync(options?: BlurhashOptions | undefined | null): string
+  /**
+   * A [ThumbHash](https://evanw.github.io/thumbhash/) placeholder for the image as it would be
+   * encoded (staged transforms applied). Unlike BlurHash it keeps alpha and the aspect ratio.
+   * Images larger than 100x100 are downscaled before hashing.
+   */
+  thumbhash(signal?: AbortSignal | undefined | null): Promise<Buffer>
+  /** Synchronous version of `thumbhash`. */
+  thumbhashSync(): Buffer
+  /**
+   * A perceptual hash of the image as it would be encoded (staged transforms applied), as a
+   * string of `size * size` `'0'`/`'1'` characters, top-left bit first. Near-duplicates have a
+   * small `hammingDistance()`; `BigInt('0b' + hash)` gives the numeric form.
+   */
+  perceptualHash(
+    options?: PerceptualHashOptions | undefined | null,
+    signal?: AbortSignal | undefined | null,
+  ): Promise<string>
+  /** Synchronous version of `perceptualHash`. */
+  perceptualHashSync(options?: PerceptualHashOptions | undefined | null): string
   /**
    * Rotate with exif orientation
    * If the orientation param is not null,
@@ -43,9 +79,12 @@ export declare class Transformer {
   fastResize(options: FastResizeOptions): this
   /**
    * Performs a Gaussian blur on this image.
-   * `sigma` is a measure of how much to blur by.
+   * `sigma` is a measure of how much to blur by; `0` blurs by `0.8`.
+   * `precision` picks the exact kernel or a faster three-box approximation (default `Auto`, by
+   * sigma). Color is premultiplied by alpha while blurring, so transparent areas don't darken
+   * the edges, and large images are blurred on several threads.
    */
-  blur(sigma: number): this
+  blur(sigma: number, precision?: BlurPrecision | undefined | null): this
   /**
    * Performs an unsharpen mask on this image.
    * `sigma` is the amount to blur the image by.
@@ -56,6 +95,12 @@ export declare class Transformer {
   unsharpen(sigma: number, threshold: number): this
   /** Filters this image with the specified 3x3 kernel. */
   filter3x3(kernel: Array<number>): this
+  /**
+   * Convolves this image with a kernel of any size, or a preset.
+   * Works at the image's native depth and leaves alpha as is; next to transparency the kernel is
+   * weighted by alpha, so edges don't darken.
+   */
+  convolve(kernel: ConvolveOptions | ConvolvePreset): this
   /**
    * Adjust the contrast of this image.
    * `contrast` is the amount to adjust the contrast by.
@@ -75,6 +120,53 @@ export declare class Transformer {
    * just like the css webkit filter hue-rotate(180)
    */
   huerotate(hue: number): this
+  /**
+   * Gamma-correct the color channels: each normalized sample becomes `sample ^ (1 / gamma)`.
+   * Values above `1` brighten the midtones, values below darken them.
+   */
+  gamma(gamma: number): this
+  /**
+   * Remap the tonal range like a levels dialog: `inBlack..=inWhite` is stretched to
+   * `outBlack..=outWhite` (defaults `0..=255`) through a midtone `gamma` (default `1`).
+   * All levels are in 8-bit units whatever the image's depth.
+   */
+  levels(
+    inBlack: number,
+    inWhite: number,
+    gamma?: number | undefined | null,
+    outBlack?: number | undefined | null,
+    outWhite?: number | undefined | null,
+  ): this
+  /**
+   * Map the color channels through tone curves given as control points. The curves are
+   * compiled to lookup tables at the image's depth.
+   */
+  curves(options: CurvesOptions): this
+  /** Scale the saturation: `0` is grayscale, `1` leaves the image unchanged, `2` doubles it. */
+  saturation(amount: number): this
+  /**
+   * Like `saturation`, but weighted towards muted colors, so skin tones and already vivid
+   * colors are left mostly alone. `amount` is in `-1..=1`; `0` leaves the image unchanged.
+   */
+  vibrance(amount: number): this
+  /**
+   * Shift the white balance. `temperature` in `-100..=100` cools (negative) or warms (positive)
+   * the image; `tint` in `-100..=100` shifts it towards green (negative) or magenta (positive).
+   */
+  temperature(temperature: number, tint?: number | undefined | null): this
+  /**
+   * Recombine the channels through a color matrix, like sharp's `recomb`: each output channel is
+   * the row's weighted sum of the input channels. A 3x3 matrix mixes RGB and leaves alpha alone;
+   * a 4x4 one mixes RGBA and adds alpha to images without it. Gray images become RGB.
+   */
+  recomb(matrix: Array<Array<number>>): this
+  /**
+   * Tint the image with `color`'s hue while keeping its luminance.
+   * Support CSS3 color, e.g. rgba(255, 255, 255, .8); the color's alpha is ignored.
+   */
+  tint(color: string): this
+  /** Apply the classic sepia color matrix. */
+  sepia(): this
   /**
    * Multiply the image's alpha channel by `factor` (clamped to `0.0..=1.0`),
    * like CSS `opacity`. `1.0` leaves the image unchanged; `0.0` makes it fully
@@ -121,8 +213,74 @@ export declare class Transformer {
    * `Over` at any alpha; for a translucent source or backdrop combined with a separable blend mode
    * (Multiply, Screen, HardLight, etc.), per-pixel values differ from sharp, which uses
    * premultiplied-alpha math.
+   *
+   * `on_top` is an encoded image, another `Transformer` (composited with the pipeline it has
+   * staged at call time), or an `OverlayInput` with raw RGBA pixels or an SVG document. The
+   * overlay is decoded/rendered once and reused by every later encode of this `Transformer`.
+   */
+  composite(onTop: Uint8Array | Transformer | OverlayInput, options?: CompositeOptions | undefined | null): this
+  /**
+   * Draw text onto this image: render `text` with the system fonts (or `fontFile`) into an RGBA
+   * layer and composite it like `composite()`, with any `BlendMode`, `Gravity` or offset. The
+   * layer must fit inside the image; set `width` to wrap long text.
+   */
+  text(text: TextOptions, options?: CompositeOptions | undefined | null): this
+  /**
+   * Draw `shapes` in order onto a transparent layer the size of this image (after the staged
+   * resize/crop), then composite that layer over the image with `blend` (default `Over`).
+   * Coordinates are pixels of the image being drawn on. Like `composite()`, drawing happens at
+   * the image's native depth and keeps its color type.
+   */
+  draw(shapes: Array<Shape>, blend?: BlendMode | undefined | null): this
+  /**
+   * Mask this image with `image`'s alpha or luminance (see `MaskOptions`): the mask is stretched
+   * over the image after the staged resize/crop and multiplies (or replaces) its alpha. The image
+   * is promoted to an alpha-capable type at its own bit depth. `image` accepts the same sources as
+   * `composite()`.
+   */
+  mask(image: Uint8Array | Transformer | OverlayInput, options?: MaskOptions | undefined | null): this
+  /**
+   * Round the corners of this image by `radius` pixels, making them transparent. Applied after
+   * the staged resize/crop, like `mask()`.
+   */
+  roundCorners(radius: number): this
+  /**
+   * Cut this image to the largest centered circle, making everything outside it transparent,
+   * e.g. for avatars. Applied after the staged resize/crop, like `mask()`.
    */
-  composite(onTop: Uint8Array, options?: CompositeOptions | undefined | null): this
+  circle(): this
+  /**
+   * Reduce this image to a single `channel`, as an `L8` image for 8-bit sources and `L16`
+   * otherwise. Applied after `mask()` and before `joinChannel()`, so a single channel can be
+   * given a new alpha.
+   */
+  extractChannel(channel: Channel): this
+  /**
+   * Append one channel per image (its luminance; alpha is ignored) to this image, e.g. to attach
+   * an alpha channel from a separate grayscale image. Joined channels must have the same size as
+   * this image after the staged resize/crop, and the result can have at most 4 channels:
+   * gray + 1 gives gray-alpha, gray + 2 RGB and RGB + 1 RGBA. Bit depth follows this image, with
+   * a float image joined into fewer than 3 channels stored as 16-bit.
+   */
+  joinChannel(images: Array<Uint8Array | Transformer | OverlayInput>): this
+  /**
+   * Draw a drop shadow of this image's shape (its alpha) behind it, growing the canvas to fit.
+   * Applied after the staged resize/crop and masks, so `circle().shadow()` shadows the circle.
+   * The result is RGBA at the image's bit depth. See `ShadowOptions`.
+   */
+  shadow(options?: ShadowOptions | undefined | null): this
+  /**
+   * Draw an outer glow around this image's shape, like an unshifted `shadow()`. See
+   * `GlowOptions`.
+   */
+  glow(options?: GlowOptions | undefined | null): this
+  /**
+   * Stroke the outline of this image's shape (its alpha) with a solid border of `width` pixels
+   * drawn outside it, growing the canvas to fit. Corners of the shape are rounded by the stroke.
+   */
+  border(options: BorderOptions): this
+  /** Render text into a new transparent image sized to fit it. See `TextOptions`. */
+  static fromText(text: TextOptions): Transformer
   /** Return this image's pixels as a native endian byte slice. */
   rawPixels(signal?: AbortSignal | undefined | null): Promise<Buffer>
   /** Return this image's pixels as a native endian byte slice. */
@@ -175,6 +333,99 @@ export declare class Transformer {
   tgaSync(): Buffer
   farbfeld(signal?: AbortSignal | undefined | null): Promise<Buffer>
   farbfeldSync(): Buffer
+  /**
+   * Encode as JPEG, WebP or AVIF at the quality a binary search picks for the targets: the
+   * lowest quality meeting `minSsim`/`maxDeltaE` against the image as staged, capped by
+   * `maxBytes`. Each candidate is decoded and scored, so this costs several encodes.
+   */
+  encodeToTarget(options: EncodeTargetOptions, signal?: AbortSignal | undefined | null): Promise<EncodeTargetResult>
+  /** Synchronous version of `encodeToTarget`. */
+  encodeToTargetSync(options: EncodeTargetOptions): EncodeTargetResult
+  /**
+   * Encode to each candidate format in parallel from one render of the staged pipeline and
+   * return the smallest result, e.g. to serve the best format an `Accept` header allows.
+   * The libuv worker encodes candidates itself, helped by extra threads from a budget of one per
+   * CPU that all concurrent calls share, so a busy pool is not oversubscribed; AVIF candidates
+   * still use the encoder's own `threads` on top.
+   */
+  encodeAuto(
+    options?: EncodeAutoOptions | undefined | null,
+    signal?: AbortSignal | undefined | null,
+  ): Promise<EncodeAutoResult>
+  /** Synchronous version of `encodeAuto`. */
+  encodeAutoSync(options?: EncodeAutoOptions | undefined | null): EncodeAutoResult
+  /**
+   * Apply the staged pipeline once and encode the result to every entry of `outputs` in
+   * parallel. The buffers come back in `outputs` order.
+   */
+  encodeMany(outputs: Array<EncodeSpec>, signal?: AbortSignal | undefined | null): Promise<Array<Buffer>>
+  /** Synchronous version of `encodeMany`. */
+  encodeManySync(outputs: Array<EncodeSpec>): Array<Buffer>
+  /**
+   * Build a responsive image set (e.g. for `<picture>`/`srcset`) from one render of the staged
+   * pipeline: every width is downsampled from the next-wider one, then every width x format is
+   * encoded in parallel. Variants come back in `widths` order, then `formats` order.
+   */
+  generateVariants(options: VariantsOptions, signal?: AbortSignal | undefined | null): Promise<Array<Variant>>
+  /** Synchronous version of `generateVariants`. */
+  generateVariantsSync(options: VariantsOptions): Array<Variant>
+  /**
+   * Cut the staged pipeline's output into a DeepZoom, IIIF, XYZ or Zoomify tile pyramid, each
+   * level downsampled from the previous one, and return the tiles plus the layout's manifest (or
+   * write them all to `directory`).
+   */
+  tile(options?: TileOptions | undefined | null, signal?: AbortSignal | undefined | null): Promise<TileSet>
+  /** Synchronous version of `tile`. */
+  tileSync(options?: TileOptions | undefined | null): TileSet
+}
+
+export interface Atlas {
+  /** The encoded atlas. */
+  image: Buffer
+  width: number
+  height: number
+  /** In input order. */
+  frames: Array<AtlasFrame>
+  /** `frames` in the TexturePacker JSON (hash) format that Phaser, PixiJS and others load. */
+  json: string
+}
+
+export interface AtlasFrame {
+  name: string
+  /** Where the (trimmed) image sits in the atlas. */
+  x: number
+  y: number
+  width: number
+  height: number
+  /**
+   * Whether transparent borders were cut off. `offsetX`/`offsetY` place the frame within the
+   * `sourceWidth`x`sourceHeight` original.
+   */
+  trimmed: boolean
+  offsetX: number
+  offsetY: number
+  sourceWidth: number
+  sourceHeight: number
+}
+
+export interface AtlasOptions {
+  /** Widest atlas to pack into; the height grows as needed. Defaults to `2048`. */
+  maxWidth?: number
+  /** Transparent pixels between frames. Defaults to `0`. */
+  padding?: number
+  /**
+   * Round the atlas width and height up to powers of two, packing within the largest power of
+   * two not above `maxWidth`. Defaults to `false`.
+   */
+  powerOfTwo?: boolean
+  /** Cut fully transparent borders off every image before packing. Defaults to `false`. */
+  trim?: boolean
+  /** Frame names, one per image. Defaults to each image's index. */
+  names?: Array<string>
+  /** Written as `meta.image` into `json`, e.g. `"atlas.png"`. */
+  imageName?: string
+  /** Atlas encoding. Defaults to `{ format: OutputFormat.Png }`. */
+  format?: EncodeSpec
 }
 
 export interface AvifConfig {
@@ -247,6 +498,60 @@ export declare enum BlendMode {
   Exclusion = 24,
 }
 
+export interface BlurhashOptions {
+  /** Horizontal components, `1..=9`. Defaults to `4`. */
+  componentsX?: number
+  /** Vertical components, `1..=9`. Defaults to `3`. */
+  componentsY?: number
+}
+
+/** How `blur()` approximates the Gaussian. */
+export declare enum BlurPrecision {
+  /** `Exact` below sigma 3, `Approximate` from there on, where the exact kernel gets expensive. */
+  Auto = 0,
+  /** A true Gaussian kernel truncated at 3 sigma. Cost grows linearly with sigma. */
+  Exact = 1,
+  /**
+   * Three box-blur passes with the same variance. Constant cost whatever the sigma, within a
+   * few percent of `Exact`.
+   */
+  Approximate = 2,
+}
+
+export interface BorderOptions {
+  /** Stroke width in pixels, outside the image's opaque shape. */
+  width: number
+  /** Support CSS3 color, e.g. rgba(255, 255, 255, .8). Defaults to `black`. */
+  color?: string
+}
+
+/** A single channel of an image, for `extractChannel()`. */
+export declare enum Channel {
+  Red = 0,
+  Green = 1,
+  Blue = 2,
+  /** Fully opaque for an image without alpha. */
+  Alpha = 3,
+  /** Rec. 709 luma of the color channels. */
+  Luminance = 4,
+}
+
+export interface ChannelStats {
+  /** Smallest sample, in the image's native range (`0..=255`, `0..=65535` or float). */
+  min: number
+  /** Largest sample, in the image's native range. */
+  max: number
+  /** Mean sample, in the image's native range. */
+  mean: number
+  /** Population standard deviation, in the image's native range. */
+  stdev: number
+  /**
+   * 256 bins over the channel's full range, whatever the depth (float samples are clamped to
+   * `0.0..=1.0`).
+   */
+  histogram: Array<number>
+}
+
 /**
  * https://en.wikipedia.org/wiki/Chroma_subsampling#Types_of_sampling_and_subsampling
  * https://developer.mozilla.org/en-US/docs/Web/Media/Formats/Video_concepts
@@ -290,6 +595,61 @@ export declare enum ChromaSubsampling {
   Yuv400 = 3,
 }
 
+/**
+ * Compare two images of the same size — encoded bytes, or `Transformer`s with their staged
+ * transforms applied — for PSNR, SSIM, MS-SSIM and CIE76 ΔE, e.g. to measure how lossy an
+ * encode is. The images may differ in color type and depth.
+ */
+export declare function compare(
+  a: Uint8Array | Transformer,
+  b: Uint8Array | Transformer,
+  options?: CompareOptions | undefined | null,
+  signal?: AbortSignal | undefined | null,
+): Promise<Comparison>
+
+export interface CompareOptions {
+  /**
+   * Also return a PNG highlighting, in red, the pixels whose ΔE exceeds `threshold` over a
+   * faded grayscale copy of the first image. Defaults to `false`.
+   */
+  diff?: boolean
+  /**
+   * ΔE above which a pixel counts as different, for `diffPixels` and the diff image. Defaults
+   * to `2.3`, about the smallest difference a viewer notices.
+   */
+  threshold?: number
+}
+
+/** Synchronous version of `compare`. */
+export declare function compareSync(
+  a: Uint8Array | Transformer,
+  b: Uint8Array | Transformer,
+  options?: CompareOptions | undefined | null,
+): Comparison
+
+export interface Comparison {
+  /**
+   * Peak signal-to-noise ratio in dB over the color channels (and alpha when either image has
+   * it). `Infinity` for identical images; above ~40 is hard to tell apart.
+   */
+  psnr: number
+  /** Structural similarity of the luminance, `1` for identical images. */
+  ssim: number
+  /**
+   * Multi-scale SSIM over up to five scales, which tracks perceived quality across viewing
+   * distances better than `ssim`.
+   */
+  msSsim: number
+  /** Largest CIE76 ΔE between corresponding pixels. */
+  maxDeltaE: number
+  /** Mean CIE76 ΔE over all pixels. */
+  meanDeltaE: number
+  /** Pixels whose ΔE exceeds `threshold`. */
+  diffPixels: number
+  /** PNG-encoded diff image, when `diff` was set. */
+  diff?: Buffer
+}
+
 export interface CompositeOptions {
   /**
    * Pixel offset from the top edge. Provide both `top` and `left` together;
@@ -337,6 +697,169 @@ export declare function compressJpeg(
 
 export declare function compressJpegSync(input: Uint8Array, options?: JpegCompressOptions | undefined | null): Buffer
 
+export interface ConvolveOptions {
+  /** Kernel width in taps. */
+  width: number
+  /** Kernel height in taps. */
+  height: number
+  /**
+   * `width * height` weights, row by row. The center tap (`width / 2`, `height / 2`) lands on
+   * the output pixel.
+   */
+  kernel: Array<number>
+  /** The weighted sum is divided by this. Defaults to the kernel's sum, or `1` when that is `0`. */
+  scale?: number
+  /** Added after scaling, in 8-bit units (`0..=255`) whatever the image's depth. Defaults to `0`. */
+  offset?: number
+}
+
+/** Ready-made kernels for `convolve()`. */
+export declare enum ConvolvePreset {
+  /** 3x3 sharpen: 5 at the center, -1 on the four sides. */
+  Sharpen = 0,
+  /** 3x3 emboss, lit from the top left. */
+  Emboss = 1,
+  /** Sobel edge detection: the gradient magnitude of the horizontal and vertical kernels. */
+  Sobel = 2,
+  /** Prewitt edge detection: like `Sobel`, with unweighted rows and columns. */
+  Prewitt = 3,
+  /** 3x3 Laplacian (4 at the center, -1 on the four sides): bright on the light side of edges. */
+  Laplacian = 4,
+}
+
+/** The region of the (rotated) source image kept by a `ResizeFit.Cover` resize. */
+export interface CropBox {
+  left: number
+  top: number
+  width: number
+  height: number
+}
+
+/** Content-aware anchor for `ResizeFit.Cover`. Takes precedence over `position` when set. */
+export declare enum CropStrategy {
+  /**
+   * Keep the region with the highest luminance entropy, trimming the
+   * least busy edge strip until the crop fits (like sharp's `entropy`).
+   */
+  Entropy = 0,
+  /**
+   * Keep the region with the most edges, skin tones and saturated
+   * color (like sharp's `attention`).
+   */
+  Attention = 1,
+}
+
+/**
+ * A control point of a `curves()` curve, both coordinates in 8-bit units (`0..=255`) whatever
+ * the image's depth.
+ */
+export interface CurvePoint {
+  x: number
+  y: number
+}
+
+/**
+ * Tone curves for `curves()`. Each needs at least two points with increasing `x`; between them
+ * the curve is a monotone cubic spline (no overshoot), outside them it is flat.
+ */
+export interface CurvesOptions {
+  /** Applied to every color channel (and to gray images' luma), before the per-channel curves. */
+  all?: Array<CurvePoint>
+  red?: Array<CurvePoint>
+  green?: Array<CurvePoint>
+  blue?: Array<CurvePoint>
+}
+
+export interface DominantColor {
+  r: number
+  g: number
+  b: number
+}
+
+export interface EncodeAutoOptions {
+  /**
+   * Candidate formats, e.g. the ones the client's `Accept` header allows. JPEG is skipped for
+   * images with transparent pixels unless it is the only candidate. Defaults to
+   * `[Avif, Webp, Jpeg]`.
+   */
+  formats?: Array<OutputFormat>
+  /** Quality of the lossy candidates, `1..=100`. Defaults to each encoder's own default. */
+  quality?: number
+  /**
+   * Quality-match the lossy candidates instead of using one `quality` for all: each is encoded
+   * at the lowest quality whose SSIM against the image reaches this value (like
+   * `encodeToTarget()`), so the smallest result is also no worse than the others.
+   */
+  minSsim?: number
+  /**
+   * Also race lossless WebP (when `Webp` is a candidate), and let lossless results win ties.
+   * PNG is always lossless. Defaults to `false`.
+   */
+  preferLossless?: boolean
+}
+
+export interface EncodeAutoResult {
+  format: OutputFormat
+  /** Whether `buffer` is lossless (PNG or lossless WebP). */
+  lossless: boolean
+  /** The encoder quality, for lossy results encoded with an explicit or searched quality. */
+  quality?: number
+  /** SSIM against the image, when `minSsim` was set and the result is lossy. */
+  ssim?: number
+  buffer: Buffer
+}
+
+export interface EncodeSpec {
+  format: OutputFormat
+  /**
+   * Quality of `Jpeg`, `Webp` and `Avif` output, `1..=100`. Defaults to `90` for JPEG and WebP,
+   * and to the AVIF encoder's default.
+   */
+  quality?: number
+  /** Lossless WebP instead of lossy. Only for `OutputFormat.Webp`. */
+  lossless?: boolean
+  /** Only for `OutputFormat.Png`. */
+  png?: PngEncodeOptions
+  /** Only for `OutputFormat.Avif`; `quality` overrides `avif.quality`. */
+  avif?: AvifConfig
+}
+
+export interface EncodeTargetOptions {
+  format: TargetFormat
+  /**
+   * Largest acceptable output in bytes. A hard limit: when it conflicts with `minSsim` or
+   * `maxDeltaE`, the size wins and `targetMet` is `false`.
+   */
+  maxBytes?: number
+  /** Smallest acceptable SSIM against the source, e.g. `0.98`. */
+  minSsim?: number
+  /** Largest acceptable CIE76 ΔE between any source pixel and its encoded counterpart. */
+  maxDeltaE?: number
+  /** Lowest quality to consider, `1..=100`. Defaults to `1`. */
+  minQuality?: number
+  /** Highest quality to consider, `1..=100`. Defaults to `100`. */
+  maxQuality?: number
+  /**
+   * Base AVIF settings (`speed`, `chromaSubsampling`, ...); `quality` is searched. Only for
+   * `TargetFormat.Avif`.
+   */
+  avif?: AvifConfig
+}
+
+export interface EncodeTargetResult {
+  /** The chosen encoder quality. */
+  quality: number
+  buffer: Buffer
+  /** SSIM of `buffer` against the source, when `minSsim` or `maxDeltaE` was set. */
+  ssim?: number
+  /** Largest CIE76 ΔE of `buffer` against the source, when `minSsim` or `maxDeltaE` was set. */
+  maxDeltaE?: number
+  /** Whether every requested target holds for `buffer`. */
+  targetMet: boolean
+  /** How many qualities were encoded during the search. */
+  attempts: number
+}
+
 export declare enum FastResizeFilter {
   /**
    * Each pixel of source image contributes to one pixel of the
@@ -382,6 +905,27 @@ export interface FastResizeOptions {
   height?: number
   filter?: FastResizeFilter
   fit?: ResizeFit
+  /**
+   * Which part of the image `ResizeFit.Cover` keeps, or where `ResizeFit.Contain` places it.
+   * Defaults to `Center`.
+   */
+  position?: Gravity
+  /** Content-aware crop anchor for `ResizeFit.Cover`; overrides `position`. */
+  strategy?: CropStrategy
+  /**
+   * Letterbox color for `ResizeFit.Contain`. Support CSS3 color, e.g. rgba(255, 255, 255, .8).
+   * Defaults to opaque black.
+   */
+  background?: string
+  /** Leave the image untouched if the resize would enlarge it on either axis. */
+  withoutEnlargement?: boolean
+  /** Leave the image untouched if the resize would shrink it on either axis. */
+  withoutReduction?: boolean
+  /**
+   * Resample in linear light instead of on gamma-encoded sRGB values, which keeps fine detail
+   * and high-contrast edges from darkening on downscale. Float images are already linear.
+   */
+  linearLight?: boolean
 }
 
 export declare enum FilterType {
@@ -409,6 +953,40 @@ export declare enum FilterType {
  * Where to anchor the overlay relative to the base image when no explicit
  * `left`/`top` is given.
  */
+export interface GlowOptions {
+  /** Gaussian blur sigma in pixels. Defaults to `8`. */
+  blur?: number
+  /** Grow the glow's shape by this many pixels before blurring. Defaults to `0`. */
+  spread?: number
+  /** Support CSS3 color, e.g. rgba(255, 255, 255, .8). Defaults to `white`. */
+  color?: string
+}
+
+export interface Gradient {
+  /** Defaults to `Linear`. */
+  kind?: GradientKind
+  x1: number
+  y1: number
+  x2?: number
+  y2?: number
+  radius?: number
+  stops: Array<GradientStop>
+}
+
+export declare enum GradientKind {
+  /** From `(x1, y1)` to `(x2, y2)`. */
+  Linear = 0,
+  /** Centered on `(x1, y1)` with `radius`. */
+  Radial = 1,
+}
+
+export interface GradientStop {
+  /** Position along the gradient, `0.0..=1.0`. */
+  offset: number
+  /** Support CSS3 color, e.g. rgba(255, 255, 255, .8). */
+  color: string
+}
+
 export declare enum Gravity {
   /** Center of the base image. */
   Center = 0,
@@ -430,6 +1008,32 @@ export declare enum Gravity {
   NorthWest = 8,
 }
 
+/**
+ * Number of differing bits between two perceptual hashes of the same size. `0` means
+ * (perceptually) identical; for 64-bit hashes, up to ~10 usually means the same picture.
+ */
+export declare function hammingDistance(a: string, b: string): number
+
+/** How `perceptualHash()` turns the image into bits. */
+export declare enum HashAlgorithm {
+  /**
+   * aHash: each bit is whether a cell of the `size`x`size` thumbnail is brighter than the mean.
+   * Fastest, but sensitive to gamma and contrast changes.
+   */
+  Average = 0,
+  /**
+   * dHash: each bit is whether a cell is brighter than its right neighbour. Robust to brightness
+   * and contrast changes.
+   */
+  Difference = 1,
+  /**
+   * pHash: each bit is whether a low-frequency DCT coefficient of a `4 * size` thumbnail is
+   * above the median of all but the DC one. Slowest, and the most robust to resizing,
+   * compression and small edits.
+   */
+  Dct = 2,
+}
+
 /**
  * Options for HEIC encoding. Decode/encode delegate to the OS HEVC codec — Apple ImageIO on macOS,
  * the Windows Imaging Component (WIC) on Windows — so this package ships no HEVC codec. Encoding is
@@ -499,6 +1103,36 @@ export declare function losslessCompressPng(
 
 export declare function losslessCompressPngSync(input: Buffer, options?: PNGLosslessOptions | undefined | null): Buffer
 
+/** Which channel of a mask image becomes the alpha of the masked image. */
+export declare enum MaskChannel {
+  /** The mask's alpha channel. */
+  Alpha = 0,
+  /** The mask's luminance, so an opaque black-and-white image works as a mask. */
+  Luminance = 1,
+}
+
+/** How a mask combines with the alpha the image already has. */
+export declare enum MaskMode {
+  /** `alpha * mask`: existing transparency is kept. */
+  Multiply = 0,
+  /** `mask`: existing transparency is discarded. */
+  Replace = 1,
+}
+
+export interface MaskOptions {
+  /** Defaults to `Alpha` when the mask has an alpha channel and `Luminance` otherwise. */
+  channel?: MaskChannel
+  /** Use `1 - mask`, hiding the image where the mask is opaque/bright. */
+  invert?: boolean
+  /**
+   * Stretch the mask to the size of the image (default `true`). When `false` the mask is placed
+   * at the top-left corner at its own size, and the image outside it is hidden.
+   */
+  resizeToFit?: boolean
+  /** Defaults to `Multiply`. */
+  mode?: MaskMode
+}
+
 export interface Metadata {
   width: number
   height: number
@@ -506,6 +1140,11 @@ export interface Metadata {
   orientation?: number
   format: string
   colorType: JsColorType
+  /**
+   * The source region kept by a staged `ResizeFit.Cover` resize, in the coordinates of the
+   * (rotated) input. Absent when no cover resize is staged.
+   */
+  cropBox?: CropBox
 }
 
 export declare enum Orientation {
@@ -520,6 +1159,54 @@ export declare enum Orientation {
   Rotate270Cw = 8,
 }
 
+/** Web formats for `encodeAuto()`, `encodeMany()` and `generateVariants()`. */
+export declare enum OutputFormat {
+  Jpeg = 0,
+  Png = 1,
+  Webp = 2,
+  Avif = 3,
+}
+
+/** A non-encoded `composite()` source: raw pixels or an SVG document. */
+export interface OverlayInput {
+  /**
+   * Straight (non-premultiplied) RGBA8 pixels, `width * height * 4` bytes. Requires `width` and
+   * `height`.
+   */
+  raw?: Uint8Array
+  /**
+   * An SVG document, rasterized at `width`x`height`. A missing dimension follows the SVG's
+   * aspect ratio; with neither set the SVG is rasterized at the largest size that fits the image
+   * it is composited onto.
+   */
+  svg?: string
+  width?: number
+  height?: number
+}
+
+/**
+ * Bin-pack images (encoded bytes or `Transformer`s, with their staged pipelines) into one
+ * texture atlas, e.g. a sprite sheet, and return it with a frame map.
+ */
+export declare function packAtlas(
+  images: Array<Uint8Array | Transformer>,
+  options?: AtlasOptions | undefined | null,
+  signal?: AbortSignal | undefined | null,
+): Promise<Atlas>
+
+/** Synchronous version of `packAtlas`. */
+export declare function packAtlasSync(
+  images: Array<Uint8Array | Transformer>,
+  options?: AtlasOptions | undefined | null,
+): Atlas
+
+export interface PerceptualHashOptions {
+  /** Defaults to `HashAlgorithm.Dct`. */
+  algorithm?: HashAlgorithm
+  /** Hash side, `2..=32`; the hash has `size * size` bits. Defaults to `8` (64 bits). */
+  size?: number
+}
+
 export interface PngEncodeOptions {
   /** Default is `CompressionType::Default` */
   compressionType?: CompressionType
@@ -698,6 +1385,17 @@ export declare enum ResizeFit {
    * resize the image to be as large as possible while ensuring its dimensions are less than or equal to both those specified.
    */
   Inside = 2,
+  /**
+   * Preserving aspect ratio
+   * fit the image inside both provided dimensions, then letterbox it to exactly that size
+   * with `background`, placed by `position`.
+   */
+  Contain = 3,
+  /**
+   * Preserving aspect ratio
+   * resize the image to be as small as possible while ensuring its dimensions are greater than or equal to both those specified.
+   */
+  Outside = 4,
 }
 
 export interface ResizeOptions {
@@ -705,4 +1403,256 @@ export interface ResizeOptions {
   height?: number
   filter?: ResizeFilterType
   fit?: ResizeFit
+  /**
+   * Which part of the image `ResizeFit.Cover` keeps, or where `ResizeFit.Contain` places it.
+   * Defaults to `Center`.
+   */
+  position?: Gravity
+  /** Content-aware crop anchor for `ResizeFit.Cover`; overrides `position`. */
+  strategy?: CropStrategy
+  /**
+   * Letterbox color for `ResizeFit.Contain`. Support CSS3 color, e.g. rgba(255, 255, 255, .8).
+   * Defaults to opaque black.
+   */
+  background?: string
+  /** Leave the image untouched if the resize would enlarge it on either axis. */
+  withoutEnlargement?: boolean
+  /** Leave the image untouched if the resize would shrink it on either axis. */
+  withoutReduction?: boolean
+  /**
+   * Resample in linear light instead of on gamma-encoded sRGB values, which keeps fine detail
+   * and high-contrast edges from darkening on downscale. Float images are already linear.
+   */
+  linearLight?: boolean
+}
+
+/** A shape for `Transformer.draw`, in the pixel coordinates of the image it is drawn onto. */
+export interface ShadowOptions {
+  /** Horizontal offset in pixels. Defaults to `4`. */
+  offsetX?: number
+  /** Vertical offset in pixels. Defaults to `4`. */
+  offsetY?: number
+  /** Gaussian blur sigma in pixels. Defaults to `4`. */
+  blur?: number
+  /** Grow the shadow's shape by this many pixels before blurring. Defaults to `0`. */
+  spread?: number
+  /** Support CSS3 color, e.g. rgba(255, 255, 255, .8). Defaults to `rgba(0, 0, 0, 0.5)`. */
+  color?: string
+}
+
+export interface Shape {
+  kind: ShapeKind
+  /** Top-left corner of a `Rect`/`RoundedRect`, or the center of a `Circle`. */
+  x?: number
+  y?: number
+  width?: number
+  height?: number
+  /** Corner radius of a `Rect`/`RoundedRect`, or the radius of a `Circle`. */
+  radius?: number
+  /** Flat `[x0, y0, x1, y1, ...]` vertices of a `Line` or `Polygon`. */
+  points?: Array<number>
+  /** SVG path data of a `Path`. */
+  d?: string
+  /** Fill color. Support CSS3 color, e.g. rgba(255, 255, 255, .8). */
+  fill?: string
+  /** Fill with a gradient instead of `fill`. */
+  gradient?: Gradient
+  /** Outline color. Support CSS3 color. */
+  stroke?: string
+  /** Outline width in pixels. Defaults to 1. */
+  strokeWidth?: number
+}
+
+export declare enum ShapeKind {
+  /** `x`, `y`, `width`, `height`; `radius` rounds the corners. */
+  Rect = 0,
+  /** A `Rect` whose corners are rounded by `radius` (required). */
+  RoundedRect = 1,
+  /** Centered on `x`, `y` with `radius`. */
+  Circle = 2,
+  /** A polyline through `points`; stroked only. */
+  Line = 3,
+  /** A closed shape through `points`. */
+  Polygon = 4,
+  /** SVG path data in `d`, e.g. `"M 0 0 L 10 10 Z"`. */
+  Path = 5,
+}
+
+export interface Stats {
+  /**
+   * One entry per channel, in the image's order (`L`, `LA`, `RGB` or `RGBA`). NaN samples are
+   * skipped.
+   */
+  channels: Array<ChannelStats>
+  /** Whether every pixel is fully opaque. Always `true` for images without alpha. */
+  isOpaque: boolean
+  /**
+   * Shannon entropy of the 8-bit luminance histogram in bits, from `0` (a single flat tone) to
+   * `8`.
+   */
+  entropy: number
+  /**
+   * Variance of the Laplacian of the 8-bit luminance. Higher is sharper; blank and blurry
+   * images score low (below ~100 is a common blur threshold for photos).
+   */
+  sharpness: number
+  /**
+   * The most common visible color, clustered perceptually (median cut, nearest in CIELAB) on a
+   * thumbnail. Absent when the image is fully transparent.
+   */
+  dominant?: DominantColor
+}
+
+/** Lossy formats whose quality `encodeToTarget()` can search. */
+export declare enum TargetFormat {
+  Jpeg = 0,
+  Webp = 1,
+  Avif = 2,
+}
+
+/** Horizontal alignment of the lines of a text layer. */
+export declare enum TextAlign {
+  Left = 0,
+  Center = 1,
+  Right = 2,
+}
+
+export interface TextOptions {
+  /** The text to draw. `\n` starts a new line. */
+  text: string
+  /**
+   * CSS font family list, e.g. `"Helvetica, Arial, sans-serif"`. Defaults to the family of
+   * `fontFile`, or `sans-serif`.
+   */
+  fontFamily?: string
+  /**
+   * A TTF/OTF/TTC font to draw with. The system fonts of the families `fontFamily` lists and of
+   * the generic families stay available as fallbacks.
+   */
+  fontFile?: Uint8Array
+  /** Font size in pixels. Defaults to 16. */
+  fontSize?: number
+  /** Support CSS3 color, e.g. rgba(255, 255, 255, .8). Defaults to opaque black. */
+  color?: string
+  /** Defaults to `Left`. */
+  align?: TextAlign
+  /**
+   * Wrap lines at word boundaries so none is wider than `width` pixels. This is also the width
+   * the lines are aligned in; without it the widest line sets the layer width.
+   */
+  width?: number
+  /** Distance between baselines, as a multiple of `fontSize`. Defaults to 1.2. */
+  lineHeight?: number
+  /** Outline color, drawn behind the fill. Support CSS3 color. */
+  strokeColor?: string
+  /** Outline width in pixels. Defaults to 1 when `strokeColor` is set. */
+  strokeWidth?: number
+  /** Drop shadow color. Support CSS3 color. */
+  shadowColor?: string
+  /** Shadow offset in pixels. Defaults to 2. */
+  shadowOffsetX?: number
+  /** Shadow offset in pixels. Defaults to 2. */
+  shadowOffsetY?: number
+  /** Gaussian blur (standard deviation) of the shadow in pixels. Defaults to 2. */
+  shadowBlur?: number
+}
+
+export interface Tile {
+  /** Relative to the tile set root, e.g. `image_files/12/3_4.jpg`. */
+  path: string
+  /** Zoom level, `0` being the smallest. */
+  level: number
+  column: number
+  row: number
+  width: number
+  height: number
+  /** The encoded tile, absent when it was written to `directory`. */
+  buffer?: Buffer
+}
+
+/** Directory layout and manifest of a `tile()` pyramid. */
+export declare enum TileLayout {
+  /**
+   * `{name}.dzi` and `{name}_files/{level}/{column}_{row}.{ext}`, levels down to 1x1 (e.g.
+   * OpenSeadragon).
+   */
+  DeepZoom = 0,
+  /**
+   * IIIF Image API 3.0 level 0: `{name}/info.json` and canonical tile URLs
+   * `{name}/{region}/{size}/0/default.{ext}`.
+   */
+  Iiif = 1,
+  /**
+   * `{name}/{z}/{x}/{y}.{ext}` with zoom 0 a single tile and edge tiles padded to full tiles
+   * (e.g. Leaflet or OpenLayers), plus a `{name}/tiles.json` summary.
+   */
+  Xyz = 2,
+  /** `{name}/ImageProperties.xml` and `{name}/TileGroup{n}/{z}-{x}-{y}.{ext}`. */
+  Zoomify = 3,
+}
+
+export interface TileOptions {
+  /** Tile side in pixels. Defaults to `256`. */
+  tileSize?: number
+  /**
+   * Pixels each tile repeats from its neighbours. Only for `TileLayout.DeepZoom`; defaults to
+   * `0`.
+   */
+  overlap?: number
+  /** Defaults to `TileLayout.DeepZoom`. */
+  layout?: TileLayout
+  /** Tile encoding. Defaults to `{ format: OutputFormat.Jpeg }`. */
+  format?: EncodeSpec
+  /** First path segment of every tile and of the manifest. Defaults to `"image"`. */
+  name?: string
+  /**
+   * The image service URI written as `id` into `info.json`. Only for `TileLayout.Iiif`; defaults
+   * to `name`.
+   */
+  id?: string
+  /**
+   * Color `TileLayout.Xyz` edge tiles are padded with. Support CSS3 color; defaults to
+   * transparent (black in JPEG).
+   */
+  background?: string
+  /**
+   * Write the tiles and the manifest under this directory (created as needed) instead of
+   * returning the tile buffers.
+   */
+  directory?: string
+}
+
+export interface TileSet {
+  /** Where the manifest belongs, relative to the tile set root, e.g. `image.dzi`. */
+  manifestPath: string
+  /** The `.dzi`, `info.json`, `tiles.json` or `ImageProperties.xml` contents. */
+  manifest: string
+  levels: number
+  /** Smallest level first, each row by row. */
+  tiles: Array<Tile>
+}
+
+export interface Variant {
+  width: number
+  height: number
+  format: OutputFormat
+  /** `buffer.length`, for srcset manifests that drop the buffers. */
+  size: number
+  buffer: Buffer
+}
+
+export interface VariantsOptions {
+  /** Output widths, in any order; duplicates are dropped. */
+  widths: Array<number>
+  /** Every width is encoded once per entry. */
+  formats: Array<EncodeSpec>
+  /** Width / height of every variant, e.g. `16 / 9`. Defaults to the image's own aspect ratio. */
+  aspectRatio?: number
+  /** How the image fills the `aspectRatio` box. Only with `aspectRatio`; defaults to `Cover`. */
+  fit?: ResizeFit
+  /**
+   * Skip widths that would enlarge the image; when every width would, the result is an empty
+   * array. Defaults to `false`.
+   */
+  withoutEnlargement?: boolean
 }
@@ -589,19 +589,37 @@ if (!nativeBinding) {
 module.exports = nativeBinding
 module.exports.Transformer = nativeBinding.Transformer
 module.exports.BlendMode = nativeBinding.BlendMode
+module.exports.BlurPrecision = nativeBinding.BlurPrecision
+module.exports.Channel = nativeBinding.Channel
 module.exports.ChromaSubsampling = nativeBinding.ChromaSubsampling
+module.exports.compare = nativeBinding.compare
+module.exports.compareSync = nativeBinding.compareSync
 module.exports.CompressionType = nativeBinding.CompressionType
 module.exports.compressJpeg = nativeBinding.compressJpeg
 module.exports.compressJpegSync = nativeBinding.compressJpegSync
+module.exports.ConvolvePreset = nativeBinding.ConvolvePreset
+module.exports.CropStrategy = nativeBinding.CropStrategy
 module.exports.FastResizeFilter = nativeBinding.FastResizeFilter
 module.exports.FilterType = nativeBinding.FilterType
+module.exports.GradientKind = nativeBinding.GradientKind
 module.exports.Gravity = nativeBinding.Gravity
+module.exports.hammingDistance = nativeBinding.hammingDistance
+module.exports.HashAlgorithm = nativeBinding.HashAlgorithm
 module.exports.JsColorType = nativeBinding.JsColorType
 module.exports.losslessCompressPng = nativeBinding.losslessCompressPng
 module.exports.losslessCompressPngSync = nativeBinding.losslessCompressPngSync
+module.exports.MaskChannel = nativeBinding.MaskChannel
+module.exports.MaskMode = nativeBinding.MaskMode
 module.exports.Orientation = nativeBinding.Orientation
+module.exports.OutputFormat = nativeBinding.OutputFormat
+module.exports.packAtlas = nativeBinding.packAtlas
+module.exports.packAtlasSync = nativeBinding.packAtlasSync
 module.exports.pngQuantize = nativeBinding.pngQuantize
 module.exports.pngQuantizeSync = nativeBinding.pngQuantizeSync
 module.exports.PngRowFilter = nativeBinding.PngRowFilter
 module.exports.ResizeFilterType = nativeBinding.ResizeFilterType
 module.exports.ResizeFit = nativeBinding.ResizeFit
+module.exports.ShapeKind = nativeBinding.ShapeKind
+module.exports.TargetFormat = nativeBinding.TargetFormat
+module.exports.TextAlign = nativeBinding.TextAlign
+module.exports.TileLayout = nativeBinding.TileLayout
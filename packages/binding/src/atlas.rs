@@ -0,0 +1,443 @@
+use image::{ColorType, DynamicImage, ImageBuffer};
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::encode_auto::EncodeSpec;
+use crate::utils::json_string;
+
+#[napi(object)]
+pub struct AtlasOptions {
+  /// Widest atlas to pack into; the height grows as needed. Defaults to `2048`.
+  pub max_width: Option<u32>,
+  /// Transparent pixels between frames. Defaults to `0`.
+  pub padding: Option<u32>,
+  /// Round the atlas width and height up to powers of two, packing within the largest power of
+  /// two not above `maxWidth`. Defaults to `false`.
+  pub power_of_two: Option<bool>,
+  /// Cut fully transparent borders off every image before packing. Defaults to `false`.
+  pub trim: Option<bool>,
+  /// Frame names, one per image. Defaults to each image's index.
+  pub names: Option<Vec<String>>,
+  /// Written as `meta.image` into `json`, e.g. `"atlas.png"`.
+  pub image_name: Option<String>,
+  /// Atlas encoding. Defaults to `{ format: OutputFormat.Png }`.
+  pub format: Option<EncodeSpec>,
+}
+
+#[napi(object)]
+pub struct AtlasFrame {
+  pub name: String,
+  /// Where the (trimmed) image sits in the atlas.
+  pub x: u32,
+  pub y: u32,
+  pub width: u32,
+  pub height: u32,
+  /// Whether transparent borders were cut off. `offsetX`/`offsetY` place the frame within the
+  /// `sourceWidth`x`sourceHeight` original.
+  pub trimmed: bool,
+  pub offset_x: u32,
+  pub offset_y: u32,
+  pub source_width: u32,
+  pub source_height: u32,
+}
+
+#[napi(object)]
+pub struct Atlas {
+  /// The encoded atlas.
+  pub image: Buffer,
+  pub width: u32,
+  pub height: u32,
+  /// In input order.
+  pub frames: Vec<AtlasFrame>,
+  /// `frames` in the TexturePacker JSON (hash) format that Phaser, PixiJS and others load.
+  pub json: String,
+}
+
+pub struct AtlasOutput {
+  pub(crate) bytes: Vec<u8>,
+  pub(crate) width: u32,
+  pub(crate) height: u32,
+  pub(crate) frames: Vec<AtlasFrame>,
+  pub(crate) json: String,
+}
+
+impl From<AtlasOutput> for Atlas {
+  fn from(output: AtlasOutput) -> Self {
+    Atlas {
+      image: output.bytes.into(),
+      width: output.width,
+      height: output.height,
+      frames: output.frames,
+      json: output.json,
+    }
+  }
+}
+
+/// An input image, trimmed when asked to, and where it came from.
+pub(crate) struct Sprite {
+  pub(crate) image: DynamicImage,
+  offset: (u32, u32),
+  source: (u32, u32),
+}
+
+/// The validated `AtlasOptions`, minus the encoder.
+pub(crate) struct AtlasPlan {
+  width: u32,
+  padding: u32,
+  power_of_two: bool,
+  trim: bool,
+  names: Vec<String>,
+  image_name: Option<String>,
+}
+
+impl AtlasPlan {
+  pub(crate) fn new(options: &AtlasOptions, count: usize) -> Result<Self> {
+    let invalid = |message: String| Err(Error::new(Status::InvalidArg, message));
+    if count == 0 {
+      return invalid("packAtlas: expected at least one image".to_owned());
+    }
+    let max_width = options.max_width.unwrap_or(2048);
+    if max_width == 0 {
+      return invalid("packAtlas: `maxWidth` must be positive".to_owned());
+    }
+    let power_of_two = options.power_of_two.unwrap_or(false);
+    let names = match &options.names {
+      Some(names) if names.len() != count => {
+        return invalid(format!(
+          "packAtlas: expected {count} `names`, got {}",
+          names.len()
+        ));
+      }
+      Some(names) => names.clone(),
+      None => (0..count).map(|index| index.to_string()).collect(),
+    };
+    if let Some((index, name)) = names
+      .iter()
+      .enumerate()
+      .find(|(index, name)| names[..*index].contains(name))
+    {
+      return invalid(format!(
+        "packAtlas: frame name {name:?} (image {index}) is not unique"
+      ));
+    }
+    Ok(AtlasPlan {
+      width: if power_of_two {
+        1 << max_width.ilog2()
+      } else {
+        max_width
+      },
+      padding: options.padding.unwrap_or(0),
+      power_of_two,
+      trim: options.trim.unwrap_or(false),
+      names,
+      image_name: options.image_name.clone(),
+    })
+  }
+
+  pub(crate) fn sprite(&self, image: &DynamicImage) -> Sprite {
+    let source = (image.width(), image.height());
+    match self.trim.then(|| opaque_bounds(image)).flatten() {
+      Some((x, y, width, height)) => Sprite {
+        image: image.crop_imm(x, y, width, height),
+        offset: (x, y),
+        source,
+      },
+      None => Sprite {
+        image: image.clone(),
+        offset: (0, 0),
+        source,
+      },
+    }
+  }
+
+  /// Place every sprite, returning its frame (in input order) and the atlas size.
+  pub(crate) fn pack(&self, sprites: &[Sprite]) -> Result<(Vec<AtlasFrame>, u32, u32)> {
+    let sizes: Vec<_> = sprites
+      .iter()
+      .map(|sprite| (sprite.image.width(), sprite.image.height()))
+      .collect();
+    if let Some(index) = sizes.iter().position(|&(width, _)| width > self.width) {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!(
+          "packAtlas: image {index} is {}px wide, more than the atlas width {}",
+          sizes[index].0, self.width
+        ),
+      ));
+    }
+    let (positions, mut width, mut height) = skyline(&sizes, self.width, self.padding);
+    if self.power_of_two {
+      (width, height) = (width.next_power_of_two(), height.next_power_of_two());
+    }
+    let frames = sprites
+      .iter()
+      .zip(positions)
+      .zip(&self.names)
+      .map(|((sprite, (x, y)), name)| AtlasFrame {
+        name: name.clone(),
+        x,
+        y,
+        width: sprite.image.width(),
+        height: sprite.image.height(),
+        trimmed: (sprite.image.width(), sprite.image.height()) != sprite.source,
+        offset_x: sprite.offset.0,
+        offset_y: sprite.offset.1,
+        source_width: sprite.source.0,
+        source_height: sprite.source.1,
+      })
+      .collect();
+    Ok((frames, width, height))
+  }
+
+  /// The TexturePacker "JSON (Hash)" frame map.
+  pub(crate) fn json(&self, frames: &[AtlasFrame], width: u32, height: u32) -> String {
+    let entries: Vec<String> = frames
+      .iter()
+      .map(|frame| {
+        format!(
+          "    {}: {{\n      \"frame\": {{ \"x\": {}, \"y\": {}, \"w\": {}, \"h\": {} }},\n      \
+           \"rotated\": false,\n      \"trimmed\": {},\n      \"spriteSourceSize\": {{ \"x\": {}, \
+           \"y\": {}, \"w\": {}, \"h\": {} }},\n      \"sourceSize\": {{ \"w\": {}, \"h\": {} }}\n    \
+           }}",
+          json_string(&frame.name),
+          frame.x,
+          frame.y,
+          frame.width,
+          frame.height,
+          frame.trimmed,
+          frame.offset_x,
+          frame.offset_y,
+          frame.width,
+          frame.height,
+          frame.source_width,
+          frame.source_height,
+        )
+      })
+      .collect();
+    let image = self
+      .image_name
+      .as_deref()
+      .map(|name| format!("\n    \"image\": {},", json_string(name)))
+      .unwrap_or_default();
+    format!(
+      "{{\n  \"frames\": {{\n{}\n  }},\n  \"meta\": {{{image}\n    \"format\": \"RGBA8888\",\n    \
+       \"size\": {{ \"w\": {width}, \"h\": {height} }},\n    \"scale\": \"1\"\n  }}\n}}\n",
+      entries.join(",\n")
+    )
+  }
+}
+
+/// A transparent `width`x`height` canvas as deep as the deepest sprite, ready for
+/// `composite_step`.
+pub(crate) fn canvas(sprites: &[Sprite], width: u32, height: u32) -> DynamicImage {
+  let depth = |color: ColorType| color.bytes_per_pixel() / color.channel_count();
+  match sprites
+    .iter()
+    .map(|sprite| depth(sprite.image.color()))
+    .max()
+  {
+    Some(4) => DynamicImage::ImageRgba32F(ImageBuffer::new(width, height)),
+    Some(2) => DynamicImage::ImageRgba16(ImageBuffer::new(width, height)),
+    _ => DynamicImage::ImageRgba8(ImageBuffer::new(width, height)),
+  }
+}
+
+/// The smallest rectangle holding every pixel with non-zero alpha, when that is smaller than the
+/// image. A fully transparent image keeps its top-left pixel.
+fn opaque_bounds(image: &DynamicImage) -> Option<(u32, u32, u32, u32)> {
+  if !image.color().has_alpha() {
+    return None;
+  }
+  let rgba = image.to_rgba16();
+  let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+  for (x, y, pixel) in rgba.enumerate_pixels() {
+    if pixel.0[3] > 0 {
+      (left, top) = (left.min(x), top.min(y));
+      (right, bottom) = (right.max(x + 1), bottom.max(y + 1));
+    }
+  }
+  if left == u32::MAX {
+    return Some((0, 0, 1, 1));
+  }
+  let bounds = (left, top, right - left, bottom - top);
+  (bounds != (0, 0, image.width(), image.height())).then_some(bounds)
+}
+
+/// Bottom-left skyline packing into a `width`-wide strip: tallest sprites first, each at the
+/// position where its top edge (plus `padding`) ends up lowest. Returns the top-left corner of
+/// every sprite (in `sizes` order) and the packed width and height.
+fn skyline(sizes: &[(u32, u32)], width: u32, padding: u32) -> (Vec<(u32, u32)>, u32, u32) {
+  let mut order: Vec<usize> = (0..sizes.len()).collect();
+  order.sort_by_key(|&index| std::cmp::Reverse((sizes[index].1, sizes[index].0)));
+  // `(x, y, width)` segments of the skyline, left to right, covering `0..width`.
+  let mut segments = vec![(0, 0, width)];
+  let mut positions = vec![(0, 0); sizes.len()];
+  let (mut used_width, mut used_height) = (0, 0);
+  for index in order {
+    let (sprite_width, sprite_height) = sizes[index];
+    let mut best: Option<(u32, usize, u32, u32)> = None;
+    for start in 0..segments.len() {
+      let x = segments[start].0;
+      if x + sprite_width > width {
+        break;
+      }
+      // Padding only separates sprites, so a sprite may use it up at the right edge.
+      let span = (sprite_width + padding).min(width - x);
+      let (mut y, mut covered) = (0, 0);
+      for &(_, segment_y, segment_width) in &segments[start..] {
+        if covered >= span {
+          break;
+        }
+        y = y.max(segment_y);
+        covered += segment_width;
+      }
+      let top = y + sprite_height + padding;
+      if best.is_none_or(|(best_top, ..)| top < best_top) {
+        best = Some((top, start, y, span));
+      }
+    }
+    let (top, start, y, span) =
+      best.expect("a sprite no wider than the strip fits at its left edge");
+    let x = segments[start].0;
+    positions[index] = (x, y);
+    used_width = used_width.max(x + sprite_width);
+    used_height = used_height.max(y + sprite_height);
+    let end = x + span;
+    let mut next: Vec<(u32, u32, u32)> = segments
+      .iter()
+      .filter(|segment| segment.0 < x)
+      .map(|&(sx, sy, sw)| (sx, sy, (sx + sw).min(x) - sx))
+      .collect();
+    next.push((x, top, span));
+    next.extend(
+      segments
+        .iter()
+        .filter(|segment| segment.0 + segment.2 > end)
+        .map(|&(sx, sy, sw)| (sx.max(end), sy, sx + sw - sx.max(end))),
+    );
+    next.dedup_by(|later, earlier| {
+      let same = later.1 == earlier.1;
+      if same {
+        earlier.2 += later.2;
+      }
+      same
+    });
+    segments = next;
+  }
+  (positions, used_width, used_height)
+}
+
+#[cfg(test)]
+mod tests {
+  use image::{Rgba, RgbaImage};
+
+  use super::*;
+
+  fn options() -> AtlasOptions {
+    AtlasOptions {
+      max_width: Some(100),
+      padding: None,
+      power_of_two: None,
+      trim: None,
+      names: None,
+      image_name: None,
+      format: None,
+    }
+  }
+
+  fn overlaps(a: &AtlasFrame, b: &AtlasFrame) -> bool {
+    a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+  }
+
+  #[test]
+  fn skyline_packs_without_overlap_within_the_width() {
+    let sizes = [(60, 40), (40, 40), (30, 20), (30, 20), (70, 10), (100, 5)];
+    let (positions, width, height) = skyline(&sizes, 100, 0);
+    assert_eq!((width, height), (100, 75));
+    assert_eq!(positions[0], (0, 0));
+    assert_eq!(positions[1], (60, 0));
+    let plan = AtlasPlan::new(&options(), sizes.len()).unwrap();
+    let sprites: Vec<_> = sizes
+      .iter()
+      .map(|&(w, h)| plan.sprite(&DynamicImage::ImageRgba8(RgbaImage::new(w, h))))
+      .collect();
+    let (frames, _, _) = plan.pack(&sprites).unwrap();
+    for (i, a) in frames.iter().enumerate() {
+      assert!(a.x + a.width <= 100);
+      for b in &frames[i + 1..] {
+        assert!(!overlaps(a, b), "{} overlaps {}", a.name, b.name);
+      }
+    }
+  }
+
+  #[test]
+  fn padding_is_dropped_at_the_right_edge() {
+    // 30px plus 2px of padding would not fit at x=70 of a 100px strip, but the sprite does.
+    let (positions, width, height) = skyline(&[(68, 10), (30, 10)], 100, 2);
+    assert_eq!(positions, [(0, 0), (70, 0)]);
+    assert_eq!((width, height), (100, 10));
+  }
+
+  #[test]
+  fn padding_and_power_of_two_grow_the_atlas() {
+    let plan = AtlasPlan::new(
+      &AtlasOptions {
+        padding: Some(2),
+        power_of_two: Some(true),
+        ..options()
+      },
+      2,
+    )
+    .unwrap();
+    let sprites: Vec<_> = [(30, 30), (30, 20)]
+      .iter()
+      .map(|&(w, h)| plan.sprite(&DynamicImage::ImageRgba8(RgbaImage::new(w, h))))
+      .collect();
+    let (frames, width, height) = plan.pack(&sprites).unwrap();
+    assert_eq!((frames[1].x, frames[1].y), (32, 0));
+    assert_eq!((width, height), (64, 32));
+    // Packed within 64, the largest power of two up to 100.
+    let wide = [plan.sprite(&DynamicImage::ImageRgba8(RgbaImage::new(80, 1)))];
+    assert!(plan.pack(&wide).is_err());
+  }
+
+  #[test]
+  fn trim_records_the_offset_in_the_source() {
+    let mut image = RgbaImage::new(10, 8);
+    image.put_pixel(3, 2, Rgba([255, 0, 0, 255]));
+    image.put_pixel(6, 4, Rgba([0, 255, 0, 128]));
+    let plan = AtlasPlan::new(
+      &AtlasOptions {
+        trim: Some(true),
+        names: Some(vec!["dot \"a\"".to_owned()]),
+        image_name: Some("atlas.png".to_owned()),
+        ..options()
+      },
+      1,
+    )
+    .unwrap();
+    let sprites = [plan.sprite(&DynamicImage::ImageRgba8(image))];
+    let (frames, width, height) = plan.pack(&sprites).unwrap();
+    let frame = &frames[0];
+    assert!(frame.trimmed);
+    assert_eq!((frame.width, frame.height), (4, 3));
+    assert_eq!((frame.offset_x, frame.offset_y), (3, 2));
+    assert_eq!((width, height), (4, 3));
+    let json = plan.json(&frames, width, height);
+    assert!(json.contains("\"dot \\\"a\\\"\": {"));
+    assert!(json.contains("\"spriteSourceSize\": { \"x\": 3, \"y\": 2, \"w\": 4, \"h\": 3 }"));
+    assert!(json.contains("\"image\": \"atlas.png\","));
+  }
+
+  #[test]
+  fn rejects_mismatched_or_duplicate_names() {
+    let names = |names: &[&str]| AtlasOptions {
+      names: Some(names.iter().map(|name| name.to_string()).collect()),
+      ..options()
+    };
+    assert!(AtlasPlan::new(&names(&["a"]), 2).is_err());
+    assert!(AtlasPlan::new(&names(&["a", "a"]), 2).is_err());
+    assert!(AtlasPlan::new(&names(&["a", "b"]), 2).is_ok());
+    assert!(AtlasPlan::new(&options(), 0).is_err());
+  }
+}
@@ -0,0 +1,339 @@
+use image::{ColorType, DynamicImage, Rgba32FImage};
+use napi_derive::napi;
+
+use crate::utils::HelperThreads;
+
+/// How `blur()` approximates the Gaussian.
+#[napi]
+#[derive(Clone, Copy, Default)]
+pub enum BlurPrecision {
+  /// `Exact` below sigma 3, `Approximate` from there on, where the exact kernel gets expensive.
+  #[default]
+  Auto,
+  /// A true Gaussian kernel truncated at 3 sigma. Cost grows linearly with sigma.
+  Exact,
+  /// Three box-blur passes with the same variance. Constant cost whatever the sigma, within a
+  /// few percent of `Exact`.
+  Approximate,
+}
+
+/// Below this many pixels a plane is blurred on the calling thread; spawning isn't worth it.
+const PARALLEL_MIN_PIXELS: usize = 1 << 16;
+
+/// Blur `image` by `sigma`, keeping its color type and depth. Samples are blurred as normalized
+/// f32 (exact for 8- and 16-bit), with color premultiplied by alpha so transparent pixels don't
+/// bleed their (usually black) color into the edges.
+pub(crate) fn gaussian_blur(
+  image: &DynamicImage,
+  sigma: f32,
+  precision: BlurPrecision,
+) -> DynamicImage {
+  let color = image.color();
+  let mut rgba = image.to_rgba32f();
+  let (width, height) = (rgba.width() as usize, rgba.height() as usize);
+  // Gray images blur one plane instead of three identical ones.
+  let color_planes = if color.has_color() { 3 } else { 1 };
+  let has_alpha = color.has_alpha();
+  let mut planes = vec![vec![0.0f32; width * height]; color_planes];
+  let mut alpha = has_alpha.then(|| vec![0.0f32; width * height]);
+  for (i, pixel) in rgba.pixels().enumerate() {
+    let a = if has_alpha { pixel[3] } else { 1.0 };
+    if let Some(alpha) = &mut alpha {
+      alpha[i] = a;
+    }
+    for (c, plane) in planes.iter_mut().enumerate() {
+      plane[i] = pixel[c] * a;
+    }
+  }
+  for plane in planes.iter_mut().chain(alpha.as_mut()) {
+    blur_plane(plane, width, height, sigma, precision);
+  }
+  for (i, pixel) in rgba.pixels_mut().enumerate() {
+    let a = alpha.as_ref().map_or(1.0, |alpha| alpha[i]);
+    let unpremultiply = |v: f32| if a > 0.0 { v / a } else { 0.0 };
+    for c in 0..3 {
+      pixel[c] = unpremultiply(planes[c.min(color_planes - 1)][i]);
+    }
+    pixel[3] = a;
+  }
+  restore_color_type(rgba, color)
+}
+
+/// Convert a working `Rgba32F` buffer back to `color`, the type the image had before it was
+/// widened for filtering.
+pub(crate) fn restore_color_type(rgba: Rgba32FImage, color: ColorType) -> DynamicImage {
+  let filtered = DynamicImage::ImageRgba32F(rgba);
+  match color {
+    ColorType::L8 => DynamicImage::ImageLuma8(filtered.to_luma8()),
+    ColorType::La8 => DynamicImage::ImageLumaA8(filtered.to_luma_alpha8()),
+    ColorType::Rgb8 => DynamicImage::ImageRgb8(filtered.to_rgb8()),
+    ColorType::L16 => DynamicImage::ImageLuma16(filtered.to_luma16()),
+    ColorType::La16 => DynamicImage::ImageLumaA16(filtered.to_luma_alpha16()),
+    ColorType::Rgb16 => DynamicImage::ImageRgb16(filtered.to_rgb16()),
+    ColorType::Rgba16 => DynamicImage::ImageRgba16(filtered.to_rgba16()),
+    ColorType::Rgb32F => DynamicImage::ImageRgb32F(filtered.to_rgb32f()),
+    ColorType::Rgba32F => filtered,
+    _ => DynamicImage::ImageRgba8(filtered.to_rgba8()),
+  }
+}
+
+/// Blur a `width`x`height` plane of samples in place: rows, then columns via a transpose so both
+/// passes walk memory in order.
+pub(crate) fn blur_plane(
+  plane: &mut [f32],
+  width: usize,
+  height: usize,
+  sigma: f32,
+  precision: BlurPrecision,
+) {
+  if width == 0 || height == 0 || sigma.is_nan() || sigma <= 0.0 {
+    return;
+  }
+  let approximate = match precision {
+    BlurPrecision::Auto => sigma >= 3.0,
+    BlurPrecision::Exact => false,
+    BlurPrecision::Approximate => true,
+  };
+  let pass = |rows: &mut [f32], len: usize| {
+    if approximate {
+      let radii = box_radii(sigma);
+      for_each_row(rows, len, |_, row, scratch| {
+        for radius in radii {
+          box_row(row, scratch, radius);
+        }
+      });
+    } else {
+      let kernel = gaussian_kernel(sigma);
+      for_each_row(rows, len, |_, row, scratch| {
+        gaussian_row(row, scratch, &kernel)
+      });
+    }
+  };
+  pass(plane, width);
+  let mut transposed = transpose(plane, width, height);
+  pass(&mut transposed, height);
+  plane.copy_from_slice(&transpose(&transposed, height, width));
+}
+
+/// Run `f(y, row, scratch)` on every `len`-sample row of `rows`, splitting large planes between
+/// the calling thread and whatever [`HelperThreads`] are free. Each thread gets its own scratch
+/// buffer.
+pub(crate) fn for_each_row(
+  rows: &mut [f32],
+  len: usize,
+  f: impl Fn(usize, &mut [f32], &mut Vec<f32>) + Sync,
+) {
+  let row_count = rows.len() / len.max(1);
+  let helpers = if rows.len() < PARALLEL_MIN_PIXELS {
+    HelperThreads::take(0)
+  } else {
+    HelperThreads::take(row_count.saturating_sub(1))
+  };
+  let run = |first: usize, chunk: &mut [f32]| {
+    let mut scratch = Vec::new();
+    chunk
+      .chunks_exact_mut(len)
+      .enumerate()
+      .for_each(|(y, row)| f(first + y, row, &mut scratch));
+  };
+  if helpers.count() == 0 {
+    run(0, rows);
+    return;
+  }
+  let rows_per_thread = row_count.div_ceil(helpers.count() + 1);
+  std::thread::scope(|scope| {
+    let mut chunks = rows.chunks_mut(rows_per_thread * len).enumerate();
+    let own = chunks.next();
+    for (i, chunk) in chunks {
+      let run = &run;
+      scope.spawn(move || run(i * rows_per_thread, chunk));
+    }
+    if let Some((_, chunk)) = own {
+      run(0, chunk);
+    }
+  });
+}
+
+fn transpose(plane: &[f32], width: usize, height: usize) -> Vec<f32> {
+  let mut out = vec![0.0; plane.len()];
+  for (y, row) in plane.chunks_exact(width).enumerate() {
+    for (x, &value) in row.iter().enumerate() {
+      out[x * height + y] = value;
+    }
+  }
+  out
+}
+
+/// A normalized Gaussian truncated at 3 sigma, `2 * radius + 1` taps.
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+  let radius = (3.0 * sigma).ceil() as i64;
+  let kernel: Vec<f32> = (-radius..=radius)
+    .map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp())
+    .collect();
+  let sum: f32 = kernel.iter().sum();
+  kernel.into_iter().map(|w| w / sum).collect()
+}
+
+/// Copy `row` into `scratch` with `radius` samples of edge replication on both sides.
+fn pad(row: &[f32], scratch: &mut Vec<f32>, radius: usize) {
+  scratch.clear();
+  scratch.extend(std::iter::repeat_n(row[0], radius));
+  scratch.extend_from_slice(row);
+  scratch.extend(std::iter::repeat_n(row[row.len() - 1], radius));
+}
+
+fn gaussian_row(row: &mut [f32], scratch: &mut Vec<f32>, kernel: &[f32]) {
+  pad(row, scratch, kernel.len() / 2);
+  for (out, window) in row.iter_mut().zip(scratch.windows(kernel.len())) {
+    *out = window.iter().zip(kernel).map(|(v, w)| v * w).sum();
+  }
+}
+
+fn box_row(row: &mut [f32], scratch: &mut Vec<f32>, radius: usize) {
+  if radius == 0 {
+    return;
+  }
+  pad(row, scratch, radius);
+  let size = 2 * radius + 1;
+  let scale = 1.0 / size as f32;
+  // A running sum in f64, so long rows don't accumulate f32 drift.
+  let mut sum: f64 = scratch[..size].iter().map(|&v| v as f64).sum();
+  row[0] = sum as f32 * scale;
+  for x in 1..row.len() {
+    sum += scratch[x + size - 1] as f64 - scratch[x - 1] as f64;
+    row[x] = sum as f32 * scale;
+  }
+}
+
+/// Radii of three box filters whose combined variance matches a Gaussian of `sigma` (Kovesi,
+/// "Fast Almost-Gaussian Filtering").
+fn box_radii(sigma: f32) -> [usize; 3] {
+  let n = 3.0;
+  let ideal = (12.0 * sigma * sigma / n + 1.0).sqrt();
+  let mut lower = ideal.floor() as i64;
+  if lower % 2 == 0 {
+    lower -= 1;
+  }
+  let lower = lower.max(1);
+  let upper = lower + 2;
+  let (wl, s2) = (lower as f32, sigma * sigma);
+  let m = ((12.0 * s2 - n * wl * wl - 4.0 * n * wl - 3.0 * n) / (-4.0 * wl - 4.0)).round() as i64;
+  let radius = |size: i64| ((size - 1) / 2) as usize;
+  [0, 1, 2].map(|i| if i < m { radius(lower) } else { radius(upper) })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::{GrayImage, ImageBuffer, Rgba, RgbaImage};
+
+  fn impulse(size: usize) -> Vec<f32> {
+    let mut plane = vec![0.0; size * size];
+    plane[size / 2 * size + size / 2] = 1000.0;
+    plane
+  }
+
+  #[test]
+  fn both_precisions_preserve_energy_and_flat_regions() {
+    for precision in [BlurPrecision::Exact, BlurPrecision::Approximate] {
+      let mut plane = impulse(41);
+      blur_plane(&mut plane, 41, 41, 3.0, precision);
+      let total: f32 = plane.iter().sum();
+      assert!((total - 1000.0).abs() < 0.5, "{total}");
+      assert_eq!(plane[20 * 41 + 17], plane[20 * 41 + 23], "symmetric");
+
+      let mut flat = vec![7.0; 30 * 20];
+      blur_plane(&mut flat, 30, 20, 4.0, precision);
+      assert!(flat.iter().all(|v| (v - 7.0).abs() < 1e-4));
+    }
+  }
+
+  #[test]
+  fn approximate_tracks_exact() {
+    let size = 61;
+    let mut exact = impulse(size);
+    let mut approximate = exact.clone();
+    blur_plane(&mut exact, size, size, 6.0, BlurPrecision::Exact);
+    blur_plane(
+      &mut approximate,
+      size,
+      size,
+      6.0,
+      BlurPrecision::Approximate,
+    );
+    let peak = exact.iter().cloned().fold(0.0, f32::max);
+    let worst = exact
+      .iter()
+      .zip(&approximate)
+      .map(|(a, b)| (a - b).abs())
+      .fold(0.0, f32::max);
+    assert!(worst < peak * 0.1, "{worst} vs peak {peak}");
+  }
+
+  #[test]
+  fn box_radii_match_the_gaussian_variance() {
+    // Odd integer box sizes can't follow small sigmas closely, which is why `Auto` only
+    // approximates from sigma 3.
+    for sigma in [3.0f32, 7.5, 20.0] {
+      let variance: f32 = box_radii(sigma)
+        .iter()
+        .map(|&r| {
+          let size = (2 * r + 1) as f32;
+          (size * size - 1.0) / 12.0
+        })
+        .sum();
+      assert!(
+        (variance - sigma * sigma).abs() / (sigma * sigma) < 0.15,
+        "{sigma}: {variance}"
+      );
+    }
+  }
+
+  #[test]
+  fn transparent_pixels_do_not_darken_edges() {
+    let image = DynamicImage::ImageRgba8(RgbaImage::from_fn(8, 1, |x, _| {
+      if x < 4 {
+        Rgba([255, 0, 0, 255])
+      } else {
+        Rgba([0, 0, 0, 0])
+      }
+    }));
+    let blurred = gaussian_blur(&image, 1.5, BlurPrecision::Exact).to_rgba8();
+    let edge = blurred.get_pixel(4, 0).0;
+    assert!(edge[3] > 0 && edge[3] < 255, "{edge:?}");
+    assert_eq!(&edge[..3], &[255, 0, 0], "the color stays red");
+  }
+
+  #[test]
+  fn keeps_color_type_and_depth() {
+    let image =
+      DynamicImage::ImageLuma16(ImageBuffer::from_raw(3, 1, vec![0u16, 65535, 0]).unwrap());
+    let blurred = gaussian_blur(&image, 1.0, BlurPrecision::Auto);
+    assert_eq!(blurred.color(), image::ColorType::L16);
+    let values = blurred.to_luma16().into_raw();
+    assert!(values[1] < 65535 && values[0] > 0 && values[0] == values[2]);
+
+    let gray = DynamicImage::ImageLuma8(GrayImage::from_raw(4, 4, vec![9; 16]).unwrap());
+    assert_eq!(gaussian_blur(&gray, 2.0, BlurPrecision::Approximate), gray);
+  }
+
+  #[test]
+  fn parallel_rows_match_a_single_thread() {
+    let (width, height) = (512, 160);
+    let mut plane: Vec<f32> = (0..width * height)
+      .map(|i| ((i * 7919) % 255) as f32)
+      .collect();
+    let mut serial = plane.clone();
+    blur_plane(&mut plane, width, height, 2.0, BlurPrecision::Exact);
+    let kernel = gaussian_kernel(2.0);
+    let mut scratch = Vec::new();
+    for row in serial.chunks_exact_mut(width) {
+      gaussian_row(row, &mut scratch, &kernel);
+    }
+    let mut transposed = transpose(&serial, width, height);
+    for column in transposed.chunks_exact_mut(height) {
+      gaussian_row(column, &mut scratch, &kernel);
+    }
+    assert_eq!(plane, transpose(&transposed, height, width));
+  }
+}
@@ -0,0 +1,415 @@
+use std::io::Cursor;
+
+use image::{
+  DynamicImage, GenericImageView, ImageEncoder, Rgba, RgbaImage, codecs::png::PngEncoder,
+};
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::lab::{LAB_SCALE, delta_e76_sq, rgb_to_lab};
+
+/// Per-scale weights of MS-SSIM (Wang, Simoncelli & Bovik 2003), finest scale first.
+const MS_SSIM_WEIGHTS: [f64; 5] = [0.0448, 0.2856, 0.3001, 0.2363, 0.1333];
+
+/// Smallest side a scale may have to take part in MS-SSIM.
+const MS_SSIM_MIN_SIDE: u32 = 11;
+
+/// SSIM stabilizers for a dynamic range of 1.
+const C1: f64 = 0.01 * 0.01;
+const C2: f64 = 0.03 * 0.03;
+
+/// Roughly the smallest color difference a viewer notices (CIE76).
+const DEFAULT_DIFF_THRESHOLD: f64 = 2.3;
+
+#[napi(object)]
+#[derive(Clone, Copy, Default)]
+pub struct CompareOptions {
+  /// Also return a PNG highlighting, in red, the pixels whose ΔE exceeds `threshold` over a
+  /// faded grayscale copy of the first image. Defaults to `false`.
+  pub diff: Option<bool>,
+  /// ΔE above which a pixel counts as different, for `diffPixels` and the diff image. Defaults
+  /// to `2.3`, about the smallest difference a viewer notices.
+  pub threshold: Option<f64>,
+}
+
+impl CompareOptions {
+  pub(crate) fn validate(&self) -> Result<()> {
+    match self.threshold {
+      Some(threshold) if !(threshold.is_finite() && threshold >= 0.0) => Err(Error::new(
+        Status::InvalidArg,
+        format!("compare: `threshold` must be a finite ΔE >= 0, got {threshold}"),
+      )),
+      _ => Ok(()),
+    }
+  }
+}
+
+#[napi(object)]
+pub struct Comparison {
+  /// Peak signal-to-noise ratio in dB over the color channels (and alpha when either image has
+  /// it). `Infinity` for identical images; above ~40 is hard to tell apart.
+  pub psnr: f64,
+  /// Structural similarity of the luminance, `1` for identical images.
+  pub ssim: f64,
+  /// Multi-scale SSIM over up to five scales, which tracks perceived quality across viewing
+  /// distances better than `ssim`.
+  pub ms_ssim: f64,
+  /// Largest CIE76 ΔE between corresponding pixels.
+  pub max_delta_e: f64,
+  /// Mean CIE76 ΔE over all pixels.
+  pub mean_delta_e: f64,
+  /// Pixels whose ΔE exceeds `threshold`.
+  pub diff_pixels: u32,
+  /// PNG-encoded diff image, when `diff` was set.
+  pub diff: Option<Buffer>,
+}
+
+pub struct ComparisonOutput {
+  pub(crate) psnr: f64,
+  pub(crate) ssim: f64,
+  pub(crate) ms_ssim: f64,
+  pub(crate) max_delta_e: f64,
+  pub(crate) mean_delta_e: f64,
+  pub(crate) diff_pixels: u32,
+  pub(crate) diff: Option<Vec<u8>>,
+}
+
+impl From<ComparisonOutput> for Comparison {
+  fn from(output: ComparisonOutput) -> Self {
+    Comparison {
+      psnr: output.psnr,
+      ssim: output.ssim,
+      ms_ssim: output.ms_ssim,
+      max_delta_e: output.max_delta_e,
+      mean_delta_e: output.mean_delta_e,
+      diff_pixels: output.diff_pixels,
+      diff: output.diff.map(Buffer::from),
+    }
+  }
+}
+
+/// An image reduced to what the metrics compare: color premultiplied by alpha (so transparent
+/// pixels compare as black whatever their color), and alpha, all in `0.0..=1.0`. Images of any
+/// depth or color type land here, so they can be compared with each other.
+struct Flattened {
+  width: u32,
+  height: u32,
+  rgb: Vec<[f32; 3]>,
+  alpha: Vec<f32>,
+}
+
+impl Flattened {
+  fn new(image: &DynamicImage) -> Self {
+    let (width, height) = image.dimensions();
+    // Snapping to the 16-bit grid makes an 8-bit image and its exact 16-bit widening identical.
+    let snap = |v: f32| (v.clamp(0.0, 1.0) * 65535.0).round() / 65535.0;
+    let rgba = image.to_rgba32f();
+    let (rgb, alpha) = rgba
+      .pixels()
+      .map(|p| {
+        let a = snap(p[3]);
+        ([0, 1, 2].map(|c| snap(p[c]) * a), a)
+      })
+      .unzip();
+    Flattened {
+      width,
+      height,
+      rgb,
+      alpha,
+    }
+  }
+
+  fn luma(&self) -> Vec<f32> {
+    self
+      .rgb
+      .iter()
+      .map(|[r, g, b]| 0.2126 * r + 0.7152 * g + 0.0722 * b)
+      .collect()
+  }
+
+  fn rgb8(&self, index: usize) -> [u8; 3] {
+    self.rgb[index].map(|c| (c * 255.0).round() as u8)
+  }
+}
+
+/// PSNR, SSIM, MS-SSIM and CIE76 ΔE between `a` and `b`, which must have the same dimensions
+/// but may differ in color type and depth.
+pub(crate) fn compare_images(
+  a: &DynamicImage,
+  b: &DynamicImage,
+  options: &CompareOptions,
+) -> Result<ComparisonOutput> {
+  if a.dimensions() != b.dimensions() {
+    return Err(Error::new(
+      Status::InvalidArg,
+      format!(
+        "compare: images differ in size, {}x{} vs {}x{}",
+        a.width(),
+        a.height(),
+        b.width(),
+        b.height()
+      ),
+    ));
+  }
+  let with_alpha = a.color().has_alpha() || b.color().has_alpha();
+  let (a, b) = (Flattened::new(a), Flattened::new(b));
+  let pixels = a.rgb.len();
+  if pixels == 0 {
+    return Err(Error::new(
+      Status::InvalidArg,
+      "compare: images are empty".to_owned(),
+    ));
+  }
+
+  let mut squared_error = 0.0f64;
+  for i in 0..pixels {
+    for c in 0..3 {
+      squared_error += ((a.rgb[i][c] - b.rgb[i][c]) as f64).powi(2);
+    }
+    if with_alpha {
+      squared_error += ((a.alpha[i] - b.alpha[i]) as f64).powi(2);
+    }
+  }
+  let mse = squared_error / (pixels * if with_alpha { 4 } else { 3 }) as f64;
+  let psnr = if mse == 0.0 {
+    f64::INFINITY
+  } else {
+    10.0 * (1.0 / mse).log10()
+  };
+
+  let (ssim, ms_ssim) = structural_similarity(&a, &b);
+
+  let threshold = options.threshold.unwrap_or(DEFAULT_DIFF_THRESHOLD);
+  let mut delta_e = vec![0.0f64; pixels];
+  for (i, delta) in delta_e.iter_mut().enumerate() {
+    let ([ar, ag, ab], [br, bg, bb]) = (a.rgb8(i), b.rgb8(i));
+    if (ar, ag, ab) != (br, bg, bb) {
+      let sq = delta_e76_sq(rgb_to_lab(ar, ag, ab), rgb_to_lab(br, bg, bb));
+      *delta = (sq as f64).sqrt() / LAB_SCALE as f64;
+    }
+  }
+  let max_delta_e = delta_e.iter().copied().fold(0.0, f64::max);
+  let mean_delta_e = delta_e.iter().sum::<f64>() / pixels as f64;
+  let diff_pixels = delta_e.iter().filter(|&&d| d > threshold).count() as u32;
+
+  let diff = if options.diff.unwrap_or(false) {
+    Some(diff_image(&a, &delta_e, threshold, max_delta_e)?)
+  } else {
+    None
+  };
+  Ok(ComparisonOutput {
+    psnr,
+    ssim,
+    ms_ssim,
+    max_delta_e,
+    mean_delta_e,
+    diff_pixels,
+    diff,
+  })
+}
+
+/// A faded grayscale copy of `base` with the pixels over `threshold` painted red, more saturated
+/// the larger their ΔE, encoded as PNG.
+fn diff_image(base: &Flattened, delta_e: &[f64], threshold: f64, max: f64) -> Result<Vec<u8>> {
+  let luma = base.luma();
+  let image = RgbaImage::from_fn(base.width, base.height, |x, y| {
+    let i = (y * base.width + x) as usize;
+    if delta_e[i] > threshold {
+      let strength = if max > threshold {
+        0.5 + 0.5 * (delta_e[i] - threshold) / (max - threshold)
+      } else {
+        1.0
+      };
+      let fade = (255.0 * (1.0 - strength)).round() as u8;
+      Rgba([255, fade, fade, 255])
+    } else {
+      // Mostly white, so the highlighted pixels stand out.
+      let faded = (255.0 - 255.0 * 0.1 * (1.0 - luma[i])).round() as u8;
+      Rgba([faded, faded, faded, 255])
+    }
+  });
+  let mut output = Cursor::new(Vec::new());
+  PngEncoder::new(&mut output)
+    .write_image(
+      image.as_raw(),
+      image.width(),
+      image.height(),
+      image::ExtendedColorType::Rgba8,
+    )
+    .map_err(|err| Error::from_reason(format!("compare: failed to encode diff image: {err}")))?;
+  Ok(output.into_inner())
+}
+
+/// Mean SSIM of the luminance at full scale, and MS-SSIM over as many of its five scales as the
+/// image is large enough for (weights renormalized).
+fn structural_similarity(a: &Flattened, b: &Flattened) -> (f64, f64) {
+  let (mut width, mut height) = (a.width as usize, a.height as usize);
+  let (mut x, mut y) = (a.luma(), b.luma());
+  let mut scales = 1;
+  while scales < MS_SSIM_WEIGHTS.len() && (a.width.min(a.height) >> scales) >= MS_SSIM_MIN_SIDE {
+    scales += 1;
+  }
+  let total: f64 = MS_SSIM_WEIGHTS[..scales].iter().sum();
+  let (mut ssim, mut ms_ssim) = (0.0, 1.0);
+  for (scale, weight) in MS_SSIM_WEIGHTS[..scales].iter().enumerate() {
+    let (full, contrast_structure) = ssim_means(&x, &y, width, height);
+    if scale == 0 {
+      ssim = full;
+    }
+    let term = if scale + 1 == scales {
+      full
+    } else {
+      contrast_structure
+    };
+    ms_ssim *= term.max(0.0).powf(weight / total);
+    if scale + 1 < scales {
+      (x, _, _) = halve(&x, width, height);
+      (y, width, height) = halve(&y, width, height);
+    }
+  }
+  (ssim, ms_ssim)
+}
+
+/// Mean SSIM and mean contrast-structure term over 11x11 Gaussian windows (sigma 1.5), with
+/// clamped edges.
+fn ssim_means(x: &[f32], y: &[f32], width: usize, height: usize) -> (f64, f64) {
+  let product = |p: &[f32], q: &[f32]| p.iter().zip(q).map(|(p, q)| p * q).collect::<Vec<_>>();
+  let mu_x = gaussian_window(x, width, height);
+  let mu_y = gaussian_window(y, width, height);
+  let xx = gaussian_window(&product(x, x), width, height);
+  let yy = gaussian_window(&product(y, y), width, height);
+  let xy = gaussian_window(&product(x, y), width, height);
+  let (mut ssim, mut contrast_structure) = (0.0f64, 0.0f64);
+  for i in 0..x.len() {
+    let (mx, my) = (mu_x[i] as f64, mu_y[i] as f64);
+    let var_x = (xx[i] as f64 - mx * mx).max(0.0);
+    let var_y = (yy[i] as f64 - my * my).max(0.0);
+    let covariance = xy[i] as f64 - mx * my;
+    let cs = (2.0 * covariance + C2) / (var_x + var_y + C2);
+    let luminance = (2.0 * mx * my + C1) / (mx * mx + my * my + C1);
+    ssim += luminance * cs;
+    contrast_structure += cs;
+  }
+  let n = x.len() as f64;
+  (ssim / n, contrast_structure / n)
+}
+
+fn gaussian_window(plane: &[f32], width: usize, height: usize) -> Vec<f32> {
+  const RADIUS: isize = 5;
+  let weights: Vec<f32> = (-RADIUS..=RADIUS)
+    .map(|d| (-(d * d) as f32 / (2.0 * 1.5 * 1.5)).exp())
+    .collect();
+  let sum: f32 = weights.iter().sum();
+  let weights: Vec<f32> = weights.iter().map(|w| w / sum).collect();
+  let clamp = |v: isize, len: usize| v.clamp(0, len as isize - 1) as usize;
+  let mut horizontal = vec![0.0f32; plane.len()];
+  for y in 0..height {
+    let row = &plane[y * width..(y + 1) * width];
+    for x in 0..width {
+      horizontal[y * width + x] = weights
+        .iter()
+        .enumerate()
+        .map(|(k, w)| w * row[clamp(x as isize + k as isize - RADIUS, width)])
+        .sum();
+    }
+  }
+  let mut out = vec![0.0f32; plane.len()];
+  for y in 0..height {
+    for x in 0..width {
+      out[y * width + x] = weights
+        .iter()
+        .enumerate()
+        .map(|(k, w)| w * horizontal[clamp(y as isize + k as isize - RADIUS, height) * width + x])
+        .sum();
+    }
+  }
+  out
+}
+
+/// 2x2 box downsample; an odd last row/column is dropped.
+fn halve(plane: &[f32], width: usize, height: usize) -> (Vec<f32>, usize, usize) {
+  let (half_width, half_height) = (width / 2, height / 2);
+  let mut out = Vec::with_capacity(half_width * half_height);
+  for y in 0..half_height {
+    for x in 0..half_width {
+      let at = |dx: usize, dy: usize| plane[(2 * y + dy) * width + 2 * x + dx];
+      out.push((at(0, 0) + at(1, 0) + at(0, 1) + at(1, 1)) / 4.0);
+    }
+  }
+  (out, half_width, half_height)
+}
+
+#[cfg(test)]
+mod tests {
+  use image::{GrayImage, ImageBuffer, Luma, Rgb, RgbImage};
+
+  use super::*;
+
+  fn pattern(width: u32, height: u32) -> RgbImage {
+    RgbImage::from_fn(width, height, |x, y| {
+      Rgb([(x * 5) as u8, (y * 3) as u8, ((x ^ y) * 7) as u8])
+    })
+  }
+
+  #[test]
+  fn identical_images_are_perfect_across_color_types() {
+    let gray = GrayImage::from_fn(40, 30, |x, y| Luma([(x * 6 + y) as u8]));
+    let a = DynamicImage::ImageLuma8(gray.clone());
+    // The same pixels as 16-bit RGBA.
+    let b = DynamicImage::ImageRgba16(ImageBuffer::from_fn(40, 30, |x, y| {
+      let v = gray.get_pixel(x, y)[0] as u16 * 257;
+      image::Rgba([v, v, v, 65535])
+    }));
+    let result = compare_images(&a, &b, &CompareOptions::default()).unwrap();
+    assert_eq!(result.psnr, f64::INFINITY);
+    assert!((result.ssim - 1.0).abs() < 1e-9);
+    assert!((result.ms_ssim - 1.0).abs() < 1e-9);
+    assert_eq!((result.max_delta_e, result.diff_pixels), (0.0, 0));
+    assert!(result.diff.is_none());
+  }
+
+  #[test]
+  fn noise_lowers_every_metric() {
+    let a = pattern(96, 96);
+    let mut b = a.clone();
+    for (i, pixel) in b.pixels_mut().enumerate() {
+      let noise = if i % 3 == 0 { 24 } else { 0 };
+      pixel[1] = pixel[1].saturating_add(noise);
+    }
+    let result = compare_images(
+      &DynamicImage::ImageRgb8(a),
+      &DynamicImage::ImageRgb8(b),
+      &CompareOptions {
+        diff: Some(true),
+        threshold: None,
+      },
+    )
+    .unwrap();
+    assert!(result.psnr.is_finite() && result.psnr > 20.0 && result.psnr < 40.0);
+    assert!(result.ssim < 0.99 && result.ms_ssim < 0.99);
+    assert!(result.max_delta_e > 2.3 && result.mean_delta_e < result.max_delta_e);
+    assert!(result.diff_pixels > 0);
+    let diff = image::load_from_memory(&result.diff.unwrap()).unwrap();
+    assert_eq!(diff.dimensions(), (96, 96));
+    // The first pixel got noise and is highlighted; the second did not and is faded out.
+    let diff = diff.to_rgba8();
+    assert_eq!(diff.get_pixel(0, 0)[1], diff.get_pixel(0, 0)[2]);
+    assert!(diff.get_pixel(0, 0)[0] > diff.get_pixel(0, 0)[1]);
+    let faded = diff.get_pixel(1, 0);
+    assert!(faded[0] == faded[1] && faded[0] > 200);
+  }
+
+  #[test]
+  fn transparent_color_does_not_count_and_sizes_must_match() {
+    let clear = |color| DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, Rgba(color)));
+    let result = compare_images(
+      &clear([255, 0, 0, 0]),
+      &clear([0, 0, 255, 0]),
+      &CompareOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(result.psnr, f64::INFINITY);
+    let small = DynamicImage::ImageRgb8(pattern(4, 4));
+    assert!(compare_images(&small, &clear([0; 4]), &CompareOptions::default()).is_err());
+  }
+}
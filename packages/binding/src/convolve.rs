@@ -0,0 +1,385 @@
+use image::DynamicImage;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::blur::{for_each_row, restore_color_type};
+
+/// Largest kernel side `convolve()` accepts. Cost grows with the kernel's area.
+const MAX_KERNEL_SIDE: u32 = 255;
+
+/// Below this share of the kernel's weight landing on visible pixels, a pixel next to
+/// transparency is convolved straight instead of alpha-weighted, which would divide by ~0.
+const MIN_COVERAGE: f32 = 1.0 / 255.0;
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct ConvolveOptions {
+  /// Kernel width in taps.
+  pub width: u32,
+  /// Kernel height in taps.
+  pub height: u32,
+  /// `width * height` weights, row by row. The center tap (`width / 2`, `height / 2`) lands on
+  /// the output pixel.
+  pub kernel: Vec<f64>,
+  /// The weighted sum is divided by this. Defaults to the kernel's sum, or `1` when that is `0`.
+  pub scale: Option<f64>,
+  /// Added after scaling, in 8-bit units (`0..=255`) whatever the image's depth. Defaults to `0`.
+  pub offset: Option<f64>,
+}
+
+/// Ready-made kernels for `convolve()`.
+#[napi]
+#[derive(Clone, Copy)]
+pub enum ConvolvePreset {
+  /// 3x3 sharpen: 5 at the center, -1 on the four sides.
+  Sharpen,
+  /// 3x3 emboss, lit from the top left.
+  Emboss,
+  /// Sobel edge detection: the gradient magnitude of the horizontal and vertical kernels.
+  Sobel,
+  /// Prewitt edge detection: like `Sobel`, with unweighted rows and columns.
+  Prewitt,
+  /// 3x3 Laplacian (4 at the center, -1 on the four sides): bright on the light side of edges.
+  Laplacian,
+}
+
+#[derive(Clone)]
+pub(crate) struct Kernel {
+  width: usize,
+  height: usize,
+  weights: Vec<f32>,
+  sum: f32,
+}
+
+impl Kernel {
+  fn new(width: usize, height: usize, weights: Vec<f32>) -> Self {
+    let sum = weights.iter().map(|&w| w as f64).sum::<f64>() as f32;
+    Self {
+      width,
+      height,
+      weights,
+      sum,
+    }
+  }
+
+  fn square3(weights: [f32; 9]) -> Self {
+    Self::new(3, 3, weights.to_vec())
+  }
+
+  /// The weighted sum around every pixel of a `width`x`height` plane, edges clamped.
+  fn apply(&self, plane: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let mut out = vec![0.0; plane.len()];
+    let (cx, cy) = (self.width / 2, self.height / 2);
+    for_each_row(&mut out, width, |y, row, _| {
+      for (ky, weights) in self.weights.chunks_exact(self.width).enumerate() {
+        let sy = (y + ky).saturating_sub(cy).min(height - 1);
+        let src = &plane[sy * width..][..width];
+        for (kx, &weight) in weights.iter().enumerate() {
+          if weight == 0.0 {
+            continue;
+          }
+          for (x, out) in row.iter_mut().enumerate() {
+            *out += weight * src[(x + kx).saturating_sub(cx).min(width - 1)];
+          }
+        }
+      }
+    });
+    out
+  }
+}
+
+/// A staged `convolve()`.
+#[derive(Clone)]
+pub(crate) enum Convolution {
+  /// `sum / scale + offset`, with `offset` normalized to `0.0..=1.0`.
+  Kernel {
+    kernel: Kernel,
+    scale: f32,
+    offset: f32,
+  },
+  /// `hypot(x, y)` of two kernels' responses.
+  Gradient { x: Kernel, y: Kernel },
+}
+
+impl Convolution {
+  pub(crate) fn from_options(options: ConvolveOptions) -> Result<Self> {
+    let ConvolveOptions {
+      width,
+      height,
+      kernel,
+      scale,
+      offset,
+    } = options;
+    if width == 0 || height == 0 || width > MAX_KERNEL_SIDE || height > MAX_KERNEL_SIDE {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!(
+          "convolve: kernel must be 1 to {MAX_KERNEL_SIDE} taps a side, got {width}x{height}"
+        ),
+      ));
+    }
+    if kernel.len() != (width * height) as usize {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!(
+          "convolve: a {width}x{height} kernel needs {} weights, got {}",
+          width * height,
+          kernel.len()
+        ),
+      ));
+    }
+    if let Some(weight) = kernel.iter().find(|w| !w.is_finite()) {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("convolve: kernel weights must be finite, got {weight}"),
+      ));
+    }
+    let kernel = Kernel::new(
+      width as usize,
+      height as usize,
+      kernel.into_iter().map(|w| w as f32).collect(),
+    );
+    let scale = match scale {
+      Some(scale) if !scale.is_finite() || scale == 0.0 => {
+        return Err(Error::new(
+          Status::InvalidArg,
+          format!("convolve: scale must be a finite, non-zero number, got {scale}"),
+        ));
+      }
+      Some(scale) => scale as f32,
+      None => default_scale(&kernel),
+    };
+    let offset = offset.unwrap_or(0.0);
+    if !offset.is_finite() {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("convolve: offset must be a finite number, got {offset}"),
+      ));
+    }
+    Ok(Self::Kernel {
+      kernel,
+      scale,
+      offset: offset as f32 / 255.0,
+    })
+  }
+
+  pub(crate) fn preset(preset: ConvolvePreset) -> Self {
+    let kernel = |weights| {
+      let kernel = Kernel::square3(weights);
+      Self::Kernel {
+        scale: default_scale(&kernel),
+        kernel,
+        offset: 0.0,
+      }
+    };
+    match preset {
+      ConvolvePreset::Sharpen => kernel([0.0, -1.0, 0.0, -1.0, 5.0, -1.0, 0.0, -1.0, 0.0]),
+      ConvolvePreset::Emboss => kernel([-2.0, -1.0, 0.0, -1.0, 1.0, 1.0, 0.0, 1.0, 2.0]),
+      ConvolvePreset::Laplacian => kernel([0.0, -1.0, 0.0, -1.0, 4.0, -1.0, 0.0, -1.0, 0.0]),
+      ConvolvePreset::Sobel => Self::Gradient {
+        x: Kernel::square3([-1.0, 0.0, 1.0, -2.0, 0.0, 2.0, -1.0, 0.0, 1.0]),
+        y: Kernel::square3([-1.0, -2.0, -1.0, 0.0, 0.0, 0.0, 1.0, 2.0, 1.0]),
+      },
+      ConvolvePreset::Prewitt => Self::Gradient {
+        x: Kernel::square3([-1.0, 0.0, 1.0, -1.0, 0.0, 1.0, -1.0, 0.0, 1.0]),
+        y: Kernel::square3([-1.0, -1.0, -1.0, 0.0, 0.0, 0.0, 1.0, 1.0, 1.0]),
+      },
+    }
+  }
+}
+
+fn default_scale(kernel: &Kernel) -> f32 {
+  if kernel.sum == 0.0 { 1.0 } else { kernel.sum }
+}
+
+/// Convolve `image`'s color channels, keeping its color type, depth and alpha. Samples are
+/// filtered as normalized f32 and clamped to `0.0..=1.0`. Next to transparency a kernel is
+/// weighted by alpha (premultiplied, then divided by the share of the kernel's weight that landed
+/// on visible pixels), so the hidden, usually black, color of transparent pixels doesn't darken
+/// the edges.
+pub(crate) fn convolve(image: &DynamicImage, convolution: &Convolution) -> DynamicImage {
+  let color = image.color();
+  let mut rgba = image.to_rgba32f();
+  let (width, height) = (rgba.width() as usize, rgba.height() as usize);
+  if width == 0 || height == 0 {
+    return image.clone();
+  }
+  // Gray images filter one plane instead of three identical ones.
+  let color_planes = if color.has_color() { 3 } else { 1 };
+  let planes: Vec<Vec<f32>> = (0..color_planes)
+    .map(|c| rgba.pixels().map(|pixel| pixel[c]).collect())
+    .collect();
+  let filtered: Vec<Vec<f32>> = match convolution {
+    Convolution::Kernel {
+      kernel,
+      scale,
+      offset,
+    } => {
+      // Zero-sum kernels (edge detectors) have no coverage to divide by; they run straight.
+      let weighted = (color.has_alpha() && kernel.sum != 0.0).then(|| {
+        let alpha: Vec<f32> = rgba.pixels().map(|pixel| pixel[3]).collect();
+        let premultiplied: Vec<Vec<f32>> = planes
+          .iter()
+          .map(|plane| {
+            let plane: Vec<f32> = plane.iter().zip(&alpha).map(|(v, a)| v * a).collect();
+            kernel.apply(&plane, width, height)
+          })
+          .collect();
+        (kernel.apply(&alpha, width, height), premultiplied)
+      });
+      planes
+        .iter()
+        .enumerate()
+        .map(|(c, plane)| {
+          let mut out = kernel.apply(plane, width, height);
+          for (i, value) in out.iter_mut().enumerate() {
+            *value /= scale;
+            if let Some((coverage, premultiplied)) = &weighted {
+              let coverage = coverage[i] / kernel.sum;
+              if coverage > MIN_COVERAGE {
+                *value = premultiplied[c][i] / scale / coverage;
+              }
+            }
+            *value += offset;
+          }
+          out
+        })
+        .collect()
+    }
+    Convolution::Gradient { x, y } => planes
+      .iter()
+      .map(|plane| {
+        let gx = x.apply(plane, width, height);
+        let gy = y.apply(plane, width, height);
+        gx.iter().zip(&gy).map(|(gx, gy)| gx.hypot(*gy)).collect()
+      })
+      .collect(),
+  };
+  for (i, pixel) in rgba.pixels_mut().enumerate() {
+    for c in 0..3 {
+      pixel[c] = filtered[c.min(color_planes - 1)][i].clamp(0.0, 1.0);
+    }
+  }
+  restore_color_type(rgba, color)
+}
+
+#[cfg(test)]
+mod tests {
+  use image::{GrayImage, Luma, LumaA, Rgba, RgbaImage};
+
+  use super::*;
+
+  fn options(width: u32, height: u32, kernel: Vec<f64>) -> ConvolveOptions {
+    ConvolveOptions {
+      width,
+      height,
+      kernel,
+      scale: None,
+      offset: None,
+    }
+  }
+
+  #[test]
+  fn rejects_mismatched_and_non_finite_kernels() {
+    assert!(Convolution::from_options(options(3, 2, vec![1.0; 5])).is_err());
+    assert!(Convolution::from_options(options(0, 1, vec![])).is_err());
+    assert!(Convolution::from_options(options(1, 1, vec![f64::NAN])).is_err());
+    let zero_scale = ConvolveOptions {
+      scale: Some(0.0),
+      ..options(1, 1, vec![1.0])
+    };
+    assert!(Convolution::from_options(zero_scale).is_err());
+  }
+
+  #[test]
+  fn concurrent_convolutions_share_the_helper_threads() {
+    // Large enough to split rows across threads; both calls draw on the same helper budget.
+    let image = DynamicImage::ImageLuma8(GrayImage::from_fn(320, 320, |x, y| {
+      Luma([((x * 7 + y * 13) % 256) as u8])
+    }));
+    let convolution = Convolution::from_options(options(5, 5, vec![1.0; 25])).unwrap();
+    let expected = convolve(&image, &convolution);
+    let results: Vec<DynamicImage> = std::thread::scope(|scope| {
+      let handles: Vec<_> = (0..2)
+        .map(|_| scope.spawn(|| convolve(&image, &convolution)))
+        .collect();
+      handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .collect()
+    });
+    for result in results {
+      assert_eq!(result.as_bytes(), expected.as_bytes());
+    }
+  }
+
+  #[test]
+  fn non_square_box_kernel_averages_at_native_depth() {
+    // A 5x1 box over a horizontal 16-bit ramp is the identity away from the clamped edges.
+    let ramp = image::ImageBuffer::from_fn(9, 2, |x, _| Luma([x as u16 * 1000 + 1]));
+    let convolution = Convolution::from_options(options(5, 1, vec![1.0; 5])).unwrap();
+    let out = convolve(&DynamicImage::ImageLuma16(ramp), &convolution);
+    let out = out.as_luma16().expect("stays L16");
+    assert_eq!(out.get_pixel(4, 1)[0], 4001);
+    // Clamped edge: (0 + 0 + 0 + 1000 + 2000) / 5 + 1.
+    assert_eq!(out.get_pixel(0, 0)[0], 601);
+  }
+
+  #[test]
+  fn scale_and_offset_apply_in_8_bit_units() {
+    let gray = GrayImage::from_pixel(3, 3, Luma([40]));
+    let convolution = Convolution::from_options(ConvolveOptions {
+      scale: Some(2.0),
+      offset: Some(10.0),
+      ..options(1, 1, vec![1.0])
+    })
+    .unwrap();
+    let out = convolve(&DynamicImage::ImageLuma8(gray.clone()), &convolution);
+    assert_eq!(out.as_luma8().unwrap().get_pixel(1, 1)[0], 30);
+    let wide = DynamicImage::ImageLuma8(gray).to_luma16();
+    let out = convolve(&DynamicImage::ImageLuma16(wide), &convolution);
+    assert_eq!(out.as_luma16().unwrap().get_pixel(1, 1)[0], 30 * 257);
+  }
+
+  #[test]
+  fn sharpening_next_to_transparency_keeps_flat_color_and_alpha() {
+    // Opaque red on the left, transparent black on the right. Straight, the transparent
+    // neighbours would skew the edge column; weighted by alpha it stays exactly red.
+    let image = RgbaImage::from_fn(4, 3, |x, _| {
+      if x < 2 {
+        Rgba([200, 0, 0, 255])
+      } else {
+        Rgba([0, 0, 0, 0])
+      }
+    });
+    let out = convolve(
+      &DynamicImage::ImageRgba8(image),
+      &Convolution::preset(ConvolvePreset::Sharpen),
+    );
+    let out = out.as_rgba8().unwrap();
+    assert_eq!(*out.get_pixel(1, 1), Rgba([200, 0, 0, 255]));
+    assert_eq!(out.get_pixel(2, 1)[3], 0);
+  }
+
+  #[test]
+  fn sobel_finds_edges_and_ignores_flat_areas() {
+    let image =
+      image::ImageBuffer::from_fn(6, 3, |x, _| LumaA([if x < 3 { 0u8 } else { 100 }, 255]));
+    let out = convolve(
+      &DynamicImage::ImageLumaA8(image),
+      &Convolution::preset(ConvolvePreset::Sobel),
+    );
+    let out = out.as_luma_alpha8().unwrap();
+    assert_eq!(*out.get_pixel(0, 1), LumaA([0, 255]));
+    assert_eq!(*out.get_pixel(5, 1), LumaA([0, 255]));
+    // 4 * 100: one full column step under the [1, 2, 1] weights.
+    assert_eq!(out.get_pixel(2, 1)[0], 255);
+    let prewitt = convolve(
+      &DynamicImage::ImageLumaA8(image::ImageBuffer::from_fn(6, 3, |x, _| {
+        LumaA([if x < 3 { 0u8 } else { 20 }, 255])
+      })),
+      &Convolution::preset(ConvolvePreset::Prewitt),
+    );
+    assert_eq!(prewitt.as_luma_alpha8().unwrap().get_pixel(3, 1)[0], 60);
+  }
+}
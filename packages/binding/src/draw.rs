@@ -0,0 +1,499 @@
+use image::RgbaImage;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use resvg::tiny_skia::{
+  FillRule, GradientStop as SkiaStop, LinearGradient, Paint, Path, PathBuilder, Pixmap, Point,
+  RadialGradient, Rect, Shader, SpreadMode, Stroke, Transform,
+};
+
+#[napi]
+#[derive(Clone, Copy, Debug)]
+pub enum ShapeKind {
+  /// `x`, `y`, `width`, `height`; `radius` rounds the corners.
+  Rect,
+  /// A `Rect` whose corners are rounded by `radius` (required).
+  RoundedRect,
+  /// Centered on `x`, `y` with `radius`.
+  Circle,
+  /// A polyline through `points`; stroked only.
+  Line,
+  /// A closed shape through `points`.
+  Polygon,
+  /// SVG path data in `d`, e.g. `"M 0 0 L 10 10 Z"`.
+  Path,
+}
+
+#[napi]
+#[derive(Clone, Copy, Default)]
+pub enum GradientKind {
+  /// From `(x1, y1)` to `(x2, y2)`.
+  #[default]
+  Linear,
+  /// Centered on `(x1, y1)` with `radius`.
+  Radial,
+}
+
+#[napi(object)]
+pub struct GradientStop {
+  /// Position along the gradient, `0.0..=1.0`.
+  pub offset: f64,
+  /// Support CSS3 color, e.g. rgba(255, 255, 255, .8).
+  pub color: String,
+}
+
+#[napi(object)]
+pub struct Gradient {
+  /// Defaults to `Linear`.
+  pub kind: Option<GradientKind>,
+  pub x1: f64,
+  pub y1: f64,
+  pub x2: Option<f64>,
+  pub y2: Option<f64>,
+  pub radius: Option<f64>,
+  pub stops: Vec<GradientStop>,
+}
+
+/// A shape for `Transformer.draw`, in the pixel coordinates of the image it is drawn onto.
+#[napi(object)]
+pub struct Shape {
+  pub kind: ShapeKind,
+  /// Top-left corner of a `Rect`/`RoundedRect`, or the center of a `Circle`.
+  pub x: Option<f64>,
+  pub y: Option<f64>,
+  pub width: Option<f64>,
+  pub height: Option<f64>,
+  /// Corner radius of a `Rect`/`RoundedRect`, or the radius of a `Circle`.
+  pub radius: Option<f64>,
+  /// Flat `[x0, y0, x1, y1, ...]` vertices of a `Line` or `Polygon`.
+  pub points: Option<Vec<f64>>,
+  /// SVG path data of a `Path`.
+  pub d: Option<String>,
+  /// Fill color. Support CSS3 color, e.g. rgba(255, 255, 255, .8).
+  pub fill: Option<String>,
+  /// Fill with a gradient instead of `fill`.
+  pub gradient: Option<Gradient>,
+  /// Outline color. Support CSS3 color.
+  pub stroke: Option<String>,
+  /// Outline width in pixels. Defaults to 1.
+  pub stroke_width: Option<f64>,
+}
+
+/// A validated `Shape`, ready to rasterize at any size.
+pub(crate) struct PreparedShape {
+  path: Path,
+  fill: Option<Paint<'static>>,
+  stroke: Option<(Paint<'static>, Stroke)>,
+}
+
+fn invalid(message: String) -> Error {
+  Error::new(Status::InvalidArg, format!("draw: {message}"))
+}
+
+fn require(value: Option<f64>, kind: ShapeKind, name: &str) -> Result<f32> {
+  value
+    .filter(|value| value.is_finite())
+    .map(|value| value as f32)
+    .ok_or_else(|| invalid(format!("{kind:?} needs a finite `{name}`")))
+}
+
+fn paint(color: &str) -> Result<Paint<'static>> {
+  let color = color
+    .parse::<svgtypes::Color>()
+    .map_err(|err| invalid(format!("invalid color {color:?}: {err}")))?;
+  let mut paint = Paint::default();
+  paint.set_color_rgba8(color.red, color.green, color.blue, color.alpha);
+  paint.anti_alias = true;
+  Ok(paint)
+}
+
+fn gradient_paint(gradient: &Gradient) -> Result<Paint<'static>> {
+  let stops = gradient
+    .stops
+    .iter()
+    .map(|stop| {
+      let color = paint(&stop.color)?.shader;
+      let Shader::SolidColor(color) = color else {
+        unreachable!("a color paint has a solid shader")
+      };
+      Ok(SkiaStop::new(stop.offset as f32, color))
+    })
+    .collect::<Result<Vec<_>>>()?;
+  let start = Point::from_xy(gradient.x1 as f32, gradient.y1 as f32);
+  let shader = match gradient.kind.unwrap_or_default() {
+    GradientKind::Linear => {
+      let end = Point::from_xy(
+        gradient.x2.unwrap_or(gradient.x1) as f32,
+        gradient.y2.unwrap_or(gradient.y1) as f32,
+      );
+      LinearGradient::new(start, end, stops, SpreadMode::Pad, Transform::identity())
+    }
+    GradientKind::Radial => RadialGradient::new(
+      start,
+      0.0,
+      start,
+      gradient.radius.unwrap_or(0.0) as f32,
+      stops,
+      SpreadMode::Pad,
+      Transform::identity(),
+    ),
+  }
+  .ok_or_else(|| {
+    invalid("degenerate gradient (no stops, zero length or zero radius)".to_owned())
+  })?;
+  Ok(Paint {
+    shader,
+    anti_alias: true,
+    ..Default::default()
+  })
+}
+
+/// A rectangle with its corners rounded by cubic quarter-circle approximations.
+fn rounded_rect(rect: Rect, radius: f32) -> Option<Path> {
+  let radius = radius.min(rect.width() / 2.0).min(rect.height() / 2.0);
+  if radius <= 0.0 {
+    return Some(PathBuilder::from_rect(rect));
+  }
+  // Distance of the control points from a corner for a circular quarter arc.
+  const KAPPA: f32 = 0.552_284_8;
+  let k = radius * (1.0 - KAPPA);
+  let (l, t, r, b) = (rect.left(), rect.top(), rect.right(), rect.bottom());
+  let mut builder = PathBuilder::new();
+  builder.move_to(l + radius, t);
+  builder.line_to(r - radius, t);
+  builder.cubic_to(r - k, t, r, t + k, r, t + radius);
+  builder.line_to(r, b - radius);
+  builder.cubic_to(r, b - k, r - k, b, r - radius, b);
+  builder.line_to(l + radius, b);
+  builder.cubic_to(l + k, b, l, b - k, l, b - radius);
+  builder.line_to(l, t + radius);
+  builder.cubic_to(l, t + k, l + k, t, l + radius, t);
+  builder.close();
+  builder.finish()
+}
+
+fn polyline(points: &[f64], close: bool) -> Option<Path> {
+  let mut builder = PathBuilder::new();
+  let mut vertices = points.chunks_exact(2);
+  let first = vertices.next()?;
+  builder.move_to(first[0] as f32, first[1] as f32);
+  for vertex in vertices {
+    builder.line_to(vertex[0] as f32, vertex[1] as f32);
+  }
+  if close {
+    builder.close();
+  }
+  builder.finish()
+}
+
+fn path_data(d: &str) -> Result<Option<Path>> {
+  let mut builder = PathBuilder::new();
+  for segment in svgtypes::SimplifyingPathParser::from(d) {
+    match segment.map_err(|err| invalid(format!("invalid path data: {err}")))? {
+      svgtypes::SimplePathSegment::MoveTo { x, y } => builder.move_to(x as f32, y as f32),
+      svgtypes::SimplePathSegment::LineTo { x, y } => builder.line_to(x as f32, y as f32),
+      svgtypes::SimplePathSegment::CurveTo {
+        x1,
+        y1,
+        x2,
+        y2,
+        x,
+        y,
+      } => builder.cubic_to(
+        x1 as f32, y1 as f32, x2 as f32, y2 as f32, x as f32, y as f32,
+      ),
+      svgtypes::SimplePathSegment::Quadratic { x1, y1, x, y } => {
+        builder.quad_to(x1 as f32, y1 as f32, x as f32, y as f32)
+      }
+      svgtypes::SimplePathSegment::ClosePath => builder.close(),
+    }
+  }
+  Ok(builder.finish())
+}
+
+fn prepare(shape: Shape) -> Result<PreparedShape> {
+  let kind = shape.kind;
+  let points = || {
+    shape
+      .points
+      .as_deref()
+      .filter(|points| points.len() >= 4 && points.len() % 2 == 0)
+      .ok_or_else(|| {
+        invalid(format!(
+          "{kind:?} needs at least two `points` as x, y pairs"
+        ))
+      })
+  };
+  let rect = || {
+    Rect::from_xywh(
+      require(shape.x, kind, "x")?,
+      require(shape.y, kind, "y")?,
+      require(shape.width, kind, "width")?,
+      require(shape.height, kind, "height")?,
+    )
+    .ok_or_else(|| invalid(format!("{kind:?} has an empty or invalid size")))
+  };
+  let path = match kind {
+    ShapeKind::Rect => rounded_rect(rect()?, shape.radius.unwrap_or(0.0) as f32),
+    ShapeKind::RoundedRect => rounded_rect(rect()?, require(shape.radius, kind, "radius")?),
+    ShapeKind::Circle => PathBuilder::from_circle(
+      require(shape.x, kind, "x")?,
+      require(shape.y, kind, "y")?,
+      require(shape.radius, kind, "radius")?,
+    ),
+    ShapeKind::Line => polyline(points()?, false),
+    ShapeKind::Polygon => polyline(points()?, true),
+    ShapeKind::Path => path_data(
+      shape
+        .d
+        .as_deref()
+        .ok_or_else(|| invalid("Path needs `d`".to_owned()))?,
+    )?,
+  }
+  .ok_or_else(|| invalid(format!("{kind:?} has no drawable geometry")))?;
+  let fill = match (&shape.gradient, &shape.fill) {
+    // A line has no interior.
+    _ if matches!(kind, ShapeKind::Line) => None,
+    (Some(gradient), _) => Some(gradient_paint(gradient)?),
+    (None, Some(fill)) => Some(paint(fill)?),
+    (None, None) => None,
+  };
+  let stroke_paint = match (&shape.stroke, kind) {
+    (Some(stroke), _) => Some(paint(stroke)?),
+    // A line is only visible stroked; default to black.
+    (None, ShapeKind::Line) => Some(paint("black")?),
+    (None, _) => None,
+  };
+  let stroke = stroke_paint.map(|paint| {
+    let stroke = Stroke {
+      width: shape.stroke_width.unwrap_or(1.0).max(0.0) as f32,
+      ..Default::default()
+    };
+    (paint, stroke)
+  });
+  Ok(PreparedShape { path, fill, stroke })
+}
+
+/// Validate `shapes` and build their paths and paints.
+pub(crate) fn prepare_shapes(shapes: Vec<Shape>) -> Result<Vec<PreparedShape>> {
+  shapes.into_iter().map(prepare).collect()
+}
+
+/// Draw `shapes` in order onto a transparent `width`x`height` straight-alpha RGBA8 layer.
+pub(crate) fn rasterize_shapes(
+  shapes: &[PreparedShape],
+  width: u32,
+  height: u32,
+) -> Result<RgbaImage> {
+  let mut pixmap = Pixmap::new(width, height).ok_or_else(|| {
+    Error::from_reason(format!(
+      "Failed to allocate a {width}x{height} drawing layer"
+    ))
+  })?;
+  for shape in shapes {
+    if let Some(fill) = &shape.fill {
+      pixmap.fill_path(
+        &shape.path,
+        fill,
+        FillRule::Winding,
+        Transform::identity(),
+        None,
+      );
+    }
+    if let Some((paint, stroke)) = &shape.stroke {
+      pixmap.stroke_path(&shape.path, paint, stroke, Transform::identity(), None);
+    }
+  }
+  let data = pixmap.take_demultiplied();
+  RgbaImage::from_vec(width, height, data).ok_or_else(|| {
+    Error::new(
+      Status::InvalidArg,
+      "Drawing layer does not match its dimensions".to_owned(),
+    )
+  })
+}
+
+/// A white mask covering a `width`x`height` image for `roundCorners()`/`circle()`: the full
+/// rectangle with its corners rounded by `radius`, or without one, the largest centered circle.
+pub(crate) fn rasterize_mask_preset(
+  width: u32,
+  height: u32,
+  radius: Option<f32>,
+) -> Result<RgbaImage> {
+  let (w, h) = (width as f32, height as f32);
+  let path = match radius {
+    Some(radius) => Rect::from_xywh(0.0, 0.0, w, h).and_then(|rect| rounded_rect(rect, radius)),
+    None => PathBuilder::from_circle(w / 2.0, h / 2.0, w.min(h) / 2.0),
+  }
+  .ok_or_else(|| {
+    Error::new(
+      Status::InvalidArg,
+      format!("mask: cannot build a mask for a {width}x{height} image"),
+    )
+  })?;
+  let shape = PreparedShape {
+    path,
+    fill: Some(paint("white")?),
+    stroke: None,
+  };
+  rasterize_shapes(&[shape], width, height)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn shape(kind: ShapeKind) -> Shape {
+    Shape {
+      kind,
+      x: None,
+      y: None,
+      width: None,
+      height: None,
+      radius: None,
+      points: None,
+      d: None,
+      fill: None,
+      gradient: None,
+      stroke: None,
+      stroke_width: None,
+    }
+  }
+
+  fn draw(shapes: Vec<Shape>) -> RgbaImage {
+    rasterize_shapes(&prepare_shapes(shapes).unwrap(), 20, 20).unwrap()
+  }
+
+  #[test]
+  fn rect_fills_exactly_its_pixels() {
+    let image = draw(vec![Shape {
+      x: Some(2.0),
+      y: Some(3.0),
+      width: Some(4.0),
+      height: Some(5.0),
+      fill: Some("red".to_owned()),
+      ..shape(ShapeKind::Rect)
+    }]);
+    assert_eq!(image.get_pixel(2, 3).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(5, 7).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(6, 7).0[3], 0);
+    assert_eq!(image.get_pixel(1, 3).0[3], 0);
+  }
+
+  #[test]
+  fn rounded_rect_clears_its_corners() {
+    let image = draw(vec![Shape {
+      x: Some(0.0),
+      y: Some(0.0),
+      width: Some(20.0),
+      height: Some(20.0),
+      radius: Some(8.0),
+      fill: Some("blue".to_owned()),
+      ..shape(ShapeKind::RoundedRect)
+    }]);
+    assert_eq!(image.get_pixel(0, 0).0[3], 0);
+    assert_eq!(image.get_pixel(10, 0).0, [0, 0, 255, 255]);
+  }
+
+  #[test]
+  fn circle_path_and_polygon_cover_their_interiors() {
+    let image = draw(vec![
+      Shape {
+        x: Some(5.0),
+        y: Some(5.0),
+        radius: Some(4.0),
+        fill: Some("#0f0".to_owned()),
+        ..shape(ShapeKind::Circle)
+      },
+      Shape {
+        d: Some("M 12 12 h 6 v 6 h -6 z".to_owned()),
+        fill: Some("white".to_owned()),
+        ..shape(ShapeKind::Path)
+      },
+      Shape {
+        points: Some(vec![0.0, 20.0, 8.0, 12.0, 8.0, 20.0]),
+        fill: Some("black".to_owned()),
+        ..shape(ShapeKind::Polygon)
+      },
+    ]);
+    assert_eq!(image.get_pixel(5, 5).0, [0, 255, 0, 255]);
+    assert_eq!(image.get_pixel(0, 0).0[3], 0);
+    assert_eq!(image.get_pixel(15, 15).0, [255, 255, 255, 255]);
+    assert_eq!(image.get_pixel(7, 19).0, [0, 0, 0, 255]);
+  }
+
+  #[test]
+  fn line_is_stroked_and_gradient_interpolates() {
+    let image = draw(vec![
+      Shape {
+        points: Some(vec![0.0, 1.5, 20.0, 1.5]),
+        stroke_width: Some(1.0),
+        ..shape(ShapeKind::Line)
+      },
+      Shape {
+        x: Some(0.0),
+        y: Some(10.0),
+        width: Some(20.0),
+        height: Some(10.0),
+        gradient: Some(Gradient {
+          kind: None,
+          x1: 0.0,
+          y1: 0.0,
+          x2: Some(20.0),
+          y2: Some(0.0),
+          radius: None,
+          stops: vec![
+            GradientStop {
+              offset: 0.0,
+              color: "black".to_owned(),
+            },
+            GradientStop {
+              offset: 1.0,
+              color: "white".to_owned(),
+            },
+          ],
+        }),
+        ..shape(ShapeKind::Rect)
+      },
+    ]);
+    assert_eq!(image.get_pixel(10, 1).0, [0, 0, 0, 255]);
+    let left = image.get_pixel(1, 15).0[0];
+    let right = image.get_pixel(18, 15).0[0];
+    assert!(
+      left < 40 && right > 215,
+      "gradient runs dark to light: {left} -> {right}"
+    );
+  }
+
+  #[test]
+  fn invalid_shapes_are_rejected() {
+    let missing = prepare_shapes(vec![shape(ShapeKind::Circle)]);
+    assert!(missing.is_err());
+    let bad_color = prepare_shapes(vec![Shape {
+      x: Some(1.0),
+      y: Some(1.0),
+      radius: Some(1.0),
+      fill: Some("nope".to_owned()),
+      ..shape(ShapeKind::Circle)
+    }]);
+    assert!(bad_color.is_err());
+    let bad_path = prepare_shapes(vec![Shape {
+      d: Some("M 0 0 Q".to_owned()),
+      ..shape(ShapeKind::Path)
+    }]);
+    assert!(bad_path.is_err());
+  }
+
+  #[test]
+  fn mask_presets_cover_the_image_except_the_corners() {
+    let rounded = rasterize_mask_preset(20, 10, Some(4.0)).unwrap();
+    assert_eq!(rounded.get_pixel(0, 0).0[3], 0);
+    assert_eq!(rounded.get_pixel(10, 0).0, [255, 255, 255, 255]);
+    assert_eq!(rounded.get_pixel(0, 5).0, [255, 255, 255, 255]);
+
+    // A non-square image gets the largest centered circle, not an ellipse.
+    let circle = rasterize_mask_preset(20, 10, None).unwrap();
+    assert_eq!(circle.get_pixel(10, 5).0, [255, 255, 255, 255]);
+    assert_eq!(circle.get_pixel(3, 5).0[3], 0);
+    assert_eq!(circle.get_pixel(19, 9).0[3], 0);
+  }
+}
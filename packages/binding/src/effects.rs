@@ -0,0 +1,331 @@
+use image::{DynamicImage, ImageBuffer, Rgba, Rgba32FImage};
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::blur::{BlurPrecision, blur_plane};
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct ShadowOptions {
+  /// Horizontal offset in pixels. Defaults to `4`.
+  pub offset_x: Option<f64>,
+  /// Vertical offset in pixels. Defaults to `4`.
+  pub offset_y: Option<f64>,
+  /// Gaussian blur sigma in pixels. Defaults to `4`.
+  pub blur: Option<f64>,
+  /// Grow the shadow's shape by this many pixels before blurring. Defaults to `0`.
+  pub spread: Option<f64>,
+  /// Support CSS3 color, e.g. rgba(255, 255, 255, .8). Defaults to `rgba(0, 0, 0, 0.5)`.
+  pub color: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct GlowOptions {
+  /// Gaussian blur sigma in pixels. Defaults to `8`.
+  pub blur: Option<f64>,
+  /// Grow the glow's shape by this many pixels before blurring. Defaults to `0`.
+  pub spread: Option<f64>,
+  /// Support CSS3 color, e.g. rgba(255, 255, 255, .8). Defaults to `white`.
+  pub color: Option<String>,
+}
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct BorderOptions {
+  /// Stroke width in pixels, outside the image's opaque shape.
+  pub width: f64,
+  /// Support CSS3 color, e.g. rgba(255, 255, 255, .8). Defaults to `black`.
+  pub color: Option<String>,
+}
+
+/// A colored layer derived from an image's alpha — grown by `spread`, blurred by `blur` and
+/// shifted by the offset — drawn behind the image. Shadows, glows and borders are all this.
+#[derive(Clone)]
+pub(crate) struct AlphaEffect {
+  pub(crate) offset_x: i64,
+  pub(crate) offset_y: i64,
+  blur: f32,
+  spread: f32,
+  color: [f32; 4],
+}
+
+fn length(name: &str, value: Option<f64>, default: f64) -> Result<f32> {
+  let value = value.unwrap_or(default);
+  if !value.is_finite() || value < 0.0 {
+    return Err(Error::new(
+      Status::InvalidArg,
+      format!("`{name}` must be a non-negative number, got {value}"),
+    ));
+  }
+  Ok(value as f32)
+}
+
+fn offset(name: &str, value: Option<f64>, default: f64) -> Result<i64> {
+  let value = value.unwrap_or(default);
+  if !value.is_finite() {
+    return Err(Error::new(
+      Status::InvalidArg,
+      format!("`{name}` must be a finite number, got {value}"),
+    ));
+  }
+  // Bounded so canvas arithmetic can't overflow; anything this far away is rejected as too large.
+  Ok(value.round().clamp(-(u32::MAX as f64), u32::MAX as f64) as i64)
+}
+
+fn color(color: Option<&str>, default: &str) -> Result<[f32; 4]> {
+  let color = color
+    .unwrap_or(default)
+    .parse::<svgtypes::Color>()
+    .map_err(|err| Error::new(Status::InvalidArg, format!("invalid `color`: {err}")))?;
+  Ok([
+    color.red as f32 / 255.0,
+    color.green as f32 / 255.0,
+    color.blue as f32 / 255.0,
+    color.alpha as f32 / 255.0,
+  ])
+}
+
+impl AlphaEffect {
+  pub(crate) fn shadow(options: ShadowOptions) -> Result<Self> {
+    Ok(AlphaEffect {
+      offset_x: offset("offsetX", options.offset_x, 4.0)?,
+      offset_y: offset("offsetY", options.offset_y, 4.0)?,
+      blur: length("blur", options.blur, 4.0)?,
+      spread: length("spread", options.spread, 0.0)?,
+      color: color(options.color.as_deref(), "rgba(0, 0, 0, 0.5)")?,
+    })
+  }
+
+  pub(crate) fn glow(options: GlowOptions) -> Result<Self> {
+    Ok(AlphaEffect {
+      offset_x: 0,
+      offset_y: 0,
+      blur: length("blur", options.blur, 8.0)?,
+      spread: length("spread", options.spread, 0.0)?,
+      color: color(options.color.as_deref(), "white")?,
+    })
+  }
+
+  pub(crate) fn border(options: BorderOptions) -> Result<Self> {
+    Ok(AlphaEffect {
+      offset_x: 0,
+      offset_y: 0,
+      blur: 0.0,
+      spread: length("width", Some(options.width), 0.0)?,
+      color: color(options.color.as_deref(), "black")?,
+    })
+  }
+
+  /// How far the layer reaches beyond the image on every side (before the offset): the spread
+  /// plus three blur sigmas.
+  pub(crate) fn extent(&self) -> u32 {
+    (self.spread + 3.0 * self.blur)
+      .ceil()
+      .min(u32::MAX as f32 / 4.0) as u32
+  }
+
+  /// Render the layer for `image`. It is `extent()` pixels larger than the image on every side,
+  /// so its top-left corner sits at `(offset_x - extent, offset_y - extent)` from the image's.
+  pub(crate) fn layer(&self, image: &DynamicImage) -> Rgba32FImage {
+    let extent = self.extent();
+    let width = image.width() + 2 * extent;
+    let height = image.height() + 2 * extent;
+    let source = image.to_rgba32f();
+    let mut alpha = vec![0.0f32; width as usize * height as usize];
+    for (x, y, pixel) in source.enumerate_pixels() {
+      let a = pixel[3];
+      alpha[((y + extent) * width + x + extent) as usize] =
+        if a.is_nan() { 0.0 } else { a.clamp(0.0, 1.0) };
+    }
+    if self.spread > 0.0 {
+      dilate(&mut alpha, width, height, self.spread);
+    }
+    let (w, h) = (width as usize, height as usize);
+    blur_plane(&mut alpha, w, h, self.blur, BlurPrecision::Auto);
+    let [r, g, b, a] = self.color;
+    ImageBuffer::from_fn(width, height, |x, y| {
+      Rgba([r, g, b, alpha[y as usize * w + x as usize] * a])
+    })
+  }
+}
+
+/// Grow the opaque (`>= 0.5`) region of `alpha` by `radius` pixels, with round corners and an
+/// anti-aliased edge, using an exact Euclidean distance transform so the cost doesn't depend on
+/// the radius.
+fn dilate(alpha: &mut [f32], width: u32, height: u32, radius: f32) {
+  let (width, height) = (width as usize, height as usize);
+  let mut distance: Vec<f32> = alpha
+    .iter()
+    .map(|&a| if a >= 0.5 { 0.0 } else { f32::INFINITY })
+    .collect();
+  let len = width.max(height);
+  let mut line = vec![0.0f32; len];
+  let mut out = vec![0.0f32; len];
+  let mut hull = vec![0usize; len];
+  let mut bounds = vec![0.0f64; len + 1];
+  for x in 0..width {
+    for y in 0..height {
+      line[y] = distance[y * width + x];
+    }
+    distance_1d(&line[..height], &mut out, &mut hull, &mut bounds);
+    for y in 0..height {
+      distance[y * width + x] = out[y];
+    }
+  }
+  for y in 0..height {
+    let row = &mut distance[y * width..(y + 1) * width];
+    line[..width].copy_from_slice(row);
+    distance_1d(&line[..width], &mut out, &mut hull, &mut bounds);
+    row.copy_from_slice(&out[..width]);
+  }
+  // The region's edge lies half a pixel beyond its outermost centers, and a pixel is fully
+  // covered once the grown edge passes its far side: half a pixel more.
+  for (a, squared) in alpha.iter_mut().zip(distance) {
+    let coverage = (radius + 1.0 - squared.sqrt()).clamp(0.0, 1.0);
+    *a = a.max(coverage);
+  }
+}
+
+/// Squared distance transform of a sampled function (Felzenszwalb & Huttenlocher): `out[q]` is
+/// `min_p (q - p)^2 + f[p]`, with `INFINITY` in `f` where no region pixel has been reached yet.
+/// The parabola intersections are computed in f64: `q * q` outgrows f32's precision on large
+/// images.
+fn distance_1d(f: &[f32], out: &mut [f32], hull: &mut [usize], bounds: &mut [f64]) {
+  let n = f.len();
+  // Parabolas rooted at infinite samples never win; skip them so the hull stays finite.
+  let Some(first) = f.iter().position(|v| v.is_finite()) else {
+    out[..n].fill(f32::INFINITY);
+    return;
+  };
+  let mut k = 0;
+  hull[0] = first;
+  bounds[0] = f64::NEG_INFINITY;
+  bounds[1] = f64::INFINITY;
+  for q in first + 1..n {
+    if !f[q].is_finite() {
+      continue;
+    }
+    // `bounds[0]` is -inf, so this pops at most down to the first parabola.
+    let s = loop {
+      let p = hull[k];
+      let s =
+        ((f[q] as f64 + (q * q) as f64) - (f[p] as f64 + (p * p) as f64)) / (2.0 * (q - p) as f64);
+      if s > bounds[k] {
+        break s;
+      }
+      k -= 1;
+    };
+    k += 1;
+    hull[k] = q;
+    bounds[k] = s;
+    bounds[k + 1] = f64::INFINITY;
+  }
+  let mut k = 0;
+  for (q, value) in out[..n].iter_mut().enumerate() {
+    while bounds[k + 1] < q as f64 {
+      k += 1;
+    }
+    let p = hull[k];
+    let d = q as f32 - p as f32;
+    *value = d * d + f[p];
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use image::RgbaImage;
+
+  fn square(size: u32, inset: u32) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(size, size, |x, y| {
+      let inside = (inset..size - inset).contains(&x) && (inset..size - inset).contains(&y);
+      Rgba([255, 0, 0, if inside { 255 } else { 0 }])
+    }))
+  }
+
+  #[test]
+  fn distance_transform_matches_brute_force() {
+    let f = [
+      f32::INFINITY,
+      0.0,
+      f32::INFINITY,
+      f32::INFINITY,
+      f32::INFINITY,
+      0.0,
+      f32::INFINITY,
+    ];
+    let mut out = [0.0; 7];
+    distance_1d(&f, &mut out, &mut [0; 7], &mut [0.0; 8]);
+    assert_eq!(out, [1.0, 0.0, 1.0, 4.0, 1.0, 0.0, 1.0]);
+
+    let mut empty = [0.0; 3];
+    distance_1d(&[f32::INFINITY; 3], &mut empty, &mut [0; 3], &mut [0.0; 4]);
+    assert!(empty.iter().all(|v| v.is_infinite()));
+  }
+
+  #[test]
+  fn border_grows_the_shape_with_round_corners() {
+    let effect = AlphaEffect::border(BorderOptions {
+      width: 3.0,
+      color: Some("blue".to_owned()),
+    })
+    .unwrap();
+    assert_eq!(effect.extent(), 3);
+    let layer = effect.layer(&square(10, 0));
+    assert_eq!(layer.dimensions(), (16, 16));
+    // Straight edges grow by the full width...
+    assert_eq!(layer.get_pixel(0, 8).0, [0.0, 0.0, 1.0, 1.0]);
+    // ...while the corner is rounded off.
+    assert_eq!(layer.get_pixel(0, 0)[3], 0.0);
+    assert_eq!(layer.get_pixel(8, 8)[3], 1.0);
+  }
+
+  #[test]
+  fn shadow_is_blurred_and_offset() {
+    let effect = AlphaEffect::shadow(ShadowOptions {
+      offset_x: Some(2.0),
+      offset_y: Some(-1.0),
+      blur: Some(1.0),
+      ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(
+      (effect.offset_x, effect.offset_y, effect.extent()),
+      (2, -1, 3)
+    );
+    let layer = effect.layer(&square(10, 2));
+    // The center stays fully covered (times the default 0.5 alpha); the edge is soft.
+    let center = layer.get_pixel(8, 8).0;
+    assert_eq!(&center[..3], &[0.0, 0.0, 0.0]);
+    assert!((center[3] - 0.5).abs() < 0.01, "{center:?}");
+    let edge = layer.get_pixel(5, 8)[3];
+    assert!(edge > 0.1 && edge < 0.4, "{edge}");
+    assert!(layer.get_pixel(0, 0)[3] < 1e-3);
+  }
+
+  #[test]
+  fn invalid_options_are_rejected() {
+    assert!(
+      AlphaEffect::glow(GlowOptions {
+        blur: Some(-1.0),
+        ..Default::default()
+      })
+      .is_err()
+    );
+    assert!(
+      AlphaEffect::shadow(ShadowOptions {
+        offset_x: Some(f64::NAN),
+        ..Default::default()
+      })
+      .is_err()
+    );
+    assert!(
+      AlphaEffect::border(BorderOptions {
+        width: 1.0,
+        color: Some("nope".to_owned()),
+      })
+      .is_err()
+    );
+  }
+}
@@ -0,0 +1,284 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::avif::AvifConfig;
+use crate::encode_target::{EncodeTargetOptions, TargetFormat, TargetSearch};
+use crate::png::PngEncodeOptions;
+
+/// Web formats for `encodeAuto()`, `encodeMany()` and `generateVariants()`.
+#[napi]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OutputFormat {
+  Jpeg,
+  Png,
+  Webp,
+  Avif,
+}
+
+#[napi(object)]
+pub struct EncodeSpec {
+  pub format: OutputFormat,
+  /// Quality of `Jpeg`, `Webp` and `Avif` output, `1..=100`. Defaults to `90` for JPEG and WebP,
+  /// and to the AVIF encoder's default.
+  pub quality: Option<u32>,
+  /// Lossless WebP instead of lossy. Only for `OutputFormat.Webp`.
+  pub lossless: Option<bool>,
+  /// Only for `OutputFormat.Png`.
+  pub png: Option<PngEncodeOptions>,
+  /// Only for `OutputFormat.Avif`; `quality` overrides `avif.quality`.
+  pub avif: Option<AvifConfig>,
+}
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct EncodeAutoOptions {
+  /// Candidate formats, e.g. the ones the client's `Accept` header allows. JPEG is skipped for
+  /// images with transparent pixels unless it is the only candidate. Defaults to
+  /// `[Avif, Webp, Jpeg]`.
+  pub formats: Option<Vec<OutputFormat>>,
+  /// Quality of the lossy candidates, `1..=100`. Defaults to each encoder's own default.
+  pub quality: Option<u32>,
+  /// Quality-match the lossy candidates instead of using one `quality` for all: each is encoded
+  /// at the lowest quality whose SSIM against the image reaches this value (like
+  /// `encodeToTarget()`), so the smallest result is also no worse than the others.
+  pub min_ssim: Option<f64>,
+  /// Also race lossless WebP (when `Webp` is a candidate), and let lossless results win ties.
+  /// PNG is always lossless. Defaults to `false`.
+  pub prefer_lossless: Option<bool>,
+}
+
+#[napi(object)]
+pub struct EncodeAutoResult {
+  pub format: OutputFormat,
+  /// Whether `buffer` is lossless (PNG or lossless WebP).
+  pub lossless: bool,
+  /// The encoder quality, for lossy results encoded with an explicit or searched quality.
+  pub quality: Option<u32>,
+  /// SSIM against the image, when `minSsim` was set and the result is lossy.
+  pub ssim: Option<f64>,
+  pub buffer: Buffer,
+}
+
+/// How one candidate is encoded.
+pub(crate) enum AutoEncode {
+  /// Lossy at a fixed quality, or the encoder's default.
+  Quality(Option<u32>),
+  /// Lossy at the quality a fidelity search picks.
+  Search(TargetSearch),
+  Lossless,
+}
+
+pub(crate) struct AutoCandidate {
+  pub(crate) format: OutputFormat,
+  pub(crate) encode: AutoEncode,
+}
+
+pub struct AutoOutput {
+  pub(crate) format: OutputFormat,
+  pub(crate) lossless: bool,
+  pub(crate) quality: Option<u32>,
+  pub(crate) ssim: Option<f64>,
+  pub(crate) bytes: Vec<u8>,
+}
+
+impl From<AutoOutput> for EncodeAutoResult {
+  fn from(output: AutoOutput) -> Self {
+    EncodeAutoResult {
+      format: output.format,
+      lossless: output.lossless,
+      quality: output.quality,
+      ssim: output.ssim,
+      buffer: output.bytes.into(),
+    }
+  }
+}
+
+impl EncodeAutoOptions {
+  pub(crate) fn validate(&self) -> Result<()> {
+    if self
+      .formats
+      .as_ref()
+      .is_some_and(|formats| formats.is_empty())
+    {
+      return Err(Error::new(
+        Status::InvalidArg,
+        "encodeAuto: `formats` must not be empty".to_owned(),
+      ));
+    }
+    if let Some(quality) = self.quality
+      && !(1..=100).contains(&quality)
+    {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("encodeAuto: `quality` must be in 1..=100, got {quality}"),
+      ));
+    }
+    // The fidelity search validates `minSsim` the same way `encodeToTarget()` does.
+    self.candidates(false).map(|_| ())
+  }
+
+  /// The encodes to race for an image with or without transparent pixels, in `formats` order
+  /// (duplicates dropped).
+  pub(crate) fn candidates(&self, transparent: bool) -> Result<Vec<AutoCandidate>> {
+    let mut formats = self
+      .formats
+      .clone()
+      .unwrap_or_else(|| vec![OutputFormat::Avif, OutputFormat::Webp, OutputFormat::Jpeg]);
+    let mut seen = Vec::with_capacity(formats.len());
+    formats.retain(|format| {
+      let first = !seen.contains(format);
+      seen.push(*format);
+      first
+    });
+    if transparent && formats.len() > 1 {
+      formats.retain(|format| *format != OutputFormat::Jpeg);
+    }
+    let prefer_lossless = self.prefer_lossless.unwrap_or(false);
+    let mut candidates = Vec::with_capacity(formats.len() + 1);
+    for format in formats {
+      let target = match format {
+        OutputFormat::Png => {
+          candidates.push(AutoCandidate {
+            format,
+            encode: AutoEncode::Lossless,
+          });
+          continue;
+        }
+        OutputFormat::Jpeg => TargetFormat::Jpeg,
+        OutputFormat::Webp => TargetFormat::Webp,
+        OutputFormat::Avif => TargetFormat::Avif,
+      };
+      let encode = match self.min_ssim {
+        Some(min_ssim) => AutoEncode::Search(TargetSearch::new(EncodeTargetOptions {
+          format: target,
+          max_bytes: None,
+          min_ssim: Some(min_ssim),
+          max_delta_e: None,
+          min_quality: None,
+          max_quality: None,
+          avif: None,
+        })?),
+        None => AutoEncode::Quality(self.quality),
+      };
+      candidates.push(AutoCandidate { format, encode });
+      if prefer_lossless && format == OutputFormat::Webp {
+        candidates.push(AutoCandidate {
+          format,
+          encode: AutoEncode::Lossless,
+        });
+      }
+    }
+    Ok(candidates)
+  }
+}
+
+/// The smallest output; ties go to lossless outputs when `prefer_lossless`, else to the earlier
+/// candidate.
+pub(crate) fn pick_smallest(outputs: Vec<AutoOutput>, prefer_lossless: bool) -> Option<AutoOutput> {
+  outputs.into_iter().reduce(|best, output| {
+    let smaller = output.bytes.len() < best.bytes.len();
+    let tie_won = output.bytes.len() == best.bytes.len()
+      && prefer_lossless
+      && output.lossless
+      && !best.lossless;
+    if smaller || tie_won { output } else { best }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn output(format: OutputFormat, lossless: bool, len: usize) -> AutoOutput {
+    AutoOutput {
+      format,
+      lossless,
+      quality: None,
+      ssim: None,
+      bytes: vec![0; len],
+    }
+  }
+
+  #[test]
+  fn candidates_follow_formats_and_alpha() {
+    let formats = |options: &EncodeAutoOptions, has_alpha| {
+      options
+        .candidates(has_alpha)
+        .unwrap()
+        .iter()
+        .map(|c| (c.format, matches!(c.encode, AutoEncode::Lossless)))
+        .collect::<Vec<_>>()
+    };
+    let defaults = EncodeAutoOptions::default();
+    assert_eq!(
+      formats(&defaults, false),
+      [
+        (OutputFormat::Avif, false),
+        (OutputFormat::Webp, false),
+        (OutputFormat::Jpeg, false)
+      ]
+    );
+    // No JPEG for alpha, lossless WebP when preferred, duplicates dropped.
+    let options = EncodeAutoOptions {
+      formats: Some(vec![
+        OutputFormat::Webp,
+        OutputFormat::Jpeg,
+        OutputFormat::Png,
+        OutputFormat::Webp,
+      ]),
+      prefer_lossless: Some(true),
+      ..Default::default()
+    };
+    assert_eq!(
+      formats(&options, true),
+      [
+        (OutputFormat::Webp, false),
+        (OutputFormat::Webp, true),
+        (OutputFormat::Png, true)
+      ]
+    );
+    let only_jpeg = EncodeAutoOptions {
+      formats: Some(vec![OutputFormat::Jpeg]),
+      ..Default::default()
+    };
+    assert_eq!(formats(&only_jpeg, true), [(OutputFormat::Jpeg, false)]);
+  }
+
+  #[test]
+  fn min_ssim_switches_lossy_candidates_to_a_search() {
+    let options = EncodeAutoOptions {
+      min_ssim: Some(0.95),
+      formats: Some(vec![OutputFormat::Avif, OutputFormat::Png]),
+      ..Default::default()
+    };
+    let candidates = options.candidates(false).unwrap();
+    assert!(matches!(candidates[0].encode, AutoEncode::Search(_)));
+    assert!(matches!(candidates[1].encode, AutoEncode::Lossless));
+    let invalid = EncodeAutoOptions {
+      min_ssim: Some(2.0),
+      ..Default::default()
+    };
+    assert!(invalid.validate().is_err());
+    let empty = EncodeAutoOptions {
+      formats: Some(vec![]),
+      ..Default::default()
+    };
+    assert!(empty.validate().is_err());
+  }
+
+  #[test]
+  fn smallest_output_wins_and_lossless_takes_ties_when_preferred() {
+    let outputs = || {
+      vec![
+        output(OutputFormat::Avif, false, 300),
+        output(OutputFormat::Webp, false, 200),
+        output(OutputFormat::Webp, true, 200),
+      ]
+    };
+    let best = pick_smallest(outputs(), false).unwrap();
+    assert!(!best.lossless);
+    let best = pick_smallest(outputs(), true).unwrap();
+    assert!(best.lossless && best.format == OutputFormat::Webp);
+    assert!(pick_smallest(vec![], true).is_none());
+  }
+}
@@ -0,0 +1,352 @@
+use std::collections::{HashMap, hash_map::Entry};
+
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::avif::AvifConfig;
+
+/// Lossy formats whose quality `encodeToTarget()` can search.
+#[napi]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TargetFormat {
+  Jpeg,
+  Webp,
+  Avif,
+}
+
+#[napi(object)]
+#[derive(Clone)]
+pub struct EncodeTargetOptions {
+  pub format: TargetFormat,
+  /// Largest acceptable output in bytes. A hard limit: when it conflicts with `minSsim` or
+  /// `maxDeltaE`, the size wins and `targetMet` is `false`.
+  pub max_bytes: Option<u32>,
+  /// Smallest acceptable SSIM against the source, e.g. `0.98`.
+  pub min_ssim: Option<f64>,
+  /// Largest acceptable CIE76 ΔE between any source pixel and its encoded counterpart.
+  pub max_delta_e: Option<f64>,
+  /// Lowest quality to consider, `1..=100`. Defaults to `1`.
+  pub min_quality: Option<u32>,
+  /// Highest quality to consider, `1..=100`. Defaults to `100`.
+  pub max_quality: Option<u32>,
+  /// Base AVIF settings (`speed`, `chromaSubsampling`, ...); `quality` is searched. Only for
+  /// `TargetFormat.Avif`.
+  pub avif: Option<AvifConfig>,
+}
+
+#[napi(object)]
+pub struct EncodeTargetResult {
+  /// The chosen encoder quality.
+  pub quality: u32,
+  pub buffer: Buffer,
+  /// SSIM of `buffer` against the source, when `minSsim` or `maxDeltaE` was set.
+  pub ssim: Option<f64>,
+  /// Largest CIE76 ΔE of `buffer` against the source, when `minSsim` or `maxDeltaE` was set.
+  pub max_delta_e: Option<f64>,
+  /// Whether every requested target holds for `buffer`.
+  pub target_met: bool,
+  /// How many qualities were encoded during the search.
+  pub attempts: u32,
+}
+
+/// The validated search: the constraints and the quality range.
+#[derive(Clone)]
+pub(crate) struct TargetSearch {
+  pub(crate) format: TargetFormat,
+  pub(crate) avif: Option<AvifConfig>,
+  max_bytes: Option<usize>,
+  min_ssim: Option<f64>,
+  max_delta_e: Option<f64>,
+  min_quality: u32,
+  max_quality: u32,
+}
+
+pub struct TargetOutput {
+  pub(crate) quality: u32,
+  pub(crate) bytes: Vec<u8>,
+  pub(crate) score: Option<Score>,
+  pub(crate) target_met: bool,
+  pub(crate) attempts: u32,
+}
+
+impl From<TargetOutput> for EncodeTargetResult {
+  fn from(output: TargetOutput) -> Self {
+    EncodeTargetResult {
+      quality: output.quality,
+      buffer: output.bytes.into(),
+      ssim: output.score.map(|score| score.ssim),
+      max_delta_e: output.score.map(|score| score.max_delta_e),
+      target_met: output.target_met,
+      attempts: output.attempts,
+    }
+  }
+}
+
+/// How close a candidate is to the source.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Score {
+  pub(crate) ssim: f64,
+  pub(crate) max_delta_e: f64,
+}
+
+struct Candidate {
+  bytes: Vec<u8>,
+  score: Option<Score>,
+}
+
+impl TargetSearch {
+  pub(crate) fn new(options: EncodeTargetOptions) -> Result<Self> {
+    let invalid = |message: String| Err(Error::new(Status::InvalidArg, message));
+    if options.max_bytes.is_none() && options.min_ssim.is_none() && options.max_delta_e.is_none() {
+      return invalid(
+        "encodeToTarget: set at least one of `maxBytes`, `minSsim` or `maxDeltaE`".to_owned(),
+      );
+    }
+    if let Some(ssim) = options.min_ssim
+      && !(ssim.is_finite() && ssim <= 1.0)
+    {
+      return invalid(format!(
+        "encodeToTarget: `minSsim` must be at most 1, got {ssim}"
+      ));
+    }
+    if let Some(delta_e) = options.max_delta_e
+      && !(delta_e.is_finite() && delta_e >= 0.0)
+    {
+      return invalid(format!(
+        "encodeToTarget: `maxDeltaE` must be a finite ΔE >= 0, got {delta_e}"
+      ));
+    }
+    let min_quality = options.min_quality.unwrap_or(1);
+    let max_quality = options.max_quality.unwrap_or(100);
+    if !(1..=max_quality).contains(&min_quality) || max_quality > 100 {
+      return invalid(format!(
+        "encodeToTarget: quality range {min_quality}..={max_quality} is not within 1..=100"
+      ));
+    }
+    if options.avif.is_some() && options.format != TargetFormat::Avif {
+      return invalid("encodeToTarget: `avif` settings need `TargetFormat.Avif`".to_owned());
+    }
+    Ok(TargetSearch {
+      format: options.format,
+      avif: options.avif,
+      max_bytes: options.max_bytes.map(|bytes| bytes as usize),
+      min_ssim: options.min_ssim,
+      max_delta_e: options.max_delta_e,
+      min_quality,
+      max_quality,
+    })
+  }
+
+  fn wants_score(&self) -> bool {
+    self.min_ssim.is_some() || self.max_delta_e.is_some()
+  }
+
+  fn score_ok(&self, score: Score) -> bool {
+    self.min_ssim.is_none_or(|min| score.ssim >= min)
+      && self.max_delta_e.is_none_or(|max| score.max_delta_e <= max)
+  }
+
+  fn size_ok(&self, bytes: &[u8]) -> bool {
+    self.max_bytes.is_none_or(|max| bytes.len() <= max)
+  }
+
+  /// Binary-search the quality, assuming file size and fidelity both grow with it: first the
+  /// lowest quality meeting `minSsim`/`maxDeltaE` (or the highest quality when neither is set),
+  /// then, if that is over `maxBytes`, the highest quality under it. `encode` turns a quality
+  /// into bytes and `score` rates bytes against the source; each quality is encoded and scored at
+  /// most once.
+  pub(crate) fn run(
+    &self,
+    mut encode: impl FnMut(u32) -> Result<Vec<u8>>,
+    mut score: impl FnMut(&[u8]) -> Result<Score>,
+  ) -> Result<TargetOutput> {
+    let mut candidates: HashMap<u32, Candidate> = HashMap::new();
+    let mut attempt = |quality: u32| -> Result<(bool, bool)> {
+      let candidate = match candidates.entry(quality) {
+        Entry::Occupied(entry) => entry.into_mut(),
+        Entry::Vacant(entry) => {
+          let bytes = encode(quality)?;
+          let scored = if self.wants_score() {
+            Some(score(&bytes)?)
+          } else {
+            None
+          };
+          entry.insert(Candidate {
+            bytes,
+            score: scored,
+          })
+        }
+      };
+      Ok((
+        candidate.score.is_none_or(|score| self.score_ok(score)),
+        self.size_ok(&candidate.bytes),
+      ))
+    };
+
+    let (mut quality, mut met) = (self.max_quality, true);
+    if self.wants_score() {
+      match lowest_passing(self.min_quality, self.max_quality, |q| {
+        attempt(q).map(|(score_ok, _)| score_ok)
+      })? {
+        Some(lowest) => quality = lowest,
+        None => met = false,
+      }
+    }
+    if self.max_bytes.is_some() && !attempt(quality)?.1 {
+      // The highest quality under the budget sits just below the first one over it. When even
+      // the lowest quality is over, settle for it (the final check flags the miss).
+      let first_too_big = lowest_passing(self.min_quality, quality - 1, |q| {
+        attempt(q).map(|(_, size_ok)| !size_ok)
+      })?;
+      quality = first_too_big
+        .unwrap_or(quality)
+        .saturating_sub(1)
+        .max(self.min_quality);
+      attempt(quality)?;
+    }
+    let attempts = candidates.len() as u32;
+    let chosen = candidates
+      .remove(&quality)
+      .expect("the chosen quality was encoded");
+    if met {
+      met = chosen.score.is_none_or(|score| self.score_ok(score)) && self.size_ok(&chosen.bytes);
+    }
+    Ok(TargetOutput {
+      quality,
+      bytes: chosen.bytes,
+      score: chosen.score,
+      target_met: met,
+      attempts,
+    })
+  }
+}
+
+/// The lowest `q` in `low..=high` for which `passes` holds, assuming it holds for every quality
+/// above the first passing one. `None` when none passes (or the range is empty).
+fn lowest_passing(
+  low: u32,
+  high: u32,
+  mut passes: impl FnMut(u32) -> Result<bool>,
+) -> Result<Option<u32>> {
+  if low > high || !passes(high)? {
+    return Ok(None);
+  }
+  let (mut low, mut high) = (low, high);
+  while low < high {
+    let mid = low + (high - low) / 2;
+    if passes(mid)? {
+      high = mid;
+    } else {
+      low = mid + 1;
+    }
+  }
+  Ok(Some(low))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn options(format: TargetFormat) -> EncodeTargetOptions {
+    EncodeTargetOptions {
+      format,
+      max_bytes: None,
+      min_ssim: None,
+      max_delta_e: None,
+      min_quality: None,
+      max_quality: None,
+      avif: None,
+    }
+  }
+
+  /// A fake encoder: `10 * quality` bytes, SSIM `quality / 100`.
+  fn fake(search: &TargetSearch) -> TargetOutput {
+    search
+      .run(
+        |quality| Ok(vec![quality as u8; 10 * quality as usize]),
+        |bytes| {
+          Ok(Score {
+            ssim: bytes[0] as f64 / 100.0,
+            max_delta_e: 100.0 - bytes[0] as f64,
+          })
+        },
+      )
+      .unwrap()
+  }
+
+  #[test]
+  fn finds_the_lowest_quality_meeting_the_fidelity_target() {
+    let search = TargetSearch::new(EncodeTargetOptions {
+      min_ssim: Some(0.9),
+      ..options(TargetFormat::Webp)
+    })
+    .unwrap();
+    let output = fake(&search);
+    assert_eq!(output.quality, 90);
+    assert!(output.target_met);
+    assert!(output.attempts <= 8, "binary search, {}", output.attempts);
+    assert_eq!(output.score.unwrap().ssim, 0.9);
+  }
+
+  #[test]
+  fn finds_the_highest_quality_under_the_size_budget() {
+    let search = TargetSearch::new(EncodeTargetOptions {
+      max_bytes: Some(425),
+      ..options(TargetFormat::Jpeg)
+    })
+    .unwrap();
+    let output = fake(&search);
+    assert_eq!((output.quality, output.bytes.len()), (42, 420));
+    assert!(output.target_met && output.score.is_none());
+  }
+
+  #[test]
+  fn size_budget_wins_over_fidelity() {
+    let search = TargetSearch::new(EncodeTargetOptions {
+      max_bytes: Some(500),
+      max_delta_e: Some(20.0),
+      ..options(TargetFormat::Avif)
+    })
+    .unwrap();
+    let output = fake(&search);
+    assert_eq!(output.quality, 50);
+    assert!(!output.target_met);
+    let within = TargetSearch::new(EncodeTargetOptions {
+      max_bytes: Some(900),
+      max_delta_e: Some(20.0),
+      ..options(TargetFormat::Avif)
+    })
+    .unwrap();
+    let output = fake(&within);
+    assert_eq!(output.quality, 80);
+    assert!(output.target_met);
+    // Nothing fits: the lowest quality is returned, flagged.
+    let tiny = TargetSearch::new(EncodeTargetOptions {
+      max_bytes: Some(5),
+      min_quality: Some(20),
+      ..options(TargetFormat::Avif)
+    })
+    .unwrap();
+    let output = fake(&tiny);
+    assert_eq!(output.quality, 20);
+    assert!(!output.target_met);
+  }
+
+  #[test]
+  fn rejects_searches_without_a_target_or_range() {
+    assert!(TargetSearch::new(options(TargetFormat::Jpeg)).is_err());
+    let inverted = EncodeTargetOptions {
+      max_bytes: Some(1),
+      min_quality: Some(80),
+      max_quality: Some(40),
+      ..options(TargetFormat::Jpeg)
+    };
+    assert!(TargetSearch::new(inverted).is_err());
+    let avif_settings = EncodeTargetOptions {
+      max_bytes: Some(1),
+      avif: Some(Default::default()),
+      ..options(TargetFormat::Jpeg)
+    };
+    assert!(TargetSearch::new(avif_settings).is_err());
+  }
+}
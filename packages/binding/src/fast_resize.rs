@@ -1,9 +1,13 @@
 use fast_image_resize as fr;
-use fr::{FilterType, images::Image};
+use fr::FilterType;
 use image::DynamicImage;
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 
+use crate::smart_crop::{CropBox, CropStrategy, cover_window, crop_box_from_window};
+use crate::transformer::{Gravity, embed, parse_background};
+use crate::utils::{fit_dimensions, from_linear_light, resize_is_blocked, to_linear_light};
+
 #[napi]
 #[derive(Default, Clone, Copy)]
 pub enum FastResizeFilter {
@@ -60,70 +64,102 @@ pub enum ResizeFit {
   /// Preserving aspect ratio
   /// resize the image to be as large as possible while ensuring its dimensions are less than or equal to both those specified.
   Inside,
+  /// Preserving aspect ratio
+  /// fit the image inside both provided dimensions, then letterbox it to exactly that size
+  /// with `background`, placed by `position`.
+  Contain,
+  /// Preserving aspect ratio
+  /// resize the image to be as small as possible while ensuring its dimensions are greater than or equal to both those specified.
+  Outside,
 }
 
 #[napi(object)]
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct FastResizeOptions {
   pub width: u32,
   pub height: Option<u32>,
   pub filter: Option<FastResizeFilter>,
   pub fit: Option<ResizeFit>,
+  /// Which part of the image `ResizeFit.Cover` keeps, or where `ResizeFit.Contain` places it.
+  /// Defaults to `Center`.
+  pub position: Option<Gravity>,
+  /// Content-aware crop anchor for `ResizeFit.Cover`; overrides `position`.
+  pub strategy: Option<CropStrategy>,
+  /// Letterbox color for `ResizeFit.Contain`. Support CSS3 color, e.g. rgba(255, 255, 255, .8).
+  /// Defaults to opaque black.
+  pub background: Option<String>,
+  /// Leave the image untouched if the resize would enlarge it on either axis.
+  pub without_enlargement: Option<bool>,
+  /// Leave the image untouched if the resize would shrink it on either axis.
+  pub without_reduction: Option<bool>,
+  /// Resample in linear light instead of on gamma-encoded sRGB values, which keeps fine detail
+  /// and high-contrast edges from darkening on downscale. Float images are already linear.
+  pub linear_light: Option<bool>,
 }
 
-pub fn fast_resize<'env>(
-  img: &'env DynamicImage,
-  options: FastResizeOptions,
-) -> Result<Image<'env>> {
+/// Resize `img` in place. Returns the source region kept by a `ResizeFit.Cover` crop.
+pub fn fast_resize(img: &mut DynamicImage, options: &FastResizeOptions) -> Result<Option<CropBox>> {
   let width = img.width();
   let height = img.height();
-  let mut rgba8 = img.to_rgba8();
-  let mut src_image = Image::from_slice_u8(width, height, rgba8.as_mut(), fr::PixelType::U8x4)
-    .map_err(|err| Error::new(Status::GenericFailure, format!("{err}")))?;
-
-  // Multiple RGB channels of source image by alpha channel
-  // (not required for the Nearest algorithm)
-  let alpha_mul_div = fr::MulDiv::default();
-  alpha_mul_div
-    .multiply_alpha_inplace(&mut src_image)
-    .map_err(|err| Error::new(Status::GenericFailure, format!("{err}")))?;
-
-  // Create container for data of destination image
-  let mut dst_width = options.width;
-  let mut dst_height = options
+  let fit = options.fit.unwrap_or_default();
+  let position = options.position.unwrap_or_default();
+  let target_width = options.width;
+  let target_height = options
     .height
-    .unwrap_or_else(|| (options.width as f32 / img.width() as f32 * img.height() as f32) as u32);
+    .unwrap_or_else(|| (options.width as f32 / width as f32 * height as f32) as u32);
+  let (dst_width, dst_height) = match fit {
+    ResizeFit::Cover => (target_width, target_height),
+    _ => fit_dimensions(width, height, target_width, target_height, fit),
+  };
+  if resize_is_blocked(
+    (width, height),
+    fit_dimensions(width, height, target_width, target_height, fit),
+    options.without_enlargement.unwrap_or(false),
+    options.without_reduction.unwrap_or(false),
+  ) {
+    return Ok(None);
+  }
+  let background = parse_background(options.background.as_deref())?;
 
+  // Resize the native pixel layout (U8/U16/F32, 1-4 channels) so the source color type and
+  // depth survive. fast_image_resize premultiplies/demultiplies alpha internally
+  // (`mul_div_alpha`, on by default) for the alpha-carrying types.
   let mut resize_options = fr::ResizeOptions {
     algorithm: fr::ResizeAlg::Convolution(options.filter.unwrap_or_default().into()),
     ..Default::default()
   };
 
-  match options.fit.unwrap_or_default() {
-    ResizeFit::Cover => {
-      resize_options = resize_options
-        .fit_into_destination(Some(((dst_width as f64) / 2.0, (dst_height as f64) / 2.0)));
-    }
-    ResizeFit::Fill => {}
-    ResizeFit::Inside => {
-      let (width, height) =
-        crate::utils::resize_dimensions(width, height, dst_width, dst_height, false);
-      dst_width = width;
-      dst_height = height;
-    }
+  let mut crop_box = None;
+  if let ResizeFit::Cover = fit {
+    // Crop an explicit source window placed by `position`/`strategy` rather than using
+    // `fit_into_destination`, whose centering argument is a 0..1 fraction, not pixels.
+    let window = cover_window(img, dst_width, dst_height, position, options.strategy);
+    let (left, top, crop_width, crop_height) = window;
+    resize_options = resize_options.crop(left, top, crop_width, crop_height);
+    crop_box = Some(crop_box_from_window(window, width, height));
   }
-  let mut dst_image = Image::new(dst_width, dst_height, src_image.pixel_type());
+  let original_color = img.color();
+  if options.linear_light.unwrap_or(false)
+    && let Some(linear) = to_linear_light(img)
+  {
+    *img = linear;
+  }
+  // Create container for data of destination image
+  let mut dst_image = DynamicImage::new(dst_width, dst_height, img.color());
 
   // Create Resizer instance and resize source image
   // into buffer of destination image
   let mut resizer = fr::Resizer::new();
   resizer
-    .resize(&src_image, &mut dst_image, Some(&resize_options))
+    .resize(&*img, &mut dst_image, Some(&resize_options))
     .map_err(|err| Error::new(Status::GenericFailure, format!("{err}")))?;
-
-  // Divide RGB channels of destination image by alpha
-  alpha_mul_div
-    .divide_alpha_inplace(&mut dst_image)
-    .map_err(|err| Error::new(Status::GenericFailure, format!("{err}")))?;
-  Ok(dst_image)
+  *img = if img.color() == original_color {
+    dst_image
+  } else {
+    from_linear_light(&dst_image, original_color)
+  };
+  if let ResizeFit::Contain = fit {
+    *img = embed(img, target_width, target_height, position, background);
+  }
+  Ok(crop_box)
 }
@@ -1,5 +1,7 @@
 use std::io::Cursor;
+use std::panic::AssertUnwindSafe;
 
+use image::{DynamicImage, ImageBuffer};
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
 
@@ -114,6 +116,62 @@ unsafe fn moz_jpeg_compress(
   }
 }
 
+/// Decode a JPEG at `1/denom` of its size (`denom` is 2, 4 or 8) with libjpeg's DCT scaling, for
+/// shrink-on-load: the skipped high-frequency coefficients are never inverse-transformed, so this
+/// is much cheaper than a full decode plus downscale. Produces `Luma8` or `Rgb8`, like the `image`
+/// decoder. Returns `None` for CMYK/YCCK input, which is left to the regular decoder, and on any
+/// libjpeg error or warning (e.g. a truncated file, which libjpeg would fill with gray) so the
+/// regular decoder reports it.
+pub(crate) fn decode_jpeg_scaled(input: &[u8], denom: u32) -> Option<DynamicImage> {
+  // Kept outside `catch_unwind` so the decompressor is destroyed even when libjpeg unwinds out
+  // of it through `unwind_error_exit`, instead of leaking its memory pools.
+  let mut err_handler = unsafe { create_error_handler() };
+  let mut de_c_info: mozjpeg_sys::jpeg_decompress_struct = unsafe { std::mem::zeroed() };
+  de_c_info.common.err = &mut err_handler;
+  let decoded = std::panic::catch_unwind(AssertUnwindSafe(|| unsafe {
+    mozjpeg_sys::jpeg_create_decompress(&mut de_c_info);
+    #[cfg(any(target_os = "windows", target_arch = "arm", target_arch = "wasm32"))]
+    mozjpeg_sys::jpeg_mem_src(&mut de_c_info, input.as_ptr(), input.len() as u32);
+    #[cfg(not(any(target_os = "windows", target_arch = "arm", target_arch = "wasm32")))]
+    mozjpeg_sys::jpeg_mem_src(&mut de_c_info, input.as_ptr(), input.len() as u64);
+    mozjpeg_sys::jpeg_read_header(&mut de_c_info, 1);
+    let out_color_space = match de_c_info.jpeg_color_space {
+      mozjpeg_sys::J_COLOR_SPACE::JCS_GRAYSCALE => mozjpeg_sys::J_COLOR_SPACE::JCS_GRAYSCALE,
+      mozjpeg_sys::J_COLOR_SPACE::JCS_YCbCr | mozjpeg_sys::J_COLOR_SPACE::JCS_RGB => {
+        mozjpeg_sys::J_COLOR_SPACE::JCS_RGB
+      }
+      _ => return None,
+    };
+    de_c_info.out_color_space = out_color_space;
+    de_c_info.scale_num = 1;
+    de_c_info.scale_denom = denom;
+    mozjpeg_sys::jpeg_start_decompress(&mut de_c_info);
+    let width = de_c_info.output_width;
+    let height = de_c_info.output_height;
+    let row_len = width as usize * de_c_info.output_components as usize;
+    let mut data = vec![0u8; row_len * height as usize];
+    while de_c_info.output_scanline < height {
+      let mut row = data
+        .as_mut_ptr()
+        .add(de_c_info.output_scanline as usize * row_len);
+      mozjpeg_sys::jpeg_read_scanlines(&mut de_c_info, &mut row, 1);
+    }
+    mozjpeg_sys::jpeg_finish_decompress(&mut de_c_info);
+    Some((width, height, out_color_space, data))
+  }));
+  unsafe { mozjpeg_sys::jpeg_destroy_decompress(&mut de_c_info) };
+  if err_handler.num_warnings > 0 {
+    return None;
+  }
+  let (width, height, color_space, data) = decoded.ok()??;
+  match color_space {
+    mozjpeg_sys::J_COLOR_SPACE::JCS_GRAYSCALE => {
+      ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageLuma8)
+    }
+    _ => ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8),
+  }
+}
+
 unsafe fn create_error_handler() -> mozjpeg_sys::jpeg_error_mgr {
   unsafe {
     let mut err: mozjpeg_sys::jpeg_error_mgr = std::mem::zeroed();
@@ -140,10 +198,17 @@ unsafe extern "C-unwind" fn unwind_error_exit(cinfo: &mut mozjpeg_sys::jpeg_comm
   std::panic::resume_unwind(Box::new(message))
 }
 
+/// Print nothing, but count warnings (`level < 0`) in `num_warnings` like libjpeg's own
+/// `emit_message`, so callers can reject files libjpeg only warned about.
 unsafe extern "C-unwind" fn silence_message(
-  _cinfo: &mut mozjpeg_sys::jpeg_common_struct,
-  _level: std::os::raw::c_int,
+  cinfo: &mut mozjpeg_sys::jpeg_common_struct,
+  level: std::os::raw::c_int,
 ) {
+  if level < 0
+    && let Some(err) = unsafe { cinfo.err.as_mut() }
+  {
+    err.num_warnings += 1;
+  }
 }
 
 pub struct ThreadsafeMozjpegCompressOutput {
@@ -6,8 +6,24 @@
 // executes under Valgrind, which binds eagerly and would abort on them otherwise).
 // `binding` is in the crate's default features, so the shipped addon is unchanged.
 #[cfg(feature = "binding")]
+mod atlas;
+#[cfg(feature = "binding")]
 pub mod avif;
 #[cfg(feature = "binding")]
+mod blur;
+#[cfg(feature = "binding")]
+mod compare;
+#[cfg(feature = "binding")]
+mod convolve;
+#[cfg(feature = "binding")]
+mod draw;
+#[cfg(feature = "binding")]
+mod effects;
+#[cfg(feature = "binding")]
+mod encode_auto;
+#[cfg(feature = "binding")]
+mod encode_target;
+#[cfg(feature = "binding")]
 mod fast_resize;
 #[cfg(feature = "binding")]
 pub mod heic;
@@ -17,6 +33,10 @@ pub mod jpeg;
 // perceptual color ASSIGNMENT metric (`pdist` in `quantize.rs`).
 mod lab;
 #[cfg(feature = "binding")]
+mod perceptual_hash;
+#[cfg(feature = "binding")]
+mod placeholder;
+#[cfg(feature = "binding")]
 pub mod png;
 mod quantize;
 // Runtime-dispatched SIMD kernels for the quantizer's nearest-palette argmin. Not
@@ -28,9 +48,22 @@ mod quantize_simd;
 /// may change without notice; nothing in the shipped JS surface depends on them.
 #[doc(hidden)]
 pub use quantize::{QuantizeConfig, QuantizeOutput, quantize_rgba};
+// Entropy/attention crop anchors for `ResizeFit::Cover` (both resize paths).
+#[cfg(feature = "binding")]
+mod smart_crop;
+#[cfg(feature = "binding")]
+mod stats;
+#[cfg(feature = "binding")]
+mod text;
+#[cfg(feature = "binding")]
+mod tile;
+#[cfg(feature = "binding")]
+mod tone;
 #[cfg(feature = "binding")]
 pub mod transformer;
 #[cfg(feature = "binding")]
 mod utils;
 #[cfg(feature = "binding")]
+mod variants;
+#[cfg(feature = "binding")]
 mod webp;
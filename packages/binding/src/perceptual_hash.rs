@@ -0,0 +1,212 @@
+use std::f32::consts::PI;
+
+use fast_image_resize as fr;
+use image::DynamicImage;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+/// How `perceptualHash()` turns the image into bits.
+#[napi]
+#[derive(Clone, Copy, Default)]
+pub enum HashAlgorithm {
+  /// aHash: each bit is whether a cell of the `size`x`size` thumbnail is brighter than the mean.
+  /// Fastest, but sensitive to gamma and contrast changes.
+  Average,
+  /// dHash: each bit is whether a cell is brighter than its right neighbour. Robust to brightness
+  /// and contrast changes.
+  Difference,
+  /// pHash: each bit is whether a low-frequency DCT coefficient of a `4 * size` thumbnail is
+  /// above the median of all but the DC one. Slowest, and the most robust to resizing,
+  /// compression and small edits.
+  #[default]
+  Dct,
+}
+
+#[napi(object)]
+#[derive(Clone, Copy, Default)]
+pub struct PerceptualHashOptions {
+  /// Defaults to `HashAlgorithm.Dct`.
+  pub algorithm: Option<HashAlgorithm>,
+  /// Hash side, `2..=32`; the hash has `size * size` bits. Defaults to `8` (64 bits).
+  pub size: Option<u32>,
+}
+
+impl PerceptualHashOptions {
+  pub(crate) fn resolve(self) -> Result<(HashAlgorithm, u32)> {
+    let size = self.size.unwrap_or(8);
+    if !(2..=32).contains(&size) {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("perceptualHash: `size` must be in 2..=32, got {size}"),
+      ));
+    }
+    Ok((self.algorithm.unwrap_or_default(), size))
+  }
+}
+
+/// The perceptual hash of `image` as a string of `size * size` `'0'`/`'1'` characters, most
+/// significant (top-left) bit first. Alpha is ignored.
+pub(crate) fn perceptual_hash(
+  image: &DynamicImage,
+  algorithm: HashAlgorithm,
+  size: u32,
+) -> Result<String> {
+  // Downscale in the image's own layout first, like `fast_resize`, so only the thumbnail is
+  // converted to gray.
+  let thumbnail = |width: u32, height: u32| -> Result<Vec<f32>> {
+    let mut thumbnail = DynamicImage::new(width, height, image.color());
+    let options = fr::ResizeOptions {
+      algorithm: fr::ResizeAlg::Convolution(fr::FilterType::Lanczos3),
+      ..Default::default()
+    };
+    fr::Resizer::new()
+      .resize(image, &mut thumbnail, Some(&options))
+      .map_err(|err| Error::new(Status::GenericFailure, format!("{err}")))?;
+    Ok(thumbnail.to_luma32f().into_raw())
+  };
+  let n = size as usize;
+  let bits: Vec<bool> = match algorithm {
+    HashAlgorithm::Average => {
+      let cells = thumbnail(size, size)?;
+      let mean = cells.iter().sum::<f32>() / cells.len() as f32;
+      cells.iter().map(|&v| v > mean).collect()
+    }
+    HashAlgorithm::Difference => {
+      let cells = thumbnail(size + 1, size)?;
+      cells
+        .chunks_exact(n + 1)
+        .flat_map(|row| row.windows(2).map(|pair| pair[1] > pair[0]))
+        .collect()
+    }
+    HashAlgorithm::Dct => {
+      let side = n * 4;
+      let coefficients = low_frequency_dct(&thumbnail(side as u32, side as u32)?, side, n);
+      // The DC coefficient (the mean brightness) dwarfs the others, so leave it out of the median.
+      let mut sorted = coefficients[1..].to_vec();
+      sorted.sort_unstable_by(f32::total_cmp);
+      let median = if sorted.len().is_multiple_of(2) {
+        (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0
+      } else {
+        sorted[sorted.len() / 2]
+      };
+      coefficients.iter().map(|&v| v > median).collect()
+    }
+  };
+  Ok(
+    bits
+      .into_iter()
+      .map(|bit| if bit { '1' } else { '0' })
+      .collect(),
+  )
+}
+
+/// The top-left `keep`x`keep` (unnormalized) DCT-II coefficients of a `side`x`side` block, row
+/// by row.
+fn low_frequency_dct(block: &[f32], side: usize, keep: usize) -> Vec<f32> {
+  let basis: Vec<f32> = (0..keep)
+    .flat_map(|u| {
+      (0..side).map(move |x| (PI * (2 * x + 1) as f32 * u as f32 / (2 * side) as f32).cos())
+    })
+    .collect();
+  // Rows first: `rows[y * keep + u]` is row `y` projected on horizontal frequency `u`.
+  let mut rows = vec![0.0f32; side * keep];
+  for (y, row) in block.chunks_exact(side).enumerate() {
+    for u in 0..keep {
+      rows[y * keep + u] = row
+        .iter()
+        .zip(&basis[u * side..(u + 1) * side])
+        .map(|(p, b)| p * b)
+        .sum();
+    }
+  }
+  let mut coefficients = vec![0.0f32; keep * keep];
+  for v in 0..keep {
+    for u in 0..keep {
+      coefficients[v * keep + u] = (0..side)
+        .map(|y| rows[y * keep + u] * basis[v * side + y])
+        .sum();
+    }
+  }
+  coefficients
+}
+
+#[napi]
+/// Number of differing bits between two perceptual hashes of the same size. `0` means
+/// (perceptually) identical; for 64-bit hashes, up to ~10 usually means the same picture.
+pub fn hamming_distance(a: String, b: String) -> Result<u32> {
+  let is_bits = |hash: &str| hash.bytes().all(|c| c == b'0' || c == b'1');
+  if a.len() != b.len() || !is_bits(&a) || !is_bits(&b) {
+    return Err(Error::new(
+      Status::InvalidArg,
+      "hammingDistance: expected two bit strings of the same length".to_string(),
+    ));
+  }
+  Ok(a.bytes().zip(b.bytes()).filter(|(x, y)| x != y).count() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+  use image::{GrayImage, Luma, Rgb, RgbImage, imageops::FilterType};
+
+  use super::*;
+
+  fn gradient(width: u32, height: u32) -> DynamicImage {
+    DynamicImage::ImageLuma8(GrayImage::from_fn(width, height, |x, y| {
+      Luma([((x * 7 + y * 3) % 256) as u8])
+    }))
+  }
+
+  #[test]
+  fn difference_hash_of_a_horizontal_ramp_is_all_ones() {
+    let ramp = DynamicImage::ImageLuma8(GrayImage::from_fn(90, 80, |x, _| Luma([x as u8 * 2])));
+    let hash = perceptual_hash(&ramp, HashAlgorithm::Difference, 8).unwrap();
+    assert_eq!(hash, "1".repeat(64));
+  }
+
+  #[test]
+  fn hashes_survive_resizing_and_color_conversion() {
+    let original = gradient(256, 192);
+    let smaller = original.resize_exact(200, 150, FilterType::Triangle);
+    let colored = DynamicImage::ImageRgb8(RgbImage::from_fn(256, 192, |x, y| {
+      let v = ((x * 7 + y * 3) % 256) as u8;
+      Rgb([v, v, v])
+    }));
+    for algorithm in [
+      HashAlgorithm::Average,
+      HashAlgorithm::Difference,
+      HashAlgorithm::Dct,
+    ] {
+      let hash = perceptual_hash(&original, algorithm, 8).unwrap();
+      assert_eq!(hash.len(), 64);
+      let resized = perceptual_hash(&smaller, algorithm, 8).unwrap();
+      assert!(hamming_distance(hash.clone(), resized).unwrap() <= 6);
+      assert_eq!(perceptual_hash(&colored, algorithm, 8).unwrap(), hash);
+    }
+  }
+
+  #[test]
+  fn dct_hash_splits_the_ac_coefficients_at_their_median() {
+    // 63 AC coefficients: 31 above the median, 31 below and the median itself.
+    let hash = perceptual_hash(&gradient(128, 96), HashAlgorithm::Dct, 8).unwrap();
+    assert_eq!(hash[1..].matches('1').count(), 31);
+  }
+
+  #[test]
+  fn different_pictures_are_far_apart() {
+    let a = perceptual_hash(&gradient(64, 64), HashAlgorithm::Dct, 8).unwrap();
+    let flipped = perceptual_hash(&gradient(64, 64).fliph(), HashAlgorithm::Dct, 8).unwrap();
+    assert!(hamming_distance(a, flipped).unwrap() > 10);
+  }
+
+  #[test]
+  fn options_and_hamming_distance_validate_input() {
+    let options = PerceptualHashOptions {
+      size: Some(33),
+      ..Default::default()
+    };
+    assert!(options.resolve().is_err());
+    assert!(hamming_distance("0101".into(), "010".into()).is_err());
+    assert!(hamming_distance("0102".into(), "0101".into()).is_err());
+    assert_eq!(hamming_distance("0110".into(), "1111".into()).unwrap(), 2);
+  }
+}
@@ -0,0 +1,399 @@
+use std::f64::consts::PI;
+
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage, imageops::FilterType};
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+const BASE83: &[u8; 83] =
+  b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+/// ThumbHash only encodes images that fit in 100x100; larger ones are downscaled first.
+const THUMBHASH_MAX_SIDE: u32 = 100;
+
+#[napi(object)]
+#[derive(Clone, Copy, Default)]
+pub struct BlurhashOptions {
+  /// Horizontal components, `1..=9`. Defaults to `4`.
+  pub components_x: Option<u32>,
+  /// Vertical components, `1..=9`. Defaults to `3`.
+  pub components_y: Option<u32>,
+}
+
+impl BlurhashOptions {
+  pub(crate) fn components(self) -> Result<(u32, u32)> {
+    let component = |name: &str, value: Option<u32>, default: u32| {
+      let value = value.unwrap_or(default);
+      if !(1..=9).contains(&value) {
+        return Err(Error::new(
+          Status::InvalidArg,
+          format!("blurhash: `{name}` must be in 1..=9, got {value}"),
+        ));
+      }
+      Ok(value)
+    };
+    Ok((
+      component("componentsX", self.components_x, 4)?,
+      component("componentsY", self.components_y, 3)?,
+    ))
+  }
+}
+
+fn srgb_to_linear(value: u8) -> f64 {
+  let v = value as f64 / 255.0;
+  if v <= 0.04045 {
+    v / 12.92
+  } else {
+    ((v + 0.055) / 1.055).powf(2.4)
+  }
+}
+
+fn linear_to_srgb(value: f64) -> u8 {
+  let v = value.clamp(0.0, 1.0);
+  if v <= 0.003_130_8 {
+    (v * 12.92 * 255.0 + 0.5) as u8
+  } else {
+    ((1.055 * v.powf(1.0 / 2.4) - 0.055) * 255.0 + 0.5) as u8
+  }
+}
+
+fn sign_pow(value: f64, exp: f64) -> f64 {
+  value.abs().powf(exp).copysign(value)
+}
+
+fn encode_base83(value: u32, length: u32, out: &mut String) {
+  for i in 1..=length {
+    let digit = (value / 83u32.pow(length - i)) % 83;
+    out.push(BASE83[digit as usize] as char);
+  }
+}
+
+fn decode_base83(digits: &str) -> Result<u32> {
+  digits.bytes().try_fold(0u32, |value, byte| {
+    let digit = BASE83.iter().position(|&c| c == byte).ok_or_else(|| {
+      Error::new(
+        Status::InvalidArg,
+        format!("blurhash: invalid character {:?}", byte as char),
+      )
+    })?;
+    Ok(value * 83 + digit as u32)
+  })
+}
+
+/// The [BlurHash](https://blurha.sh) of `image` with `components_x`x`components_y` components,
+/// bit-compatible with the reference encoder. Alpha is ignored. The DCT is evaluated separably
+/// (rows first), so full-resolution images stay cheap.
+pub(crate) fn encode_blurhash(image: &RgbaImage, components_x: u32, components_y: u32) -> String {
+  let (width, height) = (image.width() as usize, image.height() as usize);
+  let (nx, ny) = (components_x as usize, components_y as usize);
+  let linear: Vec<f64> = (0..=255).map(srgb_to_linear).collect();
+  let cos_x: Vec<f64> = (0..nx)
+    .flat_map(|i| (0..width).map(move |x| (PI * i as f64 * x as f64 / width as f64).cos()))
+    .collect();
+  let mut factors = vec![[0.0f64; 3]; nx * ny];
+  let mut row_sums = vec![[0.0f64; 3]; nx];
+  for (y, row) in image.rows().enumerate() {
+    row_sums.fill([0.0; 3]);
+    for (x, pixel) in row.enumerate() {
+      let rgb = [0, 1, 2].map(|c| linear[pixel[c] as usize]);
+      for (i, sum) in row_sums.iter_mut().enumerate() {
+        let basis = cos_x[i * width + x];
+        for c in 0..3 {
+          sum[c] += basis * rgb[c];
+        }
+      }
+    }
+    for j in 0..ny {
+      let basis = (PI * j as f64 * y as f64 / height as f64).cos();
+      for (i, sum) in row_sums.iter().enumerate() {
+        for c in 0..3 {
+          factors[j * nx + i][c] += basis * sum[c];
+        }
+      }
+    }
+  }
+  for (k, factor) in factors.iter_mut().enumerate() {
+    let normalisation = if k == 0 { 1.0 } else { 2.0 };
+    for value in factor.iter_mut() {
+      *value *= normalisation / (width * height) as f64;
+    }
+  }
+
+  let mut hash = String::with_capacity(4 + 2 * factors.len());
+  encode_base83((components_x - 1) + (components_y - 1) * 9, 1, &mut hash);
+  let ac = &factors[1..];
+  let maximum = if ac.is_empty() {
+    encode_base83(0, 1, &mut hash);
+    1.0
+  } else {
+    let actual = ac
+      .iter()
+      .flatten()
+      .fold(0.0f64, |max, value| max.max(value.abs()));
+    let quantised = (actual * 166.0 - 0.5).floor().clamp(0.0, 82.0);
+    encode_base83(quantised as u32, 1, &mut hash);
+    (quantised + 1.0) / 166.0
+  };
+  let [r, g, b] = factors[0].map(|c| linear_to_srgb(c) as u32);
+  encode_base83((r << 16) + (g << 8) + b, 4, &mut hash);
+  for factor in ac {
+    let [r, g, b] = factor.map(|c| {
+      (sign_pow(c / maximum, 0.5) * 9.0 + 9.5)
+        .floor()
+        .clamp(0.0, 18.0) as u32
+    });
+    encode_base83(r * 19 * 19 + g * 19 + b, 2, &mut hash);
+  }
+  hash
+}
+
+/// Render `hash` into an opaque `width`x`height` image, like the reference decoder with a punch
+/// of 1.
+pub(crate) fn decode_blurhash(hash: &str, width: u32, height: u32) -> Result<RgbaImage> {
+  if !hash.is_ascii() || hash.len() < 6 {
+    return Err(Error::new(
+      Status::InvalidArg,
+      format!("blurhash: {hash:?} is too short"),
+    ));
+  }
+  let size_flag = decode_base83(&hash[..1])?;
+  let (nx, ny) = ((size_flag % 9 + 1) as usize, (size_flag / 9 + 1) as usize);
+  if hash.len() != 4 + 2 * nx * ny {
+    return Err(Error::new(
+      Status::InvalidArg,
+      format!(
+        "blurhash: a {nx}x{ny} hash is {} characters long, got {}",
+        4 + 2 * nx * ny,
+        hash.len()
+      ),
+    ));
+  }
+  let maximum = (decode_base83(&hash[1..2])? as f64 + 1.0) / 166.0;
+  let dc = decode_base83(&hash[2..6])?;
+  let mut colors = vec![[dc >> 16, (dc >> 8) & 255, dc & 255].map(|c| srgb_to_linear(c as u8))];
+  for k in 1..nx * ny {
+    let value = decode_base83(&hash[4 + k * 2..6 + k * 2])?;
+    let quantised = [value / (19 * 19), (value / 19) % 19, value % 19];
+    colors.push(quantised.map(|q| sign_pow((q as f64 - 9.0) / 9.0, 2.0) * maximum));
+  }
+  let cos_x: Vec<f64> = (0..nx)
+    .flat_map(|i| (0..width).map(move |x| (PI * x as f64 * i as f64 / width as f64).cos()))
+    .collect();
+  let cos_y: Vec<f64> = (0..ny)
+    .flat_map(|j| (0..height).map(move |y| (PI * y as f64 * j as f64 / height as f64).cos()))
+    .collect();
+  Ok(RgbaImage::from_fn(width, height, |x, y| {
+    let mut rgb = [0.0f64; 3];
+    for j in 0..ny {
+      for i in 0..nx {
+        let basis =
+          cos_x[i * width as usize + x as usize] * cos_y[j * height as usize + y as usize];
+        for c in 0..3 {
+          rgb[c] += colors[j * nx + i][c] * basis;
+        }
+      }
+    }
+    let [r, g, b] = rgb.map(linear_to_srgb);
+    Rgba([r, g, b, 255])
+  }))
+}
+
+/// The [ThumbHash](https://evanw.github.io/thumbhash/) of `image`, bit-compatible with the
+/// reference `rgbaToThumbHash` once the image fits in 100x100 (larger images are downscaled
+/// first, keeping the aspect ratio).
+pub(crate) fn encode_thumbhash(image: &DynamicImage) -> Vec<u8> {
+  let (width, height) = image.dimensions();
+  let rgba = if width.max(height) > THUMBHASH_MAX_SIDE {
+    image
+      .resize(THUMBHASH_MAX_SIDE, THUMBHASH_MAX_SIDE, FilterType::Triangle)
+      .to_rgba8()
+  } else {
+    image.to_rgba8()
+  };
+  let (w, h) = (rgba.width() as usize, rgba.height() as usize);
+  if w == 0 || h == 0 {
+    return Vec::new();
+  }
+
+  // Average color, weighted by alpha.
+  let (mut avg_r, mut avg_g, mut avg_b, mut avg_a) = (0.0, 0.0, 0.0, 0.0);
+  for pixel in rgba.pixels() {
+    let alpha = pixel[3] as f64 / 255.0;
+    avg_r += alpha / 255.0 * pixel[0] as f64;
+    avg_g += alpha / 255.0 * pixel[1] as f64;
+    avg_b += alpha / 255.0 * pixel[2] as f64;
+    avg_a += alpha;
+  }
+  if avg_a > 0.0 {
+    avg_r /= avg_a;
+    avg_g /= avg_a;
+    avg_b /= avg_a;
+  }
+
+  let has_alpha = avg_a < (w * h) as f64;
+  // Fewer luminance components when alpha needs room.
+  let l_limit = if has_alpha { 5.0 } else { 7.0 };
+  let longest = w.max(h) as f64;
+  let lx = ((l_limit * w as f64 / longest).round() as usize).max(1);
+  let ly = ((l_limit * h as f64 / longest).round() as usize).max(1);
+
+  // RGBA -> LPQA (luminance, yellow-blue, red-green, alpha), composited over the average color.
+  let mut l = Vec::with_capacity(w * h);
+  let mut p = Vec::with_capacity(w * h);
+  let mut q = Vec::with_capacity(w * h);
+  let mut a = Vec::with_capacity(w * h);
+  for pixel in rgba.pixels() {
+    let alpha = pixel[3] as f64 / 255.0;
+    let r = avg_r * (1.0 - alpha) + alpha / 255.0 * pixel[0] as f64;
+    let g = avg_g * (1.0 - alpha) + alpha / 255.0 * pixel[1] as f64;
+    let b = avg_b * (1.0 - alpha) + alpha / 255.0 * pixel[2] as f64;
+    l.push((r + g + b) / 3.0);
+    p.push((r + g) / 2.0 - b);
+    q.push(r - g);
+    a.push(alpha);
+  }
+
+  // DCT into a constant (DC) term and AC terms normalized to 0..=1 by their largest magnitude.
+  let encode_channel = |channel: &[f64], nx: usize, ny: usize| {
+    let (mut dc, mut ac, mut scale) = (0.0, Vec::new(), 0.0f64);
+    for cy in 0..ny {
+      let mut cx = 0;
+      while cx * ny < nx * (ny - cy) {
+        let fx: Vec<f64> = (0..w)
+          .map(|x| (PI / w as f64 * cx as f64 * (x as f64 + 0.5)).cos())
+          .collect();
+        let mut f = 0.0;
+        for y in 0..h {
+          let fy = (PI / h as f64 * cy as f64 * (y as f64 + 0.5)).cos();
+          for x in 0..w {
+            f += channel[x + y * w] * fx[x] * fy;
+          }
+        }
+        f /= (w * h) as f64;
+        if cx > 0 || cy > 0 {
+          ac.push(f);
+          scale = scale.max(f.abs());
+        } else {
+          dc = f;
+        }
+        cx += 1;
+      }
+    }
+    if scale > 0.0 {
+      for value in ac.iter_mut() {
+        *value = 0.5 + 0.5 / scale * *value;
+      }
+    }
+    (dc, ac, scale)
+  };
+  let (l_dc, l_ac, l_scale) = encode_channel(&l, lx.max(3), ly.max(3));
+  let (p_dc, p_ac, p_scale) = encode_channel(&p, 3, 3);
+  let (q_dc, q_ac, q_scale) = encode_channel(&q, 3, 3);
+  let alpha = has_alpha.then(|| encode_channel(&a, 5, 5));
+
+  let round = |v: f64| v.round() as u32;
+  let is_landscape = w > h;
+  let header24 = round(63.0 * l_dc)
+    | (round(31.5 + 31.5 * p_dc) << 6)
+    | (round(31.5 + 31.5 * q_dc) << 12)
+    | (round(31.0 * l_scale) << 18)
+    | ((has_alpha as u32) << 23);
+  let header16 = (if is_landscape { ly } else { lx }) as u32
+    | (round(63.0 * p_scale) << 3)
+    | (round(63.0 * q_scale) << 9)
+    | ((is_landscape as u32) << 15);
+  let mut hash = vec![
+    (header24 & 255) as u8,
+    ((header24 >> 8) & 255) as u8,
+    (header24 >> 16) as u8,
+    (header16 & 255) as u8,
+    (header16 >> 8) as u8,
+  ];
+  if let Some((a_dc, _, a_scale)) = &alpha {
+    hash.push((round(15.0 * a_dc) | (round(15.0 * a_scale) << 4)) as u8);
+  }
+  let ac_start = hash.len();
+  let mut acs = vec![&l_ac, &p_ac, &q_ac];
+  if let Some((_, a_ac, _)) = &alpha {
+    acs.push(a_ac);
+  }
+  for (index, &f) in acs.into_iter().flatten().enumerate() {
+    let byte = ac_start + (index >> 1);
+    if byte == hash.len() {
+      hash.push(0);
+    }
+    hash[byte] |= (round(15.0 * f) << ((index & 1) << 2)) as u8;
+  }
+  hash
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const HASH: &str = "LEHV6nWB2yk8pyo0adR*.7kCMdnj";
+
+  #[test]
+  fn blurhash_round_trips_through_the_decoder() {
+    let decoded = decode_blurhash(HASH, 32, 32).unwrap();
+    assert_eq!(decoded.dimensions(), (32, 32));
+    assert!(decoded.pixels().all(|p| p[3] == 255));
+    let encoded = encode_blurhash(&decoded, 4, 3);
+    assert_eq!(encoded.len(), HASH.len());
+    assert_eq!(encoded[..1], HASH[..1]);
+    // Sampling the basis on a pixel grid is lossy, but the average color survives.
+    let dc = |hash: &str| decode_base83(&hash[2..6]).unwrap();
+    let (expected, actual) = (dc(HASH), dc(&encoded));
+    for shift in [16, 8, 0] {
+      let channel = |v: u32| ((v >> shift) & 255) as i32;
+      assert!((channel(expected) - channel(actual)).abs() <= 2);
+    }
+  }
+
+  #[test]
+  fn blurhash_rejects_malformed_hashes_and_components() {
+    assert!(decode_blurhash("LEHV6n", 4, 4).is_err());
+    assert!(decode_blurhash("LEHV6nWB2yk8pyo0adR*.7kCMdn!", 4, 4).is_err());
+    let options = BlurhashOptions {
+      components_x: Some(10),
+      ..Default::default()
+    };
+    assert!(options.components().is_err());
+    assert_eq!(BlurhashOptions::default().components().unwrap(), (4, 3));
+  }
+
+  #[test]
+  fn solid_blurhash_has_only_a_dc_color() {
+    let solid = RgbaImage::from_pixel(7, 5, Rgba([255, 0, 0, 255]));
+    let hash = encode_blurhash(&solid, 1, 1);
+    // Size flag 0, no AC (quantised max 0), DC = 0xff0000.
+    assert_eq!(hash, "00TI:j");
+    let decoded = decode_blurhash(&hash, 2, 2).unwrap();
+    assert_eq!(*decoded.get_pixel(1, 1), Rgba([255, 0, 0, 255]));
+  }
+
+  #[test]
+  fn thumbhash_header_encodes_the_average_color() {
+    let red = DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, Rgba([255, 0, 0, 255])));
+    let hash = encode_thumbhash(&red);
+    // l = 1/3, p = 1/2, q = 1: 21 | 47 << 6 | 63 << 12, no alpha, square 7x7 luminance.
+    let header24 = hash[0] as u32 | (hash[1] as u32) << 8 | (hash[2] as u32) << 16;
+    assert_eq!(header24, 21 | (47 << 6) | (63 << 12));
+    assert_eq!(hash[3] & 7, 7);
+    // 27 luminance + 5 + 5 chroma AC nibbles.
+    assert_eq!(hash.len(), 5 + 37usize.div_ceil(2));
+
+    let translucent = DynamicImage::ImageRgba8(RgbaImage::from_fn(8, 8, |x, _| {
+      Rgba([0, 0, 255, if x < 4 { 255 } else { 0 }])
+    }));
+    let hash = encode_thumbhash(&translucent);
+    assert_eq!(hash[2] >> 7, 1, "has-alpha bit");
+    assert_eq!(hash.len(), 6 + (14 + 5 + 5 + 14usize).div_ceil(2));
+  }
+
+  #[test]
+  fn thumbhash_downscales_large_images() {
+    let wide = DynamicImage::ImageRgba8(RgbaImage::from_pixel(400, 100, Rgba([9, 9, 9, 255])));
+    let hash = encode_thumbhash(&wide);
+    assert_eq!(hash[4] >> 7, 1, "landscape bit");
+  }
+}
@@ -1980,6 +1980,51 @@ pub fn quantize_rgba(
   first
 }
 
+/// The visible colors of `px` reduced to at most `max_colors` median-cut clusters, each with the
+/// number of pixels whose color is perceptually nearest to it (by [`nearest_lab`]), most common
+/// first. Fully-transparent pixels are ignored, so a fully-transparent image returns nothing.
+/// Ties break on the packed color, so the order is deterministic. Only `stats()` uses it, hence the
+/// `binding` gate.
+#[cfg(feature = "binding")]
+pub fn dominant_colors(px: &[RGBA8], max_colors: usize) -> Vec<(RGBA8, u64)> {
+  let hist = build_histogram(px, 0);
+  let mut entries: Vec<ColorCount> = hist
+    .iter()
+    .filter(|(c, _)| c.a > 0)
+    .map(|(&color, &count)| ColorCount { color, count })
+    .collect();
+  if entries.is_empty() {
+    return Vec::new();
+  }
+  entries.sort_by_key(|e| packed(e.color));
+  // Same precision aid as `quantize_pass`: the split sees capped weights, the centroids the
+  // true populations.
+  let mut split_entries = entries.clone();
+  let cap: u128 = 1 << 26;
+  let true_total: u128 = entries.iter().map(|e| e.count as u128).sum();
+  cap_entry_weights(&mut split_entries, cap);
+  let true_counts: Option<HashMap<u32, u64>> =
+    (true_total > cap).then(|| entries.iter().map(|e| (packed(e.color), e.count)).collect());
+  let mut palette = median_cut(
+    &split_entries,
+    max_colors.clamp(1, MAX_PALETTE),
+    true_counts.as_ref(),
+  );
+  palette.sort_by_key(|c| packed(*c));
+  palette.dedup();
+
+  let labs = palette_labs(&palette);
+  let alphas: Vec<u8> = palette.iter().map(|p| p.a).collect();
+  let mut counts = vec![0u64; palette.len()];
+  for e in &entries {
+    let lab = rgb_to_lab(e.color.r, e.color.g, e.color.b);
+    counts[nearest_lab(&labs, &alphas, lab, e.color.a, true, 0)] += e.count;
+  }
+  let mut dominant: Vec<(RGBA8, u64)> = palette.into_iter().zip(counts).collect();
+  dominant.sort_by(|x, y| y.1.cmp(&x.1).then_with(|| packed(x.0).cmp(&packed(y.0))));
+  dominant
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -3060,6 +3105,29 @@ mod tests {
     }
   }
 
+  #[cfg(feature = "binding")]
+  #[test]
+  fn dominant_colors_rank_clusters_by_population() {
+    let red = RGBA8::new(200, 10, 10, 255);
+    let near_red = RGBA8::new(205, 12, 8, 255);
+    let blue = RGBA8::new(10, 10, 200, 255);
+    let hidden = RGBA8::new(0, 255, 0, 0);
+    let mut px = vec![red; 50];
+    px.extend(vec![near_red; 20]);
+    px.extend(vec![blue; 40]);
+    px.extend(vec![hidden; 500]);
+    let dominant = dominant_colors(&px, 2);
+    assert_eq!(dominant.len(), 2);
+    assert!(
+      dominant[0].0.r > 190 && dominant[0].0.b < 20,
+      "{:?}",
+      dominant[0].0
+    );
+    assert_eq!(dominant[0].1, 70);
+    assert_eq!(dominant[1], (blue, 40));
+    assert!(dominant_colors(&[hidden; 4], 4).is_empty());
+  }
+
   #[test]
   fn cap_entry_weights_bounds_total() {
     // Fix B unit test: the population-weight cap keeps the Wu split's i128 SSE
@@ -0,0 +1,351 @@
+use image::{DynamicImage, GenericImageView, GrayImage, RgbImage, imageops::FilterType};
+use napi_derive::napi;
+
+use crate::transformer::Gravity;
+
+/// Longest side of the downsampled copy the crop strategies score. Scoring a 256px proxy is
+/// plenty to locate the interesting region and keeps the cost independent of the input size.
+const ANALYSIS_SIZE: u32 = 256;
+
+#[napi]
+#[derive(Clone, Copy, PartialEq, Eq)]
+/// Content-aware anchor for `ResizeFit.Cover`. Takes precedence over `position` when set.
+pub enum CropStrategy {
+  /// Keep the region with the highest luminance entropy, trimming the
+  /// least busy edge strip until the crop fits (like sharp's `entropy`).
+  Entropy,
+  /// Keep the region with the most edges, skin tones and saturated
+  /// color (like sharp's `attention`).
+  Attention,
+}
+
+#[napi(object)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// The region of the (rotated) source image kept by a `ResizeFit.Cover` resize.
+pub struct CropBox {
+  pub left: u32,
+  pub top: u32,
+  pub width: u32,
+  pub height: u32,
+}
+
+/// The source-space window (`left`, `top`, `width`, `height`) a cover resize of a
+/// `src_width`x`src_height` image to `dst_width`x`dst_height` keeps. The window has the
+/// destination's aspect ratio and spans the whole source along the constrained axis;
+/// `strategy` (when set) or `position` decides where it sits along the other one.
+pub(crate) fn cover_window(
+  image: &DynamicImage,
+  dst_width: u32,
+  dst_height: u32,
+  position: Gravity,
+  strategy: Option<CropStrategy>,
+) -> (f64, f64, f64, f64) {
+  let (src_width, src_height) = (image.width() as f64, image.height() as f64);
+  let (dw, dh) = (dst_width.max(1) as f64, dst_height.max(1) as f64);
+  let (width, height) = if src_width * dh > src_height * dw {
+    ((src_height * dw / dh).min(src_width), src_height)
+  } else {
+    (src_width, (src_width * dh / dw).min(src_height))
+  };
+  let (fx, fy) = match strategy {
+    Some(strategy) => strategy_anchor(image, width, height, strategy),
+    None => gravity_anchor(position),
+  };
+  (
+    (src_width - width) * fx,
+    (src_height - height) * fy,
+    width,
+    height,
+  )
+}
+
+/// Round a fractional [`cover_window`] to whole pixels inside the source bounds.
+pub(crate) fn crop_box_from_window(
+  window: (f64, f64, f64, f64),
+  src_width: u32,
+  src_height: u32,
+) -> CropBox {
+  let (left, top, width, height) = window;
+  let width = (width.round() as u32).clamp(1, src_width.max(1));
+  let height = (height.round() as u32).clamp(1, src_height.max(1));
+  CropBox {
+    left: (left.round() as u32).min(src_width.saturating_sub(width)),
+    top: (top.round() as u32).min(src_height.saturating_sub(height)),
+    width,
+    height,
+  }
+}
+
+/// Where the crop window sits along each axis, as a fraction of the slack (`0.0` keeps the
+/// left/top edge, `1.0` the right/bottom edge).
+pub(crate) fn gravity_anchor(gravity: Gravity) -> (f64, f64) {
+  match gravity {
+    Gravity::Center => (0.5, 0.5),
+    Gravity::North => (0.5, 0.0),
+    Gravity::NorthEast => (1.0, 0.0),
+    Gravity::East => (1.0, 0.5),
+    Gravity::SouthEast => (1.0, 1.0),
+    Gravity::South => (0.5, 1.0),
+    Gravity::SouthWest => (0.0, 1.0),
+    Gravity::West => (0.0, 0.5),
+    Gravity::NorthWest => (0.0, 0.0),
+  }
+}
+
+fn strategy_anchor(
+  image: &DynamicImage,
+  width: f64,
+  height: f64,
+  strategy: CropStrategy,
+) -> (f64, f64) {
+  let (src_width, src_height) = image.dimensions();
+  if src_width == 0 || src_height == 0 {
+    return (0.5, 0.5);
+  }
+  let proxy = if src_width.max(src_height) > ANALYSIS_SIZE {
+    image.resize(ANALYSIS_SIZE, ANALYSIS_SIZE, FilterType::Triangle)
+  } else {
+    image.clone()
+  };
+  let rgb = proxy.to_rgb8();
+  let scale = rgb.width() as f64 / src_width as f64;
+  let window_width = ((width * scale).round() as u32).clamp(1, rgb.width());
+  let window_height = ((height * scale).round() as u32).clamp(1, rgb.height());
+  let (left, top) = match strategy {
+    CropStrategy::Entropy => entropy_window(&proxy.to_luma8(), window_width, window_height),
+    CropStrategy::Attention => attention_window(&rgb, window_width, window_height),
+  };
+  let slack_x = rgb.width() - window_width;
+  let slack_y = rgb.height() - window_height;
+  (
+    if slack_x == 0 {
+      0.5
+    } else {
+      left as f64 / slack_x as f64
+    },
+    if slack_y == 0 {
+      0.5
+    } else {
+      top as f64 / slack_y as f64
+    },
+  )
+}
+
+/// Shannon entropy (bits) of the luminance histogram of a rectangle.
+fn region_entropy(luma: &GrayImage, x0: u32, y0: u32, x1: u32, y1: u32) -> f64 {
+  let mut histogram = [0u32; 256];
+  for y in y0..y1 {
+    for x in x0..x1 {
+      histogram[luma.get_pixel(x, y).0[0] as usize] += 1;
+    }
+  }
+  let total = ((x1 - x0) * (y1 - y0)) as f64;
+  histogram
+    .iter()
+    .filter(|&&count| count > 0)
+    .map(|&count| {
+      let p = count as f64 / total;
+      -p * p.log2()
+    })
+    .sum()
+}
+
+/// Repeatedly drop whichever edge strip carries less entropy until the window fits. Ties
+/// trim the side that has been trimmed less so far, so featureless input stays centred.
+fn entropy_window(luma: &GrayImage, window_width: u32, window_height: u32) -> (u32, u32) {
+  let (mut x0, mut x1) = (0, luma.width());
+  let (mut y0, mut y1) = (0, luma.height());
+  while x1 - x0 > window_width {
+    let excess = x1 - x0 - window_width;
+    let step = excess.min((luma.width() / 32).max(1));
+    let head = region_entropy(luma, x0, y0, x0 + step, y1);
+    let tail = region_entropy(luma, x1 - step, y0, x1, y1);
+    if head < tail || (head == tail && x0 <= luma.width() - x1) {
+      x0 += step;
+    } else {
+      x1 -= step;
+    }
+  }
+  while y1 - y0 > window_height {
+    let excess = y1 - y0 - window_height;
+    let step = excess.min((luma.height() / 32).max(1));
+    let head = region_entropy(luma, x0, y0, x1, y0 + step);
+    let tail = region_entropy(luma, x0, y1 - step, x1, y1);
+    if head < tail || (head == tail && y0 <= luma.height() - y1) {
+      y0 += step;
+    } else {
+      y1 -= step;
+    }
+  }
+  (x0, y0)
+}
+
+/// Per-pixel saliency: local luminance gradient, plus bonuses for skin-like and saturated
+/// pixels (the cues sharp's `attention` strategy uses).
+fn attention_score(rgb: &RgbImage, x: u32, y: u32) -> f64 {
+  let luma = |x: u32, y: u32| {
+    let [r, g, b] = rgb.get_pixel(x, y).0;
+    0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64
+  };
+  let (w, h) = rgb.dimensions();
+  let dx = luma((x + 1).min(w - 1), y) - luma(x.saturating_sub(1), y);
+  let dy = luma(x, (y + 1).min(h - 1)) - luma(x, y.saturating_sub(1));
+  let edge = (dx.abs() + dy.abs()) / 510.0;
+
+  let [r, g, b] = rgb.get_pixel(x, y).0;
+  let (r, g, b) = (r as i32, g as i32, b as i32);
+  let max = r.max(g).max(b);
+  let min = r.min(g).min(b);
+  // Kovac et al. RGB skin rule (uniform daylight).
+  let skin = r > 95 && g > 40 && b > 20 && max - min > 15 && (r - g).abs() > 15 && r > g && r > b;
+  let luminance = luma(x, y) / 255.0;
+  let saturation = if max > 0 && (0.2..0.95).contains(&luminance) {
+    (max - min) as f64 / max as f64
+  } else {
+    0.0
+  };
+  edge + if skin { 1.0 } else { 0.0 } + 0.5 * saturation
+}
+
+/// Slide the window over a summed-area table of [`attention_score`] and keep the position with
+/// the highest total. Ties keep the position closest to the centre.
+fn attention_window(rgb: &RgbImage, window_width: u32, window_height: u32) -> (u32, u32) {
+  let (w, h) = rgb.dimensions();
+  let stride = w as usize + 1;
+  let mut table = vec![0f64; stride * (h as usize + 1)];
+  for y in 0..h {
+    let mut row = 0.0;
+    for x in 0..w {
+      row += attention_score(rgb, x, y);
+      let i = (y as usize + 1) * stride + x as usize + 1;
+      table[i] = table[i - stride] + row;
+    }
+  }
+  let sum = |x: u32, y: u32| {
+    let (x0, y0) = (x as usize, y as usize);
+    let (x1, y1) = (x0 + window_width as usize, y0 + window_height as usize);
+    table[y1 * stride + x1] - table[y0 * stride + x1] - table[y1 * stride + x0]
+      + table[y0 * stride + x0]
+  };
+  let (slack_x, slack_y) = (w - window_width, h - window_height);
+  let distance = |x: u32, y: u32| {
+    (2.0 * x as f64 - slack_x as f64).abs() + (2.0 * y as f64 - slack_y as f64).abs()
+  };
+  let mut best = (slack_x / 2, slack_y / 2);
+  let mut best_score = sum(best.0, best.1);
+  for y in 0..=slack_y {
+    for x in 0..=slack_x {
+      let score = sum(x, y);
+      if score > best_score + 1e-9
+        || ((score - best_score).abs() <= 1e-9 && distance(x, y) < distance(best.0, best.1))
+      {
+        best = (x, y);
+        best_score = score;
+      }
+    }
+  }
+  best
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{CropBox, CropStrategy, cover_window, crop_box_from_window, gravity_anchor};
+  use crate::transformer::Gravity;
+  use image::{DynamicImage, Rgb, RgbImage};
+
+  /// A flat gray 400x100 canvas with a noisy, colorful block in the right quarter.
+  fn detail_on_the_right() -> DynamicImage {
+    let mut img = RgbImage::from_pixel(400, 100, Rgb([128, 128, 128]));
+    let mut seed = 12345u32;
+    for y in 0..100 {
+      for x in 300..400 {
+        seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+        let v = (seed >> 16) as u8;
+        img.put_pixel(x, y, Rgb([v, 255 - v, v / 2]));
+      }
+    }
+    DynamicImage::ImageRgb8(img)
+  }
+
+  fn crop_box(img: &DynamicImage, w: u32, h: u32, g: Gravity, s: Option<CropStrategy>) -> CropBox {
+    crop_box_from_window(cover_window(img, w, h, g, s), img.width(), img.height())
+  }
+
+  #[test]
+  fn gravity_anchor_maps_compass_points() {
+    assert_eq!(gravity_anchor(Gravity::Center), (0.5, 0.5));
+    assert_eq!(gravity_anchor(Gravity::NorthWest), (0.0, 0.0));
+    assert_eq!(gravity_anchor(Gravity::SouthEast), (1.0, 1.0));
+    assert_eq!(gravity_anchor(Gravity::East), (1.0, 0.5));
+  }
+
+  #[test]
+  fn cover_window_defaults_to_the_centre() {
+    let img = detail_on_the_right();
+    assert_eq!(
+      crop_box(&img, 100, 100, Gravity::Center, None),
+      CropBox {
+        left: 150,
+        top: 0,
+        width: 100,
+        height: 100
+      }
+    );
+    assert_eq!(crop_box(&img, 100, 100, Gravity::West, None).left, 0);
+    assert_eq!(crop_box(&img, 100, 100, Gravity::East, None).left, 300);
+  }
+
+  #[test]
+  fn cover_window_spans_the_constrained_axis() {
+    // 400x100 into 200x50 has the same aspect ratio: nothing to crop.
+    let img = detail_on_the_right();
+    assert_eq!(
+      crop_box(
+        &img,
+        200,
+        50,
+        Gravity::Center,
+        Some(CropStrategy::Attention)
+      ),
+      CropBox {
+        left: 0,
+        top: 0,
+        width: 400,
+        height: 100
+      }
+    );
+  }
+
+  #[test]
+  fn entropy_strategy_keeps_the_busy_region() {
+    let img = detail_on_the_right();
+    let b = crop_box(&img, 100, 100, Gravity::Center, Some(CropStrategy::Entropy));
+    assert_eq!((b.width, b.height), (100, 100));
+    assert!(b.left >= 280, "entropy crop should move right, got {b:?}");
+  }
+
+  #[test]
+  fn attention_strategy_keeps_the_busy_region() {
+    let img = detail_on_the_right();
+    let b = crop_box(
+      &img,
+      100,
+      100,
+      Gravity::Center,
+      Some(CropStrategy::Attention),
+    );
+    assert!(b.left >= 280, "attention crop should move right, got {b:?}");
+  }
+
+  #[test]
+  fn strategies_stay_centred_on_featureless_input() {
+    let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(300, 100, Rgb([10, 10, 10])));
+    for strategy in [CropStrategy::Entropy, CropStrategy::Attention] {
+      let b = crop_box(&img, 100, 100, Gravity::NorthWest, Some(strategy));
+      assert!(
+        (b.left as i64 - 100).abs() <= 5,
+        "flat input should stay centred, got {b:?}"
+      );
+    }
+  }
+}
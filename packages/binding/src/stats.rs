@@ -0,0 +1,262 @@
+use image::{DynamicImage, GenericImageView, imageops::FilterType};
+use napi_derive::napi;
+use rgb::RGBA8;
+
+use crate::quantize::dominant_colors;
+
+/// Longest side of the thumbnail the dominant color is clustered from.
+const DOMINANT_SAMPLE_SIDE: u32 = 256;
+
+/// Median-cut clusters the dominant color is picked from.
+const DOMINANT_CLUSTERS: usize = 16;
+
+#[napi(object)]
+pub struct ChannelStats {
+  /// Smallest sample, in the image's native range (`0..=255`, `0..=65535` or float).
+  pub min: f64,
+  /// Largest sample, in the image's native range.
+  pub max: f64,
+  /// Mean sample, in the image's native range.
+  pub mean: f64,
+  /// Population standard deviation, in the image's native range.
+  pub stdev: f64,
+  /// 256 bins over the channel's full range, whatever the depth (float samples are clamped to
+  /// `0.0..=1.0`).
+  pub histogram: Vec<u32>,
+}
+
+#[napi(object)]
+pub struct DominantColor {
+  pub r: u32,
+  pub g: u32,
+  pub b: u32,
+}
+
+#[napi(object)]
+pub struct Stats {
+  /// One entry per channel, in the image's order (`L`, `LA`, `RGB` or `RGBA`). NaN samples are
+  /// skipped.
+  pub channels: Vec<ChannelStats>,
+  /// Whether every pixel is fully opaque. Always `true` for images without alpha.
+  pub is_opaque: bool,
+  /// Shannon entropy of the 8-bit luminance histogram in bits, from `0` (a single flat tone) to
+  /// `8`.
+  pub entropy: f64,
+  /// Variance of the Laplacian of the 8-bit luminance. Higher is sharper; blank and blurry
+  /// images score low (below ~100 is a common blur threshold for photos).
+  pub sharpness: f64,
+  /// The most common visible color, clustered perceptually (median cut, nearest in CIELAB) on a
+  /// thumbnail. Absent when the image is fully transparent.
+  pub dominant: Option<DominantColor>,
+}
+
+/// Compute [`Stats`] for `image` as it is (transforms already applied).
+pub(crate) fn image_stats(image: &DynamicImage) -> Stats {
+  let (channels, is_opaque) = match image {
+    DynamicImage::ImageLuma8(buf) => channel_stats(buf.as_raw(), 1, 255.0, |v| v as f64),
+    DynamicImage::ImageLumaA8(buf) => channel_stats(buf.as_raw(), 2, 255.0, |v| v as f64),
+    DynamicImage::ImageRgb8(buf) => channel_stats(buf.as_raw(), 3, 255.0, |v| v as f64),
+    DynamicImage::ImageRgba8(buf) => channel_stats(buf.as_raw(), 4, 255.0, |v| v as f64),
+    DynamicImage::ImageLuma16(buf) => channel_stats(buf.as_raw(), 1, 65535.0, |v| v as f64),
+    DynamicImage::ImageLumaA16(buf) => channel_stats(buf.as_raw(), 2, 65535.0, |v| v as f64),
+    DynamicImage::ImageRgb16(buf) => channel_stats(buf.as_raw(), 3, 65535.0, |v| v as f64),
+    DynamicImage::ImageRgba16(buf) => channel_stats(buf.as_raw(), 4, 65535.0, |v| v as f64),
+    DynamicImage::ImageRgb32F(buf) => channel_stats(buf.as_raw(), 3, 1.0, |v| v as f64),
+    DynamicImage::ImageRgba32F(buf) => channel_stats(buf.as_raw(), 4, 1.0, |v| v as f64),
+    other => channel_stats(other.to_rgba32f().as_raw(), 4, 1.0, |v| v as f64),
+  };
+  let luma = image.to_luma8();
+  Stats {
+    channels,
+    is_opaque,
+    entropy: entropy(luma.as_raw()),
+    sharpness: laplacian_variance(luma.as_raw(), luma.width() as usize),
+    dominant: dominant(image),
+  }
+}
+
+/// Whether every alpha sample of `image` is at its maximum, the rule behind [`Stats::is_opaque`].
+/// Images without an alpha channel are always opaque.
+pub(crate) fn is_opaque(image: &DynamicImage) -> bool {
+  match image {
+    _ if !image.color().has_alpha() => true,
+    DynamicImage::ImageLumaA8(buf) => buf.pixels().all(|pixel| pixel.0[1] == u8::MAX),
+    DynamicImage::ImageRgba8(buf) => buf.pixels().all(|pixel| pixel.0[3] == u8::MAX),
+    DynamicImage::ImageLumaA16(buf) => buf.pixels().all(|pixel| pixel.0[1] == u16::MAX),
+    DynamicImage::ImageRgba16(buf) => buf.pixels().all(|pixel| pixel.0[3] == u16::MAX),
+    other => other
+      .to_rgba32f()
+      .pixels()
+      .all(|pixel| pixel.0[3].is_nan() || pixel.0[3] >= 1.0),
+  }
+}
+
+/// Per-channel stats over interleaved `samples`, plus whether the last channel (when it is alpha)
+/// is at `max` everywhere.
+fn channel_stats<T: Copy>(
+  samples: &[T],
+  channels: usize,
+  max: f64,
+  value: impl Fn(T) -> f64,
+) -> (Vec<ChannelStats>, bool) {
+  let mut stats: Vec<ChannelStats> = (0..channels)
+    .map(|_| ChannelStats {
+      min: f64::INFINITY,
+      max: f64::NEG_INFINITY,
+      mean: 0.0,
+      stdev: 0.0,
+      histogram: vec![0; 256],
+    })
+    .collect();
+  let mut sums = vec![(0u64, 0.0f64, 0.0f64); channels];
+  for pixel in samples.chunks_exact(channels) {
+    for ((&sample, stat), (count, sum, sum_sq)) in pixel.iter().zip(&mut stats).zip(&mut sums) {
+      let v = value(sample);
+      if v.is_nan() {
+        continue;
+      }
+      stat.min = stat.min.min(v);
+      stat.max = stat.max.max(v);
+      *count += 1;
+      *sum += v;
+      *sum_sq += v * v;
+      let bin = ((v / max).clamp(0.0, 1.0) * 255.0).round() as usize;
+      stat.histogram[bin] = stat.histogram[bin].saturating_add(1);
+    }
+  }
+  for (stat, &(count, sum, sum_sq)) in stats.iter_mut().zip(&sums) {
+    if count == 0 {
+      (stat.min, stat.max) = (0.0, 0.0);
+      continue;
+    }
+    let n = count as f64;
+    stat.mean = sum / n;
+    stat.stdev = (sum_sq / n - stat.mean * stat.mean).max(0.0).sqrt();
+  }
+  let has_alpha = channels == 2 || channels == 4;
+  let is_opaque = !has_alpha || stats[channels - 1].min >= max;
+  (stats, is_opaque)
+}
+
+fn entropy(luma: &[u8]) -> f64 {
+  let mut histogram = [0u64; 256];
+  for &v in luma {
+    histogram[v as usize] += 1;
+  }
+  let total = luma.len() as f64;
+  histogram
+    .iter()
+    .filter(|&&count| count > 0)
+    .map(|&count| {
+      let p = count as f64 / total;
+      p * (1.0 / p).log2()
+    })
+    .sum()
+}
+
+/// Variance of the 4-neighbour Laplacian over the interior pixels; `0` below 3x3.
+fn laplacian_variance(luma: &[u8], width: usize) -> f64 {
+  let height = luma.len().checked_div(width).unwrap_or(0);
+  if width < 3 || height < 3 {
+    return 0.0;
+  }
+  let at = |x: usize, y: usize| luma[y * width + x] as f64;
+  let (mut sum, mut sum_sq) = (0.0, 0.0);
+  for y in 1..height - 1 {
+    for x in 1..width - 1 {
+      let laplacian = at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4.0 * at(x, y);
+      sum += laplacian;
+      sum_sq += laplacian * laplacian;
+    }
+  }
+  let n = ((width - 2) * (height - 2)) as f64;
+  let mean = sum / n;
+  (sum_sq / n - mean * mean).max(0.0)
+}
+
+fn dominant(image: &DynamicImage) -> Option<DominantColor> {
+  let (width, height) = image.dimensions();
+  let sample = if width.max(height) > DOMINANT_SAMPLE_SIDE {
+    image.resize(
+      DOMINANT_SAMPLE_SIDE,
+      DOMINANT_SAMPLE_SIDE,
+      FilterType::Triangle,
+    )
+  } else {
+    image.clone()
+  };
+  let px: Vec<RGBA8> = sample
+    .to_rgba8()
+    .pixels()
+    .map(|p| RGBA8::new(p[0], p[1], p[2], p[3]))
+    .collect();
+  dominant_colors(&px, DOMINANT_CLUSTERS)
+    .first()
+    .map(|(color, _)| DominantColor {
+      r: color.r as u32,
+      g: color.g as u32,
+      b: color.b as u32,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+  use image::{GrayImage, ImageBuffer, Luma, LumaA, Rgba, RgbaImage};
+
+  use super::*;
+
+  #[test]
+  fn channel_stats_are_in_native_units() {
+    let image = DynamicImage::ImageLumaA16(ImageBuffer::from_fn(2, 1, |x, _| {
+      LumaA([[1000u16, 3000][x as usize], 65535])
+    }));
+    let stats = image_stats(&image);
+    assert_eq!(stats.channels.len(), 2);
+    let luma = &stats.channels[0];
+    assert_eq!(
+      (luma.min, luma.max, luma.mean, luma.stdev),
+      (1000.0, 3000.0, 2000.0, 1000.0)
+    );
+    assert_eq!(luma.histogram.iter().sum::<u32>(), 2);
+    assert_eq!(luma.histogram[4], 1, "1000 / 65535 * 255 rounds to bin 4");
+    assert!(stats.is_opaque);
+    assert!(is_opaque(&image));
+    let opaque_rgba = RgbaImage::from_pixel(2, 2, Rgba([9, 9, 9, 255]));
+    assert!(is_opaque(&DynamicImage::ImageRgba8(opaque_rgba)));
+  }
+
+  #[test]
+  fn blank_images_have_no_entropy_or_sharpness() {
+    let blank = image_stats(&DynamicImage::ImageLuma8(GrayImage::from_pixel(
+      8,
+      8,
+      Luma([90]),
+    )));
+    assert_eq!((blank.entropy, blank.sharpness), (0.0, 0.0));
+    let checker = GrayImage::from_fn(8, 8, |x, y| Luma([if (x + y) % 2 == 0 { 0 } else { 255 }]));
+    let checker = image_stats(&DynamicImage::ImageLuma8(checker));
+    assert_eq!(checker.entropy, 1.0);
+    assert!(checker.sharpness > 1000.0);
+  }
+
+  #[test]
+  fn dominant_color_ignores_transparent_pixels() {
+    let image = RgbaImage::from_fn(10, 10, |x, _| match x {
+      0..=2 => Rgba([20, 40, 200, 255]),
+      3 => Rgba([250, 250, 0, 255]),
+      _ => Rgba([255, 0, 0, 0]),
+    });
+    let image = DynamicImage::ImageRgba8(image);
+    let stats = image_stats(&image);
+    assert!(!stats.is_opaque);
+    assert!(!is_opaque(&image));
+    let dominant = stats.dominant.expect("has visible pixels");
+    assert_eq!((dominant.r, dominant.g, dominant.b), (20, 40, 200));
+    let clear = RgbaImage::from_pixel(2, 2, Rgba([9, 9, 9, 0]));
+    assert!(
+      image_stats(&DynamicImage::ImageRgba8(clear))
+        .dominant
+        .is_none()
+    );
+  }
+}
@@ -0,0 +1,472 @@
+use std::fmt::Write;
+use std::sync::Arc;
+
+use image::RgbaImage;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use resvg::{
+  tiny_skia,
+  usvg::{
+    self, Options, Rect,
+    fontdb::{Database, Family, Query, Source},
+  },
+};
+
+use crate::transformer::{font_db, rasterize_svg};
+
+#[napi]
+#[derive(Clone, Copy, Default)]
+/// Horizontal alignment of the lines of a text layer.
+pub enum TextAlign {
+  #[default]
+  Left,
+  Center,
+  Right,
+}
+
+#[napi(object)]
+pub struct TextOptions {
+  /// The text to draw. `\n` starts a new line.
+  pub text: String,
+  /// CSS font family list, e.g. `"Helvetica, Arial, sans-serif"`. Defaults to the family of
+  /// `fontFile`, or `sans-serif`.
+  pub font_family: Option<String>,
+  /// A TTF/OTF/TTC font to draw with. The system fonts of the families `fontFamily` lists and of
+  /// the generic families stay available as fallbacks.
+  pub font_file: Option<Uint8Array>,
+  /// Font size in pixels. Defaults to 16.
+  pub font_size: Option<f64>,
+  /// Support CSS3 color, e.g. rgba(255, 255, 255, .8). Defaults to opaque black.
+  pub color: Option<String>,
+  /// Defaults to `Left`.
+  pub align: Option<TextAlign>,
+  /// Wrap lines at word boundaries so none is wider than `width` pixels. This is also the width
+  /// the lines are aligned in; without it the widest line sets the layer width.
+  pub width: Option<u32>,
+  /// Distance between baselines, as a multiple of `fontSize`. Defaults to 1.2.
+  pub line_height: Option<f64>,
+  /// Outline color, drawn behind the fill. Support CSS3 color.
+  pub stroke_color: Option<String>,
+  /// Outline width in pixels. Defaults to 1 when `strokeColor` is set.
+  pub stroke_width: Option<f64>,
+  /// Drop shadow color. Support CSS3 color.
+  pub shadow_color: Option<String>,
+  /// Shadow offset in pixels. Defaults to 2.
+  pub shadow_offset_x: Option<f64>,
+  /// Shadow offset in pixels. Defaults to 2.
+  pub shadow_offset_y: Option<f64>,
+  /// Gaussian blur (standard deviation) of the shadow in pixels. Defaults to 2.
+  pub shadow_blur: Option<f64>,
+}
+
+struct Shadow {
+  color: svgtypes::Color,
+  dx: f32,
+  dy: f32,
+  blur: f32,
+}
+
+/// `TextOptions` with every default resolved and every color parsed.
+struct TextStyle {
+  fontdb: Arc<Database>,
+  family: String,
+  size: f32,
+  color: svgtypes::Color,
+  align: TextAlign,
+  line_height: f32,
+  stroke: Option<(svgtypes::Color, f32)>,
+  shadow: Option<Shadow>,
+}
+
+fn parse_color(name: &str, color: &str) -> Result<svgtypes::Color> {
+  color.parse::<svgtypes::Color>().map_err(|err| {
+    Error::new(
+      Status::InvalidArg,
+      format!("text: invalid `{name}` {color:?}: {err}"),
+    )
+  })
+}
+
+/// A database of `font_file`'s faces plus the system faces the text may fall back to: those of the
+/// families `family` lists and of the generic families. Much cheaper than copying every system
+/// face into a database of its own on each call. Returns the font file's family name too.
+fn font_file_db(font_file: &[u8], family: Option<&str>) -> Result<(Database, String)> {
+  let mut fontdb = Database::new();
+  let ids = fontdb.load_font_source(Source::Binary(Arc::new(font_file.to_vec())));
+  let loaded = ids
+    .first()
+    .and_then(|id| fontdb.face(*id))
+    .and_then(|face| face.families.first())
+    .map(|(name, _)| name.clone())
+    .ok_or_else(|| {
+      Error::new(
+        Status::InvalidArg,
+        "text: `fontFile` is not a supported font".to_owned(),
+      )
+    })?;
+  let system = font_db();
+  let mut wanted: Vec<String> = family
+    .unwrap_or_default()
+    .split(',')
+    .map(|name| name.trim().trim_matches(['\'', '"']).to_lowercase())
+    .collect();
+  // Generic families resolve to the same faces as with the system database.
+  for generic in [
+    Family::Serif,
+    Family::SansSerif,
+    Family::Cursive,
+    Family::Fantasy,
+    Family::Monospace,
+  ] {
+    let name = system.family_name(&generic).to_owned();
+    wanted.push(name.to_lowercase());
+    match generic {
+      Family::Serif => fontdb.set_serif_family(name),
+      Family::SansSerif => fontdb.set_sans_serif_family(name),
+      Family::Cursive => fontdb.set_cursive_family(name),
+      Family::Fantasy => fontdb.set_fantasy_family(name),
+      _ => fontdb.set_monospace_family(name),
+    }
+  }
+  for face in system.faces() {
+    if face
+      .families
+      .iter()
+      .any(|(name, _)| wanted.contains(&name.to_lowercase()))
+    {
+      fontdb.push_face_info(face.clone());
+    }
+  }
+  Ok((fontdb, loaded))
+}
+
+impl TextStyle {
+  fn new(options: &TextOptions) -> Result<Self> {
+    let mut family = options.font_family.clone();
+    let fontdb = match &options.font_file {
+      Some(font_file) => {
+        let (fontdb, loaded) = font_file_db(font_file, family.as_deref())?;
+        if family.is_none() {
+          family = Some(format!("'{loaded}'"));
+        }
+        Arc::new(fontdb)
+      }
+      None => font_db(),
+    };
+    // usvg drops text whose families all miss, and fontdb maps `sans-serif` to a fixed name
+    // (Arial) a minimal system may lack. End the list with `sans-serif` and, failing that, any
+    // installed face so the text never silently disappears.
+    let mut family = match family {
+      Some(family) => format!("{family}, sans-serif"),
+      None => "sans-serif".to_owned(),
+    };
+    let sans_serif = Query {
+      families: &[Family::SansSerif],
+      ..Default::default()
+    };
+    let installed = || fontdb.faces().filter_map(|face| face.families.first());
+    if fontdb.query(&sans_serif).is_none()
+      && let Some((fallback, _)) = installed()
+        .find(|(name, _)| name.contains("Sans"))
+        .or_else(|| installed().next())
+    {
+      let _ = write!(family, ", '{fallback}'");
+    }
+    let size = options.font_size.unwrap_or(16.0) as f32;
+    if !(size.is_finite() && size > 0.0) {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("text: `fontSize` must be positive, got {size}"),
+      ));
+    }
+    let stroke = options
+      .stroke_color
+      .as_deref()
+      .map(|color| parse_color("strokeColor", color))
+      .transpose()?
+      .map(|color| (color, options.stroke_width.unwrap_or(1.0).max(0.0) as f32));
+    let shadow = options
+      .shadow_color
+      .as_deref()
+      .map(|color| parse_color("shadowColor", color))
+      .transpose()?
+      .map(|color| Shadow {
+        color,
+        dx: options.shadow_offset_x.unwrap_or(2.0) as f32,
+        dy: options.shadow_offset_y.unwrap_or(2.0) as f32,
+        blur: options.shadow_blur.unwrap_or(2.0).max(0.0) as f32,
+      });
+    Ok(TextStyle {
+      fontdb,
+      family,
+      size,
+      color: options
+        .color
+        .as_deref()
+        .map(|color| parse_color("color", color))
+        .transpose()?
+        .unwrap_or_else(svgtypes::Color::black),
+      align: options.align.unwrap_or_default(),
+      line_height: options.line_height.unwrap_or(1.2) as f32 * size,
+      stroke,
+      shadow,
+    })
+  }
+
+  fn parse(&self, svg: &str) -> Result<usvg::Tree> {
+    let options = Options::<'_> {
+      fontdb: self.fontdb.clone(),
+      ..Default::default()
+    };
+    usvg::Tree::from_str(svg, &options).map_err(|err| Error::from_reason(format!("{err}")))
+  }
+
+  /// SVG markup drawing `lines` aligned within `width`, with the first line box starting at y = 0.
+  /// Each line is a `<text id="lineN">`, so its bounds can be looked up after parsing.
+  fn markup(&self, lines: &[String], width: f32) -> String {
+    let (anchor, x) = match self.align {
+      TextAlign::Left => ("start", 0.0),
+      TextAlign::Center => ("middle", width / 2.0),
+      TextAlign::Right => ("end", width),
+    };
+    let mut svg = String::from(r#"<svg xmlns="http://www.w3.org/2000/svg" width="1" height="1">"#);
+    let mut group = format!(
+      r#"<g font-family="{}" font-size="{}" fill="{}" fill-opacity="{}" text-anchor="{anchor}""#,
+      escape(&self.family),
+      self.size,
+      rgb(self.color),
+      opacity(self.color),
+    );
+    if let Some((color, width)) = self.stroke {
+      let _ = write!(
+        group,
+        r#" stroke="{}" stroke-opacity="{}" stroke-width="{width}" stroke-linejoin="round" paint-order="stroke""#,
+        rgb(color),
+        opacity(color),
+      );
+    }
+    if let Some(shadow) = &self.shadow {
+      // A user-space filter region large enough that the shadow is never clipped; the layer
+      // bounds are computed separately (see `render_text`).
+      let margin = 4.0 * self.line_height * lines.len() as f32
+        + width
+        + shadow.dx.abs()
+        + shadow.dy.abs()
+        + 3.0 * shadow.blur;
+      let _ = write!(
+        svg,
+        r#"<defs><filter id="shadow" filterUnits="userSpaceOnUse" x="{}" y="{}" width="{}" height="{}"><feDropShadow dx="{}" dy="{}" stdDeviation="{}" flood-color="{}" flood-opacity="{}"/></filter></defs>"#,
+        -margin,
+        -margin,
+        2.0 * margin,
+        2.0 * margin,
+        shadow.dx,
+        shadow.dy,
+        shadow.blur,
+        rgb(shadow.color),
+        opacity(shadow.color),
+      );
+      group.push_str(r#" filter="url(#shadow)""#);
+    }
+    svg.push_str(&group);
+    svg.push('>');
+    for (index, line) in lines.iter().enumerate() {
+      // Center the em box in the line box; 0.8em approximates the ascent of most fonts.
+      let baseline =
+        index as f32 * self.line_height + (self.line_height - self.size) / 2.0 + 0.8 * self.size;
+      let _ = write!(
+        svg,
+        r#"<text id="line{index}" x="{x}" y="{baseline}" xml:space="preserve">{}</text>"#,
+        escape(line),
+      );
+    }
+    svg.push_str("</g></svg>");
+    svg
+  }
+
+  /// Ink bounds (including the stroke) of each line of a parsed `markup`; `None` for blank lines.
+  fn line_bounds(tree: &usvg::Tree, lines: usize) -> Vec<Option<Rect>> {
+    (0..lines)
+      .map(|index| {
+        tree
+          .node_by_id(&format!("line{index}"))
+          .map(|node| node.abs_stroke_bounding_box())
+      })
+      .collect()
+  }
+
+  fn measure(&self, line: &str) -> Result<f32> {
+    let tree = self.parse(&self.markup(&[line.to_owned()], 0.0))?;
+    Ok(
+      Self::line_bounds(&tree, 1)[0]
+        .map(|bounds| bounds.width())
+        .unwrap_or(0.0),
+    )
+  }
+
+  /// Greedy word wrap of one paragraph into `lines`. A single word wider than `width` gets a line
+  /// of its own rather than being broken.
+  fn wrap(&self, paragraph: &str, width: f32, lines: &mut Vec<String>) -> Result<()> {
+    let mut current = String::new();
+    for word in paragraph.split_whitespace() {
+      if current.is_empty() {
+        current.push_str(word);
+        continue;
+      }
+      let candidate = format!("{current} {word}");
+      if self.measure(&candidate)? <= width {
+        current = candidate;
+      } else {
+        lines.push(std::mem::replace(&mut current, word.to_owned()));
+      }
+    }
+    lines.push(current);
+    Ok(())
+  }
+}
+
+fn rgb(color: svgtypes::Color) -> String {
+  format!("rgb({},{},{})", color.red, color.green, color.blue)
+}
+
+fn opacity(color: svgtypes::Color) -> f32 {
+  color.alpha as f32 / 255.0
+}
+
+fn escape(text: &str) -> String {
+  let mut escaped = String::with_capacity(text.len());
+  for c in text.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+/// Check `options` without rendering, so `text()` can reject bad input synchronously.
+pub(crate) fn validate_text(options: &TextOptions) -> Result<()> {
+  TextStyle::new(options).map(|_| ())
+}
+
+/// Render `options` into a tightly sized straight-alpha RGBA8 layer. The layer spans the line
+/// boxes (`width` wide when wrapping) grown to fit any ink, stroke or shadow that overflows them.
+pub(crate) fn render_text(options: &TextOptions) -> Result<RgbaImage> {
+  let style = TextStyle::new(options)?;
+  let mut lines = Vec::new();
+  for paragraph in options.text.split('\n') {
+    match options.width {
+      Some(width) => style.wrap(paragraph, width as f32, &mut lines)?,
+      None => lines.push(paragraph.to_owned()),
+    }
+  }
+  let width = match options.width {
+    Some(width) => width as f32,
+    None => lines
+      .iter()
+      .map(|line| style.measure(line))
+      .try_fold(0.0_f32, |widest, width| {
+        width.map(|width| widest.max(width))
+      })?,
+  };
+  let tree = style.parse(&style.markup(&lines, width))?;
+  let (mut left, mut top) = (0.0_f32, 0.0_f32);
+  let (mut right, mut bottom) = (width, lines.len() as f32 * style.line_height);
+  for bounds in TextStyle::line_bounds(&tree, lines.len())
+    .into_iter()
+    .flatten()
+  {
+    let mut grow = |l: f32, t: f32, r: f32, b: f32| {
+      left = left.min(l);
+      top = top.min(t);
+      right = right.max(r);
+      bottom = bottom.max(b);
+    };
+    grow(bounds.left(), bounds.top(), bounds.right(), bounds.bottom());
+    if let Some(shadow) = &style.shadow {
+      let spread = 3.0 * shadow.blur;
+      grow(
+        bounds.left() + shadow.dx - spread,
+        bounds.top() + shadow.dy - spread,
+        bounds.right() + shadow.dx + spread,
+        bounds.bottom() + shadow.dy + spread,
+      );
+    }
+  }
+  let (left, top) = (left.floor(), top.floor());
+  let layer_width = ((right.ceil() - left) as u32).max(1);
+  let layer_height = ((bottom.ceil() - top) as u32).max(1);
+  rasterize_svg(
+    &tree,
+    layer_width,
+    layer_height,
+    tiny_skia::Transform::from_translate(-left, -top),
+    None,
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn options(text: &str) -> TextOptions {
+    TextOptions {
+      text: text.to_owned(),
+      font_family: None,
+      font_file: None,
+      font_size: Some(20.0),
+      color: None,
+      align: None,
+      width: None,
+      line_height: None,
+      stroke_color: None,
+      stroke_width: None,
+      shadow_color: None,
+      shadow_offset_x: None,
+      shadow_offset_y: None,
+      shadow_blur: None,
+    }
+  }
+
+  #[test]
+  fn escape_covers_xml_specials() {
+    assert_eq!(
+      escape(r#"<a & 'b' "c">"#),
+      "&lt;a &amp; &apos;b&apos; &quot;c&quot;&gt;"
+    );
+  }
+
+  #[test]
+  fn layer_height_follows_line_count() {
+    let one = render_text(&options("Hi")).unwrap();
+    let three = render_text(&options("Hi\n\nHi")).unwrap();
+    assert_eq!(one.height(), 24);
+    assert_eq!(three.height(), 72);
+  }
+
+  #[test]
+  fn wrapping_keeps_lines_within_width() {
+    // Every glyph of this font is a box 0.6em (12px here) wide, so no two words fit in 80px.
+    let text = TextOptions {
+      width: Some(80),
+      font_file: Some(include_bytes!("../__test__/boxes.ttf").to_vec().into()),
+      ..options("one two three four five")
+    };
+    let image = render_text(&text).unwrap();
+    assert_eq!(image.width(), 80);
+    assert_eq!(image.height(), 5 * 24, "one word per line");
+  }
+
+  #[test]
+  fn invalid_colors_are_rejected() {
+    let text = TextOptions {
+      color: Some("not a color".to_owned()),
+      ..options("Hi")
+    };
+    assert!(validate_text(&text).is_err());
+  }
+}
@@ -0,0 +1,480 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+
+use image::DynamicImage;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::encode_auto::{EncodeSpec, OutputFormat};
+use crate::fast_resize::{FastResizeOptions, ResizeFit, fast_resize};
+use crate::transformer::{Gravity, embed, parse_background};
+use crate::utils::json_string;
+
+/// Directory layout and manifest of a `tile()` pyramid.
+#[napi]
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum TileLayout {
+  /// `{name}.dzi` and `{name}_files/{level}/{column}_{row}.{ext}`, levels down to 1x1 (e.g.
+  /// OpenSeadragon).
+  #[default]
+  DeepZoom,
+  /// IIIF Image API 3.0 level 0: `{name}/info.json` and canonical tile URLs
+  /// `{name}/{region}/{size}/0/default.{ext}`.
+  Iiif,
+  /// `{name}/{z}/{x}/{y}.{ext}` with zoom 0 a single tile and edge tiles padded to full tiles
+  /// (e.g. Leaflet or OpenLayers), plus a `{name}/tiles.json` summary.
+  Xyz,
+  /// `{name}/ImageProperties.xml` and `{name}/TileGroup{n}/{z}-{x}-{y}.{ext}`.
+  Zoomify,
+}
+
+#[napi(object)]
+pub struct TileOptions {
+  /// Tile side in pixels. Defaults to `256`.
+  pub tile_size: Option<u32>,
+  /// Pixels each tile repeats from its neighbours. Only for `TileLayout.DeepZoom`; defaults to
+  /// `0`.
+  pub overlap: Option<u32>,
+  /// Defaults to `TileLayout.DeepZoom`.
+  pub layout: Option<TileLayout>,
+  /// Tile encoding. Defaults to `{ format: OutputFormat.Jpeg }`.
+  pub format: Option<EncodeSpec>,
+  /// First path segment of every tile and of the manifest. Defaults to `"image"`.
+  pub name: Option<String>,
+  /// The image service URI written as `id` into `info.json`. Only for `TileLayout.Iiif`; defaults
+  /// to `name`.
+  pub id: Option<String>,
+  /// Color `TileLayout.Xyz` edge tiles are padded with. Support CSS3 color; defaults to
+  /// transparent (black in JPEG).
+  pub background: Option<String>,
+  /// Write the tiles and the manifest under this directory (created as needed) instead of
+  /// returning the tile buffers.
+  pub directory: Option<String>,
+}
+
+#[napi(object)]
+pub struct TileSet {
+  /// Where the manifest belongs, relative to the tile set root, e.g. `image.dzi`.
+  pub manifest_path: String,
+  /// The `.dzi`, `info.json`, `tiles.json` or `ImageProperties.xml` contents.
+  pub manifest: String,
+  pub levels: u32,
+  /// Smallest level first, each row by row.
+  pub tiles: Vec<Tile>,
+}
+
+#[napi(object)]
+pub struct Tile {
+  /// Relative to the tile set root, e.g. `image_files/12/3_4.jpg`.
+  pub path: String,
+  /// Zoom level, `0` being the smallest.
+  pub level: u32,
+  pub column: u32,
+  pub row: u32,
+  pub width: u32,
+  pub height: u32,
+  /// The encoded tile, absent when it was written to `directory`.
+  pub buffer: Option<Buffer>,
+}
+
+pub struct TileSetOutput {
+  pub(crate) manifest_path: String,
+  pub(crate) manifest: String,
+  pub(crate) levels: u32,
+  pub(crate) tiles: Vec<TileOutput>,
+}
+
+pub struct TileOutput {
+  pub(crate) rect: TileRect,
+  pub(crate) bytes: Option<Vec<u8>>,
+}
+
+impl From<TileSetOutput> for TileSet {
+  fn from(output: TileSetOutput) -> Self {
+    TileSet {
+      manifest_path: output.manifest_path,
+      manifest: output.manifest,
+      levels: output.levels,
+      tiles: output
+        .tiles
+        .into_iter()
+        .map(|tile| Tile {
+          path: tile.rect.path,
+          level: tile.rect.level,
+          column: tile.rect.column,
+          row: tile.rect.row,
+          width: tile.rect.width,
+          height: tile.rect.height,
+          buffer: tile.bytes.map(Buffer::from),
+        })
+        .collect(),
+    }
+  }
+}
+
+/// One tile: the `width`x`height` region at (`x`, `y`) of level `level`'s image.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TileRect {
+  pub(crate) path: String,
+  pub(crate) level: u32,
+  pub(crate) column: u32,
+  pub(crate) row: u32,
+  pub(crate) x: u32,
+  pub(crate) y: u32,
+  pub(crate) width: u32,
+  pub(crate) height: u32,
+}
+
+/// The validated `TileOptions`, minus the encoder.
+pub(crate) struct TilePlan {
+  layout: TileLayout,
+  tile_size: u32,
+  overlap: u32,
+  format: OutputFormat,
+  name: String,
+  id: Option<String>,
+  background: svgtypes::Color,
+  pub(crate) directory: Option<PathBuf>,
+}
+
+impl TilePlan {
+  pub(crate) fn new(options: &TileOptions) -> Result<Self> {
+    let invalid = |message: String| Err(Error::new(Status::InvalidArg, message));
+    let layout = options.layout.unwrap_or_default();
+    let tile_size = options.tile_size.unwrap_or(256);
+    let overlap = options.overlap.unwrap_or(0);
+    if tile_size == 0 {
+      return invalid("tile: `tileSize` must be positive".to_owned());
+    }
+    if overlap > 0 && layout != TileLayout::DeepZoom {
+      return invalid("tile: `overlap` is only for `TileLayout.DeepZoom`".to_owned());
+    }
+    if overlap >= tile_size {
+      return invalid(format!(
+        "tile: `overlap` must be smaller than `tileSize`, got {overlap}"
+      ));
+    }
+    if options.id.is_some() && layout != TileLayout::Iiif {
+      return invalid("tile: `id` is only for `TileLayout.Iiif`".to_owned());
+    }
+    if options.background.is_some() && layout != TileLayout::Xyz {
+      return invalid("tile: `background` is only for `TileLayout.Xyz`".to_owned());
+    }
+    let name = options.name.clone().unwrap_or_else(|| "image".to_owned());
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+      return invalid(format!(
+        "tile: `name` must be a plain file name, got {name:?}"
+      ));
+    }
+    Ok(TilePlan {
+      layout,
+      tile_size,
+      overlap,
+      format: options
+        .format
+        .as_ref()
+        .map_or(OutputFormat::Jpeg, |spec| spec.format),
+      name,
+      id: options.id.clone(),
+      background: parse_background(Some(options.background.as_deref().unwrap_or("transparent")))?,
+      directory: options.directory.as_ref().map(PathBuf::from),
+    })
+  }
+
+  fn extension(&self) -> &'static str {
+    match self.format {
+      OutputFormat::Jpeg => "jpg",
+      OutputFormat::Png => "png",
+      OutputFormat::Webp => "webp",
+      OutputFormat::Avif => "avif",
+    }
+  }
+
+  /// Level sizes of a `width`x`height` image, full size first, each half the previous one
+  /// (rounded up): down to 1x1 for DeepZoom, else until the level fits in one tile. Expects a
+  /// non-empty image.
+  pub(crate) fn level_sizes(&self, width: u32, height: u32) -> Vec<(u32, u32)> {
+    let mut sizes = vec![(width, height)];
+    loop {
+      let (width, height) = sizes[sizes.len() - 1];
+      let done = match self.layout {
+        TileLayout::DeepZoom => width <= 1 && height <= 1,
+        _ => width <= self.tile_size && height <= self.tile_size,
+      };
+      if done {
+        return sizes;
+      }
+      sizes.push((width.div_ceil(2), height.div_ceil(2)));
+    }
+  }
+
+  /// Every tile of the pyramid with the given `level_sizes`, smallest level first.
+  pub(crate) fn tiles(&self, level_sizes: &[(u32, u32)]) -> Vec<TileRect> {
+    let (tile, overlap) = (self.tile_size, self.overlap);
+    let (full_width, full_height) = level_sizes[0];
+    let mut tiles = Vec::new();
+    for (level, &(width, height)) in level_sizes.iter().rev().enumerate() {
+      let level = level as u32;
+      // IIIF regions are in full-size coordinates.
+      let scale = 1u32 << (level_sizes.len() as u32 - 1 - level);
+      for row in 0..height.div_ceil(tile) {
+        for column in 0..width.div_ceil(tile) {
+          let x = (column * tile).saturating_sub(overlap);
+          let y = (row * tile).saturating_sub(overlap);
+          let right = ((column + 1) * tile + overlap).min(width);
+          let bottom = ((row + 1) * tile + overlap).min(height);
+          let (tile_width, tile_height) = (right - x, bottom - y);
+          let path = match self.layout {
+            TileLayout::DeepZoom => format!("{}_files/{level}/{column}_{row}", self.name),
+            TileLayout::Iiif => {
+              let region_x = x * scale;
+              let region_y = y * scale;
+              let region_width = (tile * scale).min(full_width - region_x);
+              let region_height = (tile * scale).min(full_height - region_y);
+              let region = if (region_width, region_height) == (full_width, full_height) {
+                "full".to_owned()
+              } else {
+                format!("{region_x},{region_y},{region_width},{region_height}")
+              };
+              let size = if scale == 1 {
+                "max".to_owned()
+              } else {
+                format!("{tile_width},{tile_height}")
+              };
+              format!("{}/{region}/{size}/0/default", self.name)
+            }
+            TileLayout::Xyz => format!("{}/{level}/{column}/{row}", self.name),
+            TileLayout::Zoomify => format!(
+              "{}/TileGroup{}/{level}-{column}-{row}",
+              self.name,
+              tiles.len() / 256
+            ),
+          };
+          tiles.push(TileRect {
+            path: format!("{path}.{}", self.extension()),
+            level,
+            column,
+            row,
+            x,
+            y,
+            width: tile_width,
+            height: tile_height,
+          });
+        }
+      }
+    }
+    tiles
+  }
+
+  /// The manifest's path (relative to the tile set root) and contents.
+  pub(crate) fn manifest(&self, level_sizes: &[(u32, u32)], tile_count: usize) -> (String, String) {
+    let (width, height) = level_sizes[0];
+    let name = &self.name;
+    let tile = self.tile_size;
+    match self.layout {
+      TileLayout::DeepZoom => (
+        format!("{name}.dzi"),
+        format!(
+          "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Image \
+           xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" Format=\"{}\" Overlap=\"{}\" \
+           TileSize=\"{tile}\">\n  <Size Width=\"{width}\" Height=\"{height}\"/>\n</Image>\n",
+          self.extension(),
+          self.overlap
+        ),
+      ),
+      TileLayout::Iiif => {
+        let scale_factors = (0..level_sizes.len())
+          .map(|level| (1u32 << level).to_string())
+          .collect::<Vec<_>>()
+          .join(", ");
+        let id = json_string(self.id.as_deref().unwrap_or(name));
+        (
+          format!("{name}/info.json"),
+          format!(
+            "{{\n  \"@context\": \"http://iiif.io/api/image/3/context.json\",\n  \"id\": {id},\n  \
+             \"type\": \"ImageService3\",\n  \"protocol\": \"http://iiif.io/api/image\",\n  \
+             \"profile\": \"level0\",\n  \"width\": {width},\n  \"height\": {height},\n  \
+             \"tiles\": [{{ \"width\": {tile}, \"scaleFactors\": [{scale_factors}] }}]\n}}\n"
+          ),
+        )
+      }
+      TileLayout::Xyz => (
+        format!("{name}/tiles.json"),
+        format!(
+          "{{\n  \"width\": {width},\n  \"height\": {height},\n  \"tileSize\": {tile},\n  \
+           \"minZoom\": 0,\n  \"maxZoom\": {}\n}}\n",
+          level_sizes.len() - 1
+        ),
+      ),
+      TileLayout::Zoomify => (
+        format!("{name}/ImageProperties.xml"),
+        format!(
+          "<IMAGE_PROPERTIES WIDTH=\"{width}\" HEIGHT=\"{height}\" NUMTILES=\"{tile_count}\" \
+           NUMIMAGES=\"1\" VERSION=\"1.8\" TILESIZE=\"{tile}\" />\n"
+        ),
+      ),
+    }
+  }
+
+  /// Resize `source` into `level_sizes`, full size first, each level from the previous one.
+  /// Returned smallest first, like [`Self::tiles`] numbers them; the full-size level borrows
+  /// `source` rather than copying it.
+  pub(crate) fn pyramid<'a>(
+    &self,
+    source: &'a DynamicImage,
+    level_sizes: &[(u32, u32)],
+  ) -> Result<Vec<Cow<'a, DynamicImage>>> {
+    let mut levels = vec![Cow::Borrowed(source)];
+    for &(width, height) in &level_sizes[1..] {
+      let mut level = levels[levels.len() - 1].as_ref().clone();
+      fast_resize(
+        &mut level,
+        &FastResizeOptions {
+          width,
+          height: Some(height),
+          filter: None,
+          fit: Some(ResizeFit::Fill),
+          position: None,
+          strategy: None,
+          background: None,
+          without_enlargement: None,
+          without_reduction: None,
+          linear_light: None,
+        },
+      )?;
+      levels.push(Cow::Owned(level));
+    }
+    levels.reverse();
+    Ok(levels)
+  }
+
+  /// The pixels of `rect`, cut from its level's image (and padded to a full tile for XYZ).
+  pub(crate) fn slice(&self, level: &DynamicImage, rect: &TileRect) -> DynamicImage {
+    let tile = level.crop_imm(rect.x, rect.y, rect.width, rect.height);
+    if self.layout == TileLayout::Xyz
+      && (rect.width, rect.height) != (self.tile_size, self.tile_size)
+    {
+      embed(
+        &tile,
+        self.tile_size,
+        self.tile_size,
+        Gravity::NorthWest,
+        self.background,
+      )
+    } else {
+      tile
+    }
+  }
+
+  /// Write `bytes` to `path` under `directory`, creating parent directories.
+  pub(crate) fn write(directory: &Path, path: &str, bytes: &[u8]) -> Result<()> {
+    let target = directory.join(path);
+    if let Some(parent) = target.parent() {
+      std::fs::create_dir_all(parent).map_err(|err| write_error(&target, err))?;
+    }
+    std::fs::write(&target, bytes).map_err(|err| write_error(&target, err))
+  }
+}
+
+fn write_error(path: &Path, err: std::io::Error) -> Error {
+  Error::new(
+    Status::GenericFailure,
+    format!("tile: cannot write {}: {err}", path.display()),
+  )
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn plan(layout: TileLayout, tile_size: u32, overlap: u32) -> TilePlan {
+    TilePlan::new(&TileOptions {
+      tile_size: Some(tile_size),
+      overlap: Some(overlap),
+      layout: Some(layout),
+      format: None,
+      name: None,
+      id: None,
+      background: None,
+      directory: None,
+    })
+    .unwrap()
+  }
+
+  #[test]
+  fn deep_zoom_levels_go_down_to_one_pixel_and_tiles_overlap() {
+    let plan = plan(TileLayout::DeepZoom, 256, 1);
+    let sizes = plan.level_sizes(600, 300);
+    assert_eq!(sizes.len(), 11);
+    assert_eq!(sizes[1], (300, 150));
+    assert_eq!(sizes[10], (1, 1));
+    // Degenerate sizes stop instead of halving 0 forever.
+    assert_eq!(plan.level_sizes(0, 0), [(0, 0)]);
+    assert_eq!(plan.level_sizes(0, 3).last(), Some(&(0, 1)));
+    let tiles = plan.tiles(&sizes);
+    let top = &tiles[tiles.len() - 6..];
+    assert_eq!(top[0].path, "image_files/10/0_0.jpg");
+    assert_eq!(
+      (top[0].x, top[0].width, top[1].x, top[1].width, top[2].width),
+      (0, 257, 255, 258, 89)
+    );
+    assert_eq!((top[3].y, top[3].height), (255, 45));
+    let (path, dzi) = plan.manifest(&sizes, tiles.len());
+    assert_eq!(path, "image.dzi");
+    assert!(dzi.contains("Overlap=\"1\" TileSize=\"256\""));
+    assert!(dzi.contains("<Size Width=\"600\" Height=\"300\"/>"));
+  }
+
+  #[test]
+  fn iiif_tiles_use_canonical_full_size_regions() {
+    let plan = plan(TileLayout::Iiif, 256, 0);
+    let sizes = plan.level_sizes(600, 300);
+    assert_eq!(sizes, [(600, 300), (300, 150), (150, 75)]);
+    let paths: Vec<_> = plan.tiles(&sizes).into_iter().map(|t| t.path).collect();
+    assert_eq!(paths[0], "image/full/150,75/0/default.jpg");
+    assert_eq!(paths[1], "image/0,0,512,300/256,150/0/default.jpg");
+    assert_eq!(paths[2], "image/512,0,88,300/44,150/0/default.jpg");
+    assert_eq!(paths[3], "image/0,0,256,256/max/0/default.jpg");
+    assert_eq!(paths[8], "image/512,256,88,44/max/0/default.jpg");
+    let (_, info) = plan.manifest(&sizes, paths.len());
+    assert!(info.contains("\"scaleFactors\": [1, 2, 4]"));
+    assert!(info.contains("\"id\": \"image\""));
+    let small = plan.level_sizes(200, 100);
+    assert_eq!(plan.tiles(&small)[0].path, "image/full/max/0/default.jpg");
+  }
+
+  #[test]
+  fn zoomify_groups_256_tiles_and_xyz_counts_zooms() {
+    let zoomify = plan(TileLayout::Zoomify, 16, 0);
+    let sizes = zoomify.level_sizes(400, 400);
+    let tiles = zoomify.tiles(&sizes);
+    assert_eq!(tiles[0].path, "image/TileGroup0/0-0-0.jpg");
+    assert_eq!(tiles[256].path, "image/TileGroup1/5-17-0.jpg");
+    let (_, properties) = zoomify.manifest(&sizes, tiles.len());
+    assert!(properties.contains(&format!("NUMTILES=\"{}\"", tiles.len())));
+    let xyz = plan(TileLayout::Xyz, 256, 0);
+    let sizes = xyz.level_sizes(1000, 300);
+    assert_eq!(sizes.len(), 3);
+    assert_eq!(xyz.tiles(&sizes)[0].path, "image/0/0/0.jpg");
+    assert!(xyz.manifest(&sizes, 0).1.contains("\"maxZoom\": 2"));
+  }
+
+  #[test]
+  fn rejects_options_for_other_layouts() {
+    let options = |layout, overlap, name: &str| TileOptions {
+      tile_size: None,
+      overlap: Some(overlap),
+      layout: Some(layout),
+      format: None,
+      name: Some(name.to_owned()),
+      id: None,
+      background: None,
+      directory: None,
+    };
+    assert!(TilePlan::new(&options(TileLayout::Xyz, 1, "image")).is_err());
+    assert!(TilePlan::new(&options(TileLayout::DeepZoom, 256, "image")).is_err());
+    assert!(TilePlan::new(&options(TileLayout::DeepZoom, 0, "../up")).is_err());
+    assert!(TilePlan::new(&options(TileLayout::DeepZoom, 1, "ok")).is_ok());
+    assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+  }
+}
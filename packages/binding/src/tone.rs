@@ -0,0 +1,729 @@
+use std::sync::Arc;
+
+use image::DynamicImage;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+/// A control point of a `curves()` curve, both coordinates in 8-bit units (`0..=255`) whatever
+/// the image's depth.
+#[napi(object)]
+#[derive(Clone, Copy)]
+pub struct CurvePoint {
+  pub x: f64,
+  pub y: f64,
+}
+
+/// Tone curves for `curves()`. Each needs at least two points with increasing `x`; between them
+/// the curve is a monotone cubic spline (no overshoot), outside them it is flat.
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct CurvesOptions {
+  /// Applied to every color channel (and to gray images' luma), before the per-channel curves.
+  pub all: Option<Vec<CurvePoint>>,
+  pub red: Option<Vec<CurvePoint>>,
+  pub green: Option<Vec<CurvePoint>>,
+  pub blue: Option<Vec<CurvePoint>>,
+}
+
+/// A monotone cubic (Fritsch-Carlson) interpolation of normalized control points.
+#[derive(Clone)]
+pub(crate) struct Spline {
+  xs: Vec<f32>,
+  ys: Vec<f32>,
+  tangents: Vec<f32>,
+}
+
+impl Spline {
+  fn new(name: &str, points: &[CurvePoint]) -> Result<Self> {
+    if points.len() < 2 {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!(
+          "curves: `{name}` needs at least 2 points, got {}",
+          points.len()
+        ),
+      ));
+    }
+    if let Some(point) = points
+      .iter()
+      .find(|p| !(0.0..=255.0).contains(&p.x) || !(0.0..=255.0).contains(&p.y))
+    {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!(
+          "curves: `{name}` points must lie in 0..=255, got ({}, {})",
+          point.x, point.y
+        ),
+      ));
+    }
+    if points.windows(2).any(|pair| pair[1].x <= pair[0].x) {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("curves: `{name}` points must have strictly increasing `x`"),
+      ));
+    }
+    let xs: Vec<f32> = points.iter().map(|p| (p.x / 255.0) as f32).collect();
+    let ys: Vec<f32> = points.iter().map(|p| (p.y / 255.0) as f32).collect();
+    let n = xs.len();
+    let slopes: Vec<f32> = (0..n - 1)
+      .map(|k| (ys[k + 1] - ys[k]) / (xs[k + 1] - xs[k]))
+      .collect();
+    let mut tangents: Vec<f32> = (0..n)
+      .map(|k| match k {
+        0 => slopes[0],
+        k if k == n - 1 => slopes[n - 2],
+        k if slopes[k - 1] * slopes[k] <= 0.0 => 0.0,
+        k => (slopes[k - 1] + slopes[k]) / 2.0,
+      })
+      .collect();
+    // Shrink tangents that would make a segment overshoot its end points.
+    for (k, &slope) in slopes.iter().enumerate() {
+      if slope == 0.0 {
+        tangents[k] = 0.0;
+        tangents[k + 1] = 0.0;
+        continue;
+      }
+      let (a, b) = (tangents[k] / slope, tangents[k + 1] / slope);
+      let s = a * a + b * b;
+      if s > 9.0 {
+        let tau = 3.0 / s.sqrt();
+        tangents[k] = tau * a * slope;
+        tangents[k + 1] = tau * b * slope;
+      }
+    }
+    Ok(Self { xs, ys, tangents })
+  }
+
+  fn eval(&self, x: f32) -> f32 {
+    let (xs, ys, m) = (&self.xs, &self.ys, &self.tangents);
+    let last = xs.len() - 1;
+    if x.is_nan() {
+      return x;
+    }
+    if x <= xs[0] {
+      return ys[0];
+    }
+    if x >= xs[last] {
+      return ys[last];
+    }
+    let i = xs.partition_point(|&px| px <= x) - 1;
+    let h = xs[i + 1] - xs[i];
+    let t = (x - xs[i]) / h;
+    let (t2, t3) = (t * t, t * t * t);
+    ys[i] * (2.0 * t3 - 3.0 * t2 + 1.0)
+      + h * m[i] * (t3 - 2.0 * t2 + t)
+      + ys[i + 1] * (3.0 * t2 - 2.0 * t3)
+      + h * m[i + 1] * (t3 - t2)
+  }
+}
+
+/// One staged tone or color adjustment. Levels and curve points are normalized to `0.0..=1.0`.
+#[derive(Clone)]
+pub(crate) enum ToneAdjustment {
+  Gamma(f32),
+  Levels {
+    in_black: f32,
+    in_white: f32,
+    gamma: f32,
+    out_black: f32,
+    out_white: f32,
+  },
+  /// `[all, red, green, blue]`.
+  Curves(Arc<[Option<Spline>; 4]>),
+  Saturation(f32),
+  Vibrance(f32),
+  Temperature {
+    temperature: f32,
+    tint: f32,
+  },
+  /// A color matrix: output `[r, g, b, a]` rows over `[r, g, b, a]` inputs. `alpha` is set for
+  /// 4x4 matrices, the only ones that may change alpha.
+  Recomb {
+    matrix: [[f32; 4]; 4],
+    alpha: bool,
+  },
+  /// The tint color's chroma, `color[c] - luma(color)`, added to every pixel's luma.
+  Tint([f32; 3]),
+}
+
+fn level(name: &str, value: f64) -> Result<f32> {
+  if !(0.0..=255.0).contains(&value) {
+    return Err(Error::new(
+      Status::InvalidArg,
+      format!("levels: `{name}` must be in 0..=255, got {value}"),
+    ));
+  }
+  Ok((value / 255.0) as f32)
+}
+
+fn positive(method: &str, name: &str, value: f64) -> Result<f32> {
+  if !value.is_finite() || value <= 0.0 {
+    return Err(Error::new(
+      Status::InvalidArg,
+      format!("{method}: `{name}` must be a positive number, got {value}"),
+    ));
+  }
+  Ok(value as f32)
+}
+
+fn ranged(method: &str, value: f64, min: f64, max: f64) -> Result<f32> {
+  if !(min..=max).contains(&value) {
+    return Err(Error::new(
+      Status::InvalidArg,
+      format!("{method}: value must be in {min}..={max}, got {value}"),
+    ));
+  }
+  Ok(value as f32)
+}
+
+impl ToneAdjustment {
+  pub(crate) fn gamma(gamma: f64) -> Result<Self> {
+    Ok(Self::Gamma(positive("gamma", "gamma", gamma)?))
+  }
+
+  pub(crate) fn levels(
+    in_black: f64,
+    in_white: f64,
+    gamma: Option<f64>,
+    out_black: Option<f64>,
+    out_white: Option<f64>,
+  ) -> Result<Self> {
+    let (in_black, in_white) = (level("inBlack", in_black)?, level("inWhite", in_white)?);
+    if in_white <= in_black {
+      return Err(Error::new(
+        Status::InvalidArg,
+        "levels: `inWhite` must be greater than `inBlack`".to_owned(),
+      ));
+    }
+    Ok(Self::Levels {
+      in_black,
+      in_white,
+      gamma: positive("levels", "gamma", gamma.unwrap_or(1.0))?,
+      out_black: level("outBlack", out_black.unwrap_or(0.0))?,
+      out_white: level("outWhite", out_white.unwrap_or(255.0))?,
+    })
+  }
+
+  pub(crate) fn curves(options: CurvesOptions) -> Result<Self> {
+    let spline = |name: &str, points: Option<Vec<CurvePoint>>| {
+      points.map(|points| Spline::new(name, &points)).transpose()
+    };
+    Ok(Self::Curves(Arc::new([
+      spline("all", options.all)?,
+      spline("red", options.red)?,
+      spline("green", options.green)?,
+      spline("blue", options.blue)?,
+    ])))
+  }
+
+  pub(crate) fn saturation(amount: f64) -> Result<Self> {
+    if !amount.is_finite() || amount < 0.0 {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("saturation: value must be a non-negative number, got {amount}"),
+      ));
+    }
+    Ok(Self::Saturation(amount as f32))
+  }
+
+  pub(crate) fn vibrance(amount: f64) -> Result<Self> {
+    Ok(Self::Vibrance(ranged("vibrance", amount, -1.0, 1.0)?))
+  }
+
+  pub(crate) fn temperature(temperature: f64, tint: Option<f64>) -> Result<Self> {
+    Ok(Self::Temperature {
+      temperature: ranged("temperature", temperature, -100.0, 100.0)? / 100.0,
+      tint: ranged("temperature", tint.unwrap_or(0.0), -100.0, 100.0)? / 100.0,
+    })
+  }
+
+  pub(crate) fn recomb(matrix: Vec<Vec<f64>>) -> Result<Self> {
+    let size = matrix.len();
+    if !(size == 3 || size == 4) || matrix.iter().any(|row| row.len() != size) {
+      return Err(Error::new(
+        Status::InvalidArg,
+        "recomb: matrix must be 3x3 or 4x4".to_owned(),
+      ));
+    }
+    if let Some(value) = matrix.iter().flatten().find(|v| !v.is_finite()) {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("recomb: matrix values must be finite, got {value}"),
+      ));
+    }
+    // A 3x3 matrix passes alpha through: identity row and column.
+    let mut rows = [
+      [1.0, 0.0, 0.0, 0.0],
+      [0.0, 1.0, 0.0, 0.0],
+      [0.0, 0.0, 1.0, 0.0],
+      [0.0, 0.0, 0.0, 1.0],
+    ];
+    for (row, values) in rows.iter_mut().zip(&matrix) {
+      for (cell, &value) in row.iter_mut().zip(values) {
+        *cell = value as f32;
+      }
+    }
+    Ok(Self::Recomb {
+      matrix: rows,
+      alpha: size == 4,
+    })
+  }
+
+  /// Color with `color`'s hue and saturation while keeping each pixel's luminance: every output
+  /// channel is the input's luma plus `color[c] - luma(color)`. Those offsets add no luma, and
+  /// near black and white they are scaled down until no channel clips, so luma never shifts.
+  pub(crate) fn tint(color: &str) -> Result<Self> {
+    let color = color
+      .parse::<svgtypes::Color>()
+      .map_err(|err| Error::new(Status::InvalidArg, format!("tint: invalid color: {err}")))?;
+    let rgb = [color.red, color.green, color.blue].map(|c| c as f32 / 255.0);
+    let luma = luma(rgb);
+    Ok(Self::Tint(rgb.map(|c| c - luma)))
+  }
+
+  /// The classic sepia matrix.
+  pub(crate) fn sepia() -> Self {
+    Self::Recomb {
+      matrix: [
+        [0.393, 0.769, 0.189, 0.0],
+        [0.349, 0.686, 0.168, 0.0],
+        [0.272, 0.534, 0.131, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+      ],
+      alpha: false,
+    }
+  }
+
+  /// Whether this adjustment promotes the image's color type (see [`promote_color_type`]), so
+  /// `metadata()` has to account for it.
+  pub(crate) fn changes_color_type(&self) -> bool {
+    matches!(self, Self::Recomb { .. } | Self::Tint(_))
+  }
+
+  /// Widen `image` to what this adjustment writes, keeping its depth: a color matrix or a tint
+  /// needs RGB (gray is spread over the three channels) and a 4x4 matrix also needs alpha.
+  pub(crate) fn promote_color_type(&self, image: &mut DynamicImage) {
+    let alpha = match self {
+      Self::Recomb { alpha, .. } => alpha,
+      Self::Tint(_) => &false,
+      _ => return,
+    };
+    let color = image.color();
+    if color.has_color() && (color.has_alpha() || !alpha) {
+      return;
+    }
+    let alpha = *alpha || color.has_alpha();
+    *image = match (color.bytes_per_pixel() / color.channel_count(), alpha) {
+      (1, false) => DynamicImage::ImageRgb8(image.to_rgb8()),
+      (1, true) => DynamicImage::ImageRgba8(image.to_rgba8()),
+      (2, false) => DynamicImage::ImageRgb16(image.to_rgb16()),
+      (2, true) => DynamicImage::ImageRgba16(image.to_rgba16()),
+      (_, false) => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+      (_, true) => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+    };
+  }
+
+  /// Map normalized sample `v` of color `channel` (`None` for gray luma) through a per-channel
+  /// adjustment.
+  fn transfer(&self, channel: Option<usize>, v: f32) -> f32 {
+    match self {
+      Self::Gamma(gamma) => {
+        if v > 0.0 {
+          v.powf(1.0 / gamma)
+        } else {
+          v
+        }
+      }
+      Self::Levels {
+        in_black,
+        in_white,
+        gamma,
+        out_black,
+        out_white,
+      } => {
+        let t = ((v - in_black) / (in_white - in_black)).clamp(0.0, 1.0);
+        out_black + t.powf(1.0 / gamma) * (out_white - out_black)
+      }
+      Self::Curves(splines) => {
+        let v = splines[0].as_ref().map_or(v, |all| all.eval(v));
+        match channel.and_then(|c| splines[c + 1].as_ref()) {
+          Some(spline) => spline.eval(v),
+          None => v,
+        }
+      }
+      _ => v,
+    }
+  }
+
+  /// Map a normalized RGB triple through a color adjustment.
+  fn recolor(&self, [r, g, b]: [f32; 3]) -> [f32; 3] {
+    let saturate = |amount: f32| {
+      let luma = luma([r, g, b]);
+      [r, g, b].map(|c| luma + (c - luma) * amount)
+    };
+    match self {
+      Self::Saturation(amount) => saturate(*amount),
+      Self::Vibrance(amount) => {
+        // Muted colors get the full boost, already saturated ones little to none.
+        let chroma = (r.max(g).max(b) - r.min(g).min(b)).clamp(0.0, 1.0);
+        saturate(1.0 + amount * (1.0 - chroma))
+      }
+      Self::Temperature { temperature, tint } => [
+        r * (1.0 + 0.2 * temperature),
+        g * (1.0 - 0.2 * tint),
+        b * (1.0 - 0.2 * temperature),
+      ],
+      Self::Tint(chroma) => {
+        let luma = luma([r, g, b]);
+        let fits = chroma.iter().fold(1.0f32, |fits, &offset| {
+          let room = if offset > 0.0 { 1.0 - luma } else { luma };
+          if offset != 0.0 {
+            fits.min(room / offset.abs())
+          } else {
+            fits
+          }
+        });
+        chroma.map(|offset| luma + offset * fits.max(0.0))
+      }
+      _ => [r, g, b],
+    }
+  }
+}
+
+/// Apply `adjustment` to `image` at its native depth, leaving alpha untouched like
+/// `apply_contrast`/`apply_huerotate`. Integer samples go through per-channel lookup tables and are
+/// rounded back to their depth; float samples are only clamped below (HDR magnitudes and NaN pass).
+/// Color adjustments (saturation, vibrance, temperature) leave gray images as they are, while a
+/// color matrix or a tint first promotes them to RGB.
+pub(crate) fn apply_tone(image: &mut DynamicImage, adjustment: &ToneAdjustment) {
+  match adjustment {
+    ToneAdjustment::Gamma(_) | ToneAdjustment::Levels { .. } | ToneAdjustment::Curves(_) => {
+      apply_transfer(image, |channel, v| adjustment.transfer(channel, v))
+    }
+    ToneAdjustment::Recomb { matrix, alpha } => {
+      adjustment.promote_color_type(image);
+      apply_matrix(image, matrix, *alpha);
+    }
+    ToneAdjustment::Tint(_) => {
+      adjustment.promote_color_type(image);
+      apply_recolor(image, |rgb| adjustment.recolor(rgb));
+    }
+    _ => apply_recolor(image, |rgb| adjustment.recolor(rgb)),
+  }
+}
+
+fn luma([r, g, b]: [f32; 3]) -> f32 {
+  0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn non_negative(v: f32) -> f32 {
+  if v < 0.0 { 0.0 } else { v }
+}
+
+fn apply_transfer(image: &mut DynamicImage, transfer: impl Fn(Option<usize>, f32) -> f32) {
+  let channels: Vec<Option<usize>> = if image.color().has_color() {
+    vec![Some(0), Some(1), Some(2)]
+  } else {
+    vec![None]
+  };
+  // Color samples come first in every layout, so `pixel.0[c]` for `c < channels.len()` never
+  // touches alpha.
+  macro_rules! transfer_int {
+    ($buf:expr, $ty:ty, $max:expr) => {{
+      let luts: Vec<Vec<$ty>> = channels
+        .iter()
+        .map(|&channel| {
+          (0..=$max as u32)
+            .map(|v| (transfer(channel, v as f32 / $max).clamp(0.0, 1.0) * $max).round() as $ty)
+            .collect()
+        })
+        .collect();
+      for pixel in $buf.pixels_mut() {
+        for (c, lut) in luts.iter().enumerate() {
+          pixel.0[c] = lut[pixel.0[c] as usize];
+        }
+      }
+    }};
+  }
+  macro_rules! transfer_float {
+    ($buf:expr) => {{
+      for pixel in $buf.pixels_mut() {
+        for (c, &channel) in channels.iter().enumerate() {
+          pixel.0[c] = non_negative(transfer(channel, pixel.0[c]));
+        }
+      }
+    }};
+  }
+  match image {
+    DynamicImage::ImageLuma8(buf) => transfer_int!(buf, u8, 255.0),
+    DynamicImage::ImageLumaA8(buf) => transfer_int!(buf, u8, 255.0),
+    DynamicImage::ImageRgb8(buf) => transfer_int!(buf, u8, 255.0),
+    DynamicImage::ImageRgba8(buf) => transfer_int!(buf, u8, 255.0),
+    DynamicImage::ImageLuma16(buf) => transfer_int!(buf, u16, 65535.0),
+    DynamicImage::ImageLumaA16(buf) => transfer_int!(buf, u16, 65535.0),
+    DynamicImage::ImageRgb16(buf) => transfer_int!(buf, u16, 65535.0),
+    DynamicImage::ImageRgba16(buf) => transfer_int!(buf, u16, 65535.0),
+    DynamicImage::ImageRgb32F(buf) => transfer_float!(buf),
+    DynamicImage::ImageRgba32F(buf) => transfer_float!(buf),
+    _ => {}
+  }
+}
+
+fn apply_recolor(image: &mut DynamicImage, recolor: impl Fn([f32; 3]) -> [f32; 3]) {
+  macro_rules! recolor_int {
+    ($buf:expr, $ty:ty, $max:expr) => {{
+      for pixel in $buf.pixels_mut() {
+        let rgb = [0, 1, 2].map(|c| pixel.0[c] as f32 / $max);
+        for (c, v) in recolor(rgb).into_iter().enumerate() {
+          pixel.0[c] = (v.clamp(0.0, 1.0) * $max).round() as $ty;
+        }
+      }
+    }};
+  }
+  macro_rules! recolor_float {
+    ($buf:expr) => {{
+      for pixel in $buf.pixels_mut() {
+        let rgb = [pixel.0[0], pixel.0[1], pixel.0[2]];
+        for (c, v) in recolor(rgb).into_iter().enumerate() {
+          pixel.0[c] = non_negative(v);
+        }
+      }
+    }};
+  }
+  match image {
+    DynamicImage::ImageRgb8(buf) => recolor_int!(buf, u8, 255.0),
+    DynamicImage::ImageRgba8(buf) => recolor_int!(buf, u8, 255.0),
+    DynamicImage::ImageRgb16(buf) => recolor_int!(buf, u16, 65535.0),
+    DynamicImage::ImageRgba16(buf) => recolor_int!(buf, u16, 65535.0),
+    DynamicImage::ImageRgb32F(buf) => recolor_float!(buf),
+    DynamicImage::ImageRgba32F(buf) => recolor_float!(buf),
+    // Gray has no color to adjust.
+    _ => {}
+  }
+}
+
+/// Multiply every pixel by `matrix`. Alpha reads as `1.0` on images without it and is only
+/// written back when `write_alpha`.
+fn apply_matrix(image: &mut DynamicImage, matrix: &[[f32; 4]; 4], write_alpha: bool) {
+  let mix = |input: [f32; 4]| {
+    matrix.map(|row| {
+      row
+        .iter()
+        .zip(input)
+        .map(|(weight, v)| weight * v)
+        .sum::<f32>()
+    })
+  };
+  macro_rules! matrix_int {
+    ($buf:expr, $ty:ty, $max:expr) => {{
+      for pixel in $buf.pixels_mut() {
+        let channels = pixel.0.len();
+        let input = [0, 1, 2, 3].map(|c| pixel.0.get(c).map_or(1.0, |&v| v as f32 / $max));
+        let output = mix(input);
+        let written = if write_alpha { channels } else { 3 };
+        for c in 0..written {
+          pixel.0[c] = (output[c].clamp(0.0, 1.0) * $max).round() as $ty;
+        }
+      }
+    }};
+  }
+  macro_rules! matrix_float {
+    ($buf:expr) => {{
+      for pixel in $buf.pixels_mut() {
+        let channels = pixel.0.len();
+        let input = [0, 1, 2, 3].map(|c| pixel.0.get(c).copied().unwrap_or(1.0));
+        let output = mix(input);
+        let written = if write_alpha { channels } else { 3 };
+        for c in 0..written {
+          pixel.0[c] = non_negative(output[c]);
+        }
+      }
+    }};
+  }
+  match image {
+    DynamicImage::ImageRgb8(buf) => matrix_int!(buf, u8, 255.0),
+    DynamicImage::ImageRgba8(buf) => matrix_int!(buf, u8, 255.0),
+    DynamicImage::ImageRgb16(buf) => matrix_int!(buf, u16, 65535.0),
+    DynamicImage::ImageRgba16(buf) => matrix_int!(buf, u16, 65535.0),
+    DynamicImage::ImageRgb32F(buf) => matrix_float!(buf),
+    DynamicImage::ImageRgba32F(buf) => matrix_float!(buf),
+    _ => {}
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use image::{ImageBuffer, LumaA, Rgba, Rgba32FImage, RgbaImage};
+
+  use super::*;
+
+  fn points(points: &[(f64, f64)]) -> Vec<CurvePoint> {
+    points.iter().map(|&(x, y)| CurvePoint { x, y }).collect()
+  }
+
+  #[test]
+  fn spline_hits_its_points_without_overshooting() {
+    let spline = Spline::new("all", &points(&[(0.0, 0.0), (64.0, 200.0), (255.0, 255.0)])).unwrap();
+    assert!((spline.eval(64.0 / 255.0) - 200.0 / 255.0).abs() < 1e-6);
+    let mut previous = 0.0;
+    for i in 0..=255 {
+      let y = spline.eval(i as f32 / 255.0);
+      assert!(
+        y >= previous - 1e-6 && y <= 1.0 + 1e-6,
+        "monotone at {i}: {y}"
+      );
+      previous = y;
+    }
+    assert!(Spline::new("all", &points(&[(10.0, 0.0), (10.0, 5.0)])).is_err());
+    assert!(Spline::new("all", &points(&[(0.0, 0.0)])).is_err());
+  }
+
+  #[test]
+  fn levels_and_gamma_keep_16bit_depth_and_alpha() {
+    let mut image = DynamicImage::ImageLumaA16(ImageBuffer::from_fn(3, 1, |x, _| {
+      LumaA([[0u16, 32768, 65535][x as usize], 1234])
+    }));
+    let levels = ToneAdjustment::levels(0.0, 128.0, None, Some(0.0), Some(255.0)).unwrap();
+    apply_tone(&mut image, &levels);
+    let buf = image.as_luma_alpha16().expect("stays LA16");
+    assert_eq!(*buf.get_pixel(1, 0), LumaA([65280, 1234]));
+    assert_eq!(*buf.get_pixel(2, 0), LumaA([65535, 1234]));
+
+    let mut image = DynamicImage::ImageLumaA16(ImageBuffer::from_pixel(1, 1, LumaA([16384, 7])));
+    apply_tone(&mut image, &ToneAdjustment::gamma(2.0).unwrap());
+    assert_eq!(
+      *image.as_luma_alpha16().unwrap().get_pixel(0, 0),
+      LumaA([32768, 7])
+    );
+    assert!(ToneAdjustment::gamma(0.0).is_err());
+    assert!(ToneAdjustment::levels(100.0, 50.0, None, None, None).is_err());
+  }
+
+  #[test]
+  fn channel_curves_only_touch_their_channel() {
+    let curves = ToneAdjustment::curves(CurvesOptions {
+      red: Some(points(&[(0.0, 255.0), (255.0, 0.0)])),
+      ..Default::default()
+    })
+    .unwrap();
+    let mut image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([55, 66, 77, 88])));
+    apply_tone(&mut image, &curves);
+    assert_eq!(
+      *image.as_rgba8().unwrap().get_pixel(0, 0),
+      Rgba([200, 66, 77, 88])
+    );
+  }
+
+  #[test]
+  fn saturation_vibrance_and_temperature_recolor() {
+    let color = |image: &DynamicImage| *image.as_rgba8().unwrap().get_pixel(0, 0);
+    let mut gray = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([200, 40, 40, 99])));
+    apply_tone(&mut gray, &ToneAdjustment::saturation(0.0).unwrap());
+    let Rgba([r, g, b, a]) = color(&gray);
+    assert!(r == g && g == b && a == 99);
+
+    // Vibrance boosts a muted color more than a vivid one.
+    let boost = |pixel: [u8; 4]| {
+      let mut image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba(pixel)));
+      apply_tone(&mut image, &ToneAdjustment::vibrance(1.0).unwrap());
+      let out = color(&image);
+      (out[0] as i32 - out[1] as i32) - (pixel[0] as i32 - pixel[1] as i32)
+    };
+    assert!(boost([140, 120, 120, 255]) > 0);
+    assert!(boost([140, 120, 120, 255]) > boost([250, 10, 10, 255]));
+
+    let mut warm =
+      DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([100, 100, 100, 255])));
+    apply_tone(&mut warm, &ToneAdjustment::temperature(50.0, None).unwrap());
+    assert_eq!(color(&warm), Rgba([110, 100, 90, 255]));
+    assert!(ToneAdjustment::temperature(101.0, None).is_err());
+  }
+
+  #[test]
+  fn recomb_promotes_gray_and_only_touches_alpha_when_4x4() {
+    let swap = ToneAdjustment::recomb(vec![
+      vec![0.0, 0.0, 1.0],
+      vec![0.0, 1.0, 0.0],
+      vec![1.0, 0.0, 0.0],
+    ])
+    .unwrap();
+    let mut image = DynamicImage::ImageRgba16(ImageBuffer::from_pixel(1, 1, Rgba([1, 2, 3, 4u16])));
+    apply_tone(&mut image, &swap);
+    assert_eq!(
+      *image.as_rgba16().expect("stays RGBA16").get_pixel(0, 0),
+      Rgba([3, 2, 1, 4])
+    );
+
+    let half_alpha = ToneAdjustment::recomb(vec![
+      vec![1.0, 0.0, 0.0, 0.0],
+      vec![0.0, 1.0, 0.0, 0.0],
+      vec![0.0, 0.0, 1.0, 0.0],
+      vec![0.0, 0.0, 0.0, 0.5],
+    ])
+    .unwrap();
+    let mut gray = DynamicImage::ImageLuma8(ImageBuffer::from_pixel(1, 1, image::Luma([90u8])));
+    apply_tone(&mut gray, &half_alpha);
+    assert_eq!(
+      *gray.as_rgba8().expect("promoted to RGBA8").get_pixel(0, 0),
+      Rgba([90, 90, 90, 128])
+    );
+
+    let mut gray = DynamicImage::ImageLuma16(ImageBuffer::from_pixel(1, 1, image::Luma([1000u16])));
+    apply_tone(&mut gray, &ToneAdjustment::sepia());
+    let sepia = gray.as_rgb16().expect("promoted to RGB16").get_pixel(0, 0);
+    assert!(sepia[0] > sepia[1] && sepia[1] > sepia[2]);
+
+    assert!(ToneAdjustment::recomb(vec![vec![1.0; 3]; 2]).is_err());
+    assert!(ToneAdjustment::recomb(vec![vec![1.0, 0.0, 0.0, 0.0]; 3]).is_err());
+  }
+
+  #[test]
+  fn tint_takes_the_colors_hue_and_keeps_luma_and_alpha() {
+    let luma8 = |[r, g, b, _]: [u8; 4]| luma([r, g, b].map(|c| c as f32 / 255.0));
+    let mut image =
+      DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([128, 128, 128, 50])));
+    apply_tone(&mut image, &ToneAdjustment::tint("rgb(0, 0, 255)").unwrap());
+    let Rgba([r, g, b, a]) = *image.as_rgba8().unwrap().get_pixel(0, 0);
+    assert!(b > g && g == r && a == 50, "blue; got {:?}", [r, g, b, a]);
+    assert!((luma8([r, g, b, a]) - 128.0 / 255.0).abs() < 0.01);
+    // A light gray tinted red stays light instead of clipping to pure red.
+    let mut image =
+      DynamicImage::ImageRgb8(image::RgbImage::from_pixel(1, 1, image::Rgb([200; 3])));
+    apply_tone(&mut image, &ToneAdjustment::tint("red").unwrap());
+    let image::Rgb([r, g, b]) = *image.as_rgb8().unwrap().get_pixel(0, 0);
+    assert!(
+      r == 255 && g == b && g > 150,
+      "light red; got {:?}",
+      [r, g, b]
+    );
+    // Gray images are promoted to RGB and tinted too.
+    let mut gray = DynamicImage::ImageLuma8(ImageBuffer::from_pixel(1, 1, image::Luma([100u8])));
+    apply_tone(&mut gray, &ToneAdjustment::tint("rgb(0, 128, 0)").unwrap());
+    let image::Rgb([r, g, b]) = *gray.as_rgb8().expect("promoted to RGB8").get_pixel(0, 0);
+    assert!(g > r && r == b);
+    assert!((luma8([r, g, b, 255]) - 100.0 / 255.0).abs() < 0.01);
+    // White has luma 1, so tinting with it just desaturates.
+    let mut image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([90, 90, 90, 255])));
+    apply_tone(&mut image, &ToneAdjustment::tint("white").unwrap());
+    assert_eq!(
+      *image.as_rgba8().unwrap().get_pixel(0, 0),
+      Rgba([90, 90, 90, 255])
+    );
+    assert!(ToneAdjustment::tint("not a color").is_err());
+  }
+
+  #[test]
+  fn float_images_keep_hdr_and_nan() {
+    let mut image = DynamicImage::ImageRgba32F(Rgba32FImage::from_pixel(
+      1,
+      1,
+      Rgba([4.0, f32::NAN, 0.25, 0.5]),
+    ));
+    apply_tone(&mut image, &ToneAdjustment::gamma(0.5).unwrap());
+    let pixel = image.as_rgba32f().unwrap().get_pixel(0, 0);
+    assert_eq!(pixel[0], 16.0);
+    assert!(pixel[1].is_nan());
+    assert_eq!(pixel[2], 0.0625);
+    assert_eq!(pixel[3], 0.5);
+  }
+}
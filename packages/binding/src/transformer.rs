@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::io::Cursor;
-use std::sync::Arc;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex};
 
 use image::imageops::overlay;
 use image::{
@@ -16,14 +17,40 @@ use resvg::{
 };
 
 use crate::{
+  atlas::{Atlas, AtlasOptions, AtlasOutput, AtlasPlan, canvas},
   avif::{AvifConfig, encode_avif_inner},
+  blur::{BlurPrecision, gaussian_blur},
+  compare::{CompareOptions, Comparison, ComparisonOutput, compare_images},
+  convolve::{Convolution, ConvolveOptions, ConvolvePreset, convolve},
+  draw::{PreparedShape, Shape, prepare_shapes, rasterize_mask_preset, rasterize_shapes},
+  effects::{AlphaEffect, BorderOptions, GlowOptions, ShadowOptions},
+  encode_auto::{
+    AutoCandidate, AutoEncode, AutoOutput, EncodeAutoOptions, EncodeAutoResult, EncodeSpec,
+    OutputFormat, pick_smallest,
+  },
+  encode_target::{
+    EncodeTargetOptions, EncodeTargetResult, Score, TargetFormat, TargetOutput, TargetSearch,
+  },
   fast_resize::{FastResizeOptions, ResizeFit, fast_resize},
   heic::HeicConfig,
+  perceptual_hash::{PerceptualHashOptions, perceptual_hash},
+  placeholder::{BlurhashOptions, decode_blurhash, encode_blurhash, encode_thumbhash},
   png::PngEncodeOptions,
+  smart_crop::{CropBox, CropStrategy, cover_window, crop_box_from_window, gravity_anchor},
+  stats::{Stats, image_stats, is_opaque},
+  text::{TextOptions, render_text, validate_text},
+  tile::{TileOptions, TileOutput, TilePlan, TileSet, TileSetOutput},
+  tone::{CurvesOptions, ToneAdjustment, apply_tone},
+  utils::{HelperThreads, fit_dimensions, from_linear_light, resize_is_blocked, to_linear_light},
+  variants::{Variant, VariantOutput, VariantPlan, VariantsOptions},
 };
 
 static FONT_DB: once_cell::sync::OnceCell<Arc<Database>> = once_cell::sync::OnceCell::new();
 
+// Upper bound on a rasterized SVG's (or a shadow/glow/border canvas's) area (~1 GiB of RGBA).
+// Bounds memory for adversarial or degenerate sizes; tune if you need larger rasters.
+const MAX_SVG_PIXELS: u64 = 1 << 28; // 268_435_456 px
+
 pub enum EncodeOptions {
   Png(PngEncodeOptions),
   Jpeg(u32),
@@ -429,9 +456,172 @@ impl ThreadsafeDynamicImage {
   }
 }
 
+impl ThreadsafeDynamicImage {
+  /// Shrink-on-load: when the full image has not been decoded yet, the input is a JPEG or WebP and
+  /// the first staged resize shrinks it, decode straight to a reduced size (libjpeg DCT scaling
+  /// by 1/2, 1/4 or 1/8, or libwebp's scaled decode) instead of decoding every pixel first.
+  ///
+  /// The reduced image is returned to the caller and never cached, so later metadata/encodes of
+  /// the same `Transformer` still see the full-size source. On success the first resize in `args`
+  /// is pinned to the dimensions it would have produced from the full-size image (a missing
+  /// `height`, `Inside` and `Outside` all depend on the source size). Returns the reduced image
+  /// and the EXIF orientation a staged `rotate()` must apply.
+  fn shrink_on_load(
+    &self,
+    args: &mut ImageTransformArgs,
+  ) -> Result<Option<(DynamicImage, Option<u16>)>> {
+    if unsafe { (*self.image).is_some() } {
+      return Ok(None);
+    }
+    let input = self.raw.as_ref();
+    let format = match image::guess_format(input) {
+      Ok(format @ (ImageFormat::Jpeg | ImageFormat::WebP)) => format,
+      _ => return Ok(None),
+    };
+    let Ok((width, height)) =
+      image::ImageReader::with_format(Cursor::new(input), format).into_dimensions()
+    else {
+      return Ok(None);
+    };
+    let base_orientation = if args.rotate {
+      parse_exif(input, &format).and_then(|(_, orientation)| orientation)
+    } else {
+      None
+    };
+    // `rotate()` runs before the resize; orientations 5-8 swap the axes it sees.
+    let swap = (args.rotate || args.orientation.is_some())
+      && matches!(
+        args.orientation.map(u16::from).or(base_orientation),
+        Some(5..=8)
+      );
+    let (oriented_width, oriented_height) = if swap {
+      (height, width)
+    } else {
+      (width, height)
+    };
+    let Some((need_width, need_height)) = shrink_target(args, oriented_width, oriented_height)
+    else {
+      return Ok(None);
+    };
+    let (need_width, need_height) = if swap {
+      (need_height, need_width)
+    } else {
+      (need_width, need_height)
+    };
+    let decoded = match format {
+      ImageFormat::Jpeg => [8, 4, 2]
+        .into_iter()
+        .find(|&denom| width.div_ceil(denom) >= need_width && height.div_ceil(denom) >= need_height)
+        .and_then(|denom| crate::jpeg::decode_jpeg_scaled(input, denom)),
+      _ => {
+        // libwebp scales with a simple box filter, so keep a 2x margin for the real resampler.
+        let scale = f64::max(
+          2.0 * need_width as f64 / width as f64,
+          2.0 * need_height as f64 / height as f64,
+        );
+        (scale < 1.0)
+          .then(|| {
+            crate::webp::decode_webp_scaled(
+              input,
+              ((width as f64 * scale).ceil() as u32).max(1),
+              ((height as f64 * scale).ceil() as u32).max(1),
+            )
+          })
+          .flatten()
+      }
+    };
+    Ok(decoded.map(|image| {
+      pin_resize_to_source(args, oriented_width, oriented_height);
+      (image, base_orientation)
+    }))
+  }
+}
+
 unsafe impl Send for ThreadsafeDynamicImage {}
 unsafe impl Sync for ThreadsafeDynamicImage {}
 
+/// The size the first staged resize resamples a `width`x`height` (post-rotation) source to, or
+/// `None` when shrink-on-load must not run: nothing is staged, `withoutReduction` is set, or the
+/// resize is skipped by `withoutEnlargement`.
+fn shrink_target(args: &ImageTransformArgs, width: u32, height: u32) -> Option<(u32, u32)> {
+  let (target_width, target_height, fit, without_enlargement, without_reduction) =
+    match (&args.resize, &args.fast_resize) {
+      (Some(resize), _) => (
+        resize.width,
+        resize.height,
+        resize.fit,
+        resize.without_enlargement,
+        resize.without_reduction,
+      ),
+      (None, Some(resize)) => (
+        resize.width,
+        resize.height,
+        resize.fit,
+        resize.without_enlargement,
+        resize.without_reduction,
+      ),
+      (None, None) => return None,
+    };
+  if without_reduction.unwrap_or(false) || width == 0 || height == 0 {
+    return None;
+  }
+  let target_height =
+    target_height.unwrap_or(((target_width as f32 / width as f32) * (height as f32)) as u32);
+  let resampled = fit_dimensions(
+    width,
+    height,
+    target_width,
+    target_height,
+    fit.unwrap_or_default(),
+  );
+  (!resize_is_blocked(
+    (width, height),
+    resampled,
+    without_enlargement.unwrap_or(false),
+    false,
+  ))
+  .then_some(resampled)
+}
+
+/// Rewrite the first staged resize so it produces the same dimensions from a shrunk-on-load
+/// image as it would from the full `width`x`height` (post-rotation) source.
+fn pin_resize_to_source(args: &mut ImageTransformArgs, width: u32, height: u32) {
+  fn pin(
+    target_width: &mut u32,
+    target_height: &mut Option<u32>,
+    fit: &mut Option<ResizeFit>,
+    width: u32,
+    height: u32,
+  ) {
+    let resolved_height =
+      target_height.unwrap_or(((*target_width as f32 / width as f32) * (height as f32)) as u32);
+    *target_height = Some(resolved_height);
+    if let Some(ResizeFit::Inside | ResizeFit::Outside) = fit {
+      let (w, h) = fit_dimensions(width, height, *target_width, resolved_height, fit.unwrap());
+      *target_width = w;
+      *target_height = Some(h);
+      *fit = Some(ResizeFit::Fill);
+    }
+  }
+  if let Some(resize) = &mut args.resize {
+    pin(
+      &mut resize.width,
+      &mut resize.height,
+      &mut resize.fit,
+      width,
+      height,
+    );
+  } else if let Some(resize) = &mut args.fast_resize {
+    pin(
+      &mut resize.width,
+      &mut resize.height,
+      &mut resize.fit,
+      width,
+      height,
+    );
+  }
+}
+
 #[napi]
 pub enum JsColorType {
   /// Pixel is 8-bit luminance
@@ -484,6 +674,9 @@ pub struct Metadata {
   pub orientation: Option<u32>,
   pub format: String,
   pub color_type: JsColorType,
+  /// The source region kept by a staged `ResizeFit.Cover` resize, in the coordinates of the
+  /// (rotated) input. Absent when no cover resize is staged.
+  pub crop_box: Option<CropBox>,
 }
 
 pub struct MetadataTask {
@@ -501,6 +694,7 @@ impl Task for MetadataTask {
     Option<u16>,
     DetectedFormat,
     ColorType,
+    Option<CropBox>,
   );
   type JsValue = Metadata;
 
@@ -510,20 +704,26 @@ impl Task for MetadataTask {
     let meta = self
       .dynamic_image
       .get(self.with_exif || self.image_transform_args.rotate)?;
-    let (width, height, color_type) = if self.image_transform_args.changes_dimensions_or_color() {
-      // Compute on a CLONE so the shared, cached `DynamicImage` is never mutated;
-      // a later encode of the same `Transformer` must still apply transforms once.
-      let mut image = meta.image.clone();
-      apply_transforms(
-        &mut image,
-        &self.image_transform_args,
-        meta.orientation,
-        false,
-      )?;
-      (image.width(), image.height(), image.color())
-    } else {
-      (meta.image.width(), meta.image.height(), meta.color_type)
-    };
+    let (width, height, color_type, crop_box) =
+      if self.image_transform_args.changes_dimensions_or_color() {
+        // Compute on a CLONE so the shared, cached `DynamicImage` is never mutated;
+        // a later encode of the same `Transformer` must still apply transforms once.
+        let mut image = meta.image.clone();
+        let crop_box = apply_transforms(
+          &mut image,
+          &self.image_transform_args,
+          meta.orientation,
+          false,
+        )?;
+        (image.width(), image.height(), image.color(), crop_box)
+      } else {
+        (
+          meta.image.width(),
+          meta.image.height(),
+          meta.color_type,
+          None,
+        )
+      };
     // Decide the RETURNED EXIF/orientation (#158). Two concerns: (1) a pending
     // rotate forced us to parse EXIF above for swapped dims, but a
     // `with_exif=false` caller never requested it, so never leak it; (2) when a
@@ -557,7 +757,15 @@ impl Task for MetadataTask {
       };
       (HashMap::new(), orientation)
     };
-    Ok((width, height, exif, orientation, meta.format, color_type))
+    Ok((
+      width,
+      height,
+      exif,
+      orientation,
+      meta.format,
+      color_type,
+      crop_box,
+    ))
   }
 
   fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
@@ -568,17 +776,325 @@ impl Task for MetadataTask {
       orientation: output.3.map(|o| o as u32),
       format: output.4.as_str(),
       color_type: output.5.into(),
+      crop_box: output.6,
+    })
+  }
+}
+
+pub struct StatsTask {
+  image: Arc<ThreadsafeDynamicImage>,
+  image_transform_args: ImageTransformArgs,
+}
+
+#[napi]
+impl Task for StatsTask {
+  type Output = Stats;
+  type JsValue = Stats;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    with_rendered_image(&self.image, &mut self.image_transform_args, image_stats)
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output)
+  }
+}
+
+/// Same borrow-or-render split as `EncodeTask`: `f` sees what an encode would write, and the
+/// cached decode is only cloned when something is staged.
+fn with_rendered_image<T>(
+  image: &ThreadsafeDynamicImage,
+  args: &mut ImageTransformArgs,
+  f: impl FnOnce(&DynamicImage) -> T,
+) -> Result<T> {
+  if args.is_noop() {
+    return Ok(f(&image.get(args.rotate)?.image));
+  }
+  let rendered = render_pipeline(image, args)?;
+  Ok(f(&rendered))
+}
+
+pub struct BlurhashTask {
+  image: Arc<ThreadsafeDynamicImage>,
+  image_transform_args: ImageTransformArgs,
+  components: (u32, u32),
+}
+
+#[napi]
+impl Task for BlurhashTask {
+  type Output = String;
+  type JsValue = String;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    let (x, y) = self.components;
+    with_rendered_image(&self.image, &mut self.image_transform_args, |image| {
+      encode_blurhash(&image.to_rgba8(), x, y)
+    })
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output)
+  }
+}
+
+pub struct PerceptualHashTask {
+  image: Arc<ThreadsafeDynamicImage>,
+  image_transform_args: ImageTransformArgs,
+  options: PerceptualHashOptions,
+}
+
+#[napi]
+impl Task for PerceptualHashTask {
+  type Output = String;
+  type JsValue = String;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    let (algorithm, size) = self.options.resolve()?;
+    with_rendered_image(&self.image, &mut self.image_transform_args, |image| {
+      perceptual_hash(image, algorithm, size)
+    })?
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output)
+  }
+}
+
+pub struct ThumbhashTask {
+  image: Arc<ThreadsafeDynamicImage>,
+  image_transform_args: ImageTransformArgs,
+}
+
+#[napi]
+impl Task for ThumbhashTask {
+  type Output = Vec<u8>;
+  type JsValue = Buffer;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    with_rendered_image(
+      &self.image,
+      &mut self.image_transform_args,
+      encode_thumbhash,
+    )
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output.into())
+  }
+}
+
+/// An image argument of a free function: encoded bytes, or a `Transformer` with its staged
+/// pipeline.
+struct RenderSource {
+  image: Arc<ThreadsafeDynamicImage>,
+  args: ImageTransformArgs,
+}
+
+impl RenderSource {
+  fn from_js(image: Either<Uint8Array, ClassInstance<'_, Transformer>>) -> Self {
+    match image {
+      Either::A(buffer) => RenderSource {
+        image: Arc::new(ThreadsafeDynamicImage::new(Arc::new(buffer))),
+        args: Default::default(),
+      },
+      Either::B(transformer) => RenderSource {
+        image: transformer.dynamic_image.clone(),
+        args: transformer.image_transform_args.clone(),
+      },
+    }
+  }
+}
+
+pub struct CompareTask {
+  a: RenderSource,
+  b: RenderSource,
+  options: CompareOptions,
+}
+
+#[napi]
+impl Task for CompareTask {
+  type Output = ComparisonOutput;
+  type JsValue = Comparison;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    let (a, b, options) = (&mut self.a, &mut self.b, &self.options);
+    with_rendered_image(&a.image, &mut a.args, |a_image| {
+      with_rendered_image(&b.image, &mut b.args, |b_image| {
+        compare_images(a_image, b_image, options)
+      })
+    })??
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output.into())
+  }
+}
+
+#[napi]
+/// Compare two images of the same size — encoded bytes, or `Transformer`s with their staged
+/// transforms applied — for PSNR, SSIM, MS-SSIM and CIE76 ΔE, e.g. to measure how lossy an
+/// encode is. The images may differ in color type and depth.
+pub fn compare(
+  a: Either<Uint8Array, ClassInstance<'_, Transformer>>,
+  b: Either<Uint8Array, ClassInstance<'_, Transformer>>,
+  options: Option<CompareOptions>,
+  signal: Option<AbortSignal>,
+) -> Result<AsyncTask<CompareTask>> {
+  let options = options.unwrap_or_default();
+  options.validate()?;
+  Ok(AsyncTask::with_optional_signal(
+    CompareTask {
+      a: RenderSource::from_js(a),
+      b: RenderSource::from_js(b),
+      options,
+    },
+    signal,
+  ))
+}
+
+#[napi]
+/// Synchronous version of `compare`.
+pub fn compare_sync(
+  env: Env,
+  a: Either<Uint8Array, ClassInstance<'_, Transformer>>,
+  b: Either<Uint8Array, ClassInstance<'_, Transformer>>,
+  options: Option<CompareOptions>,
+) -> Result<Comparison> {
+  let options = options.unwrap_or_default();
+  options.validate()?;
+  let mut task = CompareTask {
+    a: RenderSource::from_js(a),
+    b: RenderSource::from_js(b),
+    options,
+  };
+  let output = task.compute()?;
+  task.resolve(env, output)
+}
+
+pub struct PackAtlasTask {
+  sources: Vec<RenderSource>,
+  plan: AtlasPlan,
+  encode: EncodeOptions,
+}
+
+impl PackAtlasTask {
+  fn new(
+    images: Vec<Either<Uint8Array, ClassInstance<'_, Transformer>>>,
+    options: Option<AtlasOptions>,
+  ) -> Result<Self> {
+    let options = options.unwrap_or(AtlasOptions {
+      max_width: None,
+      padding: None,
+      power_of_two: None,
+      trim: None,
+      names: None,
+      image_name: None,
+      format: None,
+    });
+    let plan = AtlasPlan::new(&options, images.len())?;
+    let encode = match options.format {
+      Some(spec) => spec.into_options(0)?,
+      None => EncodeOptions::Png(Default::default()),
+    };
+    Ok(PackAtlasTask {
+      sources: images.into_iter().map(RenderSource::from_js).collect(),
+      plan,
+      encode,
+    })
+  }
+}
+
+#[napi]
+impl Task for PackAtlasTask {
+  type Output = AtlasOutput;
+  type JsValue = Atlas;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    let plan = &self.plan;
+    let sprites = self
+      .sources
+      .iter_mut()
+      .map(|source| {
+        with_rendered_image(&source.image, &mut source.args, |image| plan.sprite(image))
+      })
+      .collect::<Result<Vec<_>>>()?;
+    let (frames, width, height) = plan.pack(&sprites)?;
+    // Frames never overlap, so compositing onto the transparent canvas copies each one in.
+    let mut atlas = canvas(&sprites, width, height);
+    for (sprite, frame) in sprites.iter().zip(&frames) {
+      composite_step(
+        &mut atlas,
+        &sprite.image,
+        frame.x as i64,
+        frame.y as i64,
+        BlendMode::Over,
+        1.0,
+        false,
+      );
+    }
+    let json = plan.json(&frames, width, height);
+    Ok(AtlasOutput {
+      bytes: encode_image(&atlas, &self.encode)?.into_vec(),
+      width,
+      height,
+      frames,
+      json,
     })
   }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output.into())
+  }
+}
+
+#[napi]
+/// Bin-pack images (encoded bytes or `Transformer`s, with their staged pipelines) into one
+/// texture atlas, e.g. a sprite sheet, and return it with a frame map.
+pub fn pack_atlas(
+  images: Vec<Either<Uint8Array, ClassInstance<'_, Transformer>>>,
+  options: Option<AtlasOptions>,
+  signal: Option<AbortSignal>,
+) -> Result<AsyncTask<PackAtlasTask>> {
+  Ok(AsyncTask::with_optional_signal(
+    PackAtlasTask::new(images, options)?,
+    signal,
+  ))
+}
+
+#[napi]
+/// Synchronous version of `packAtlas`.
+pub fn pack_atlas_sync(
+  env: Env,
+  images: Vec<Either<Uint8Array, ClassInstance<'_, Transformer>>>,
+  options: Option<AtlasOptions>,
+) -> Result<Atlas> {
+  let mut task = PackAtlasTask::new(images, options)?;
+  let output = task.compute()?;
+  task.resolve(env, output)
 }
 
 #[napi(object)]
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct ResizeOptions {
   pub width: u32,
   pub height: Option<u32>,
   pub filter: Option<ResizeFilterType>,
   pub fit: Option<ResizeFit>,
+  /// Which part of the image `ResizeFit.Cover` keeps, or where `ResizeFit.Contain` places it.
+  /// Defaults to `Center`.
+  pub position: Option<Gravity>,
+  /// Content-aware crop anchor for `ResizeFit.Cover`; overrides `position`.
+  pub strategy: Option<CropStrategy>,
+  /// Letterbox color for `ResizeFit.Contain`. Support CSS3 color, e.g. rgba(255, 255, 255, .8).
+  /// Defaults to opaque black.
+  pub background: Option<String>,
+  /// Leave the image untouched if the resize would enlarge it on either axis.
+  pub without_enlargement: Option<bool>,
+  /// Leave the image untouched if the resize would shrink it on either axis.
+  pub without_reduction: Option<bool>,
+  /// Resample in linear light instead of on gamma-encoded sRGB values, which keeps fine detail
+  /// and high-contrast edges from darkening on downscale. Float images are already linear.
+  pub linear_light: Option<bool>,
 }
 
 #[napi(object)]
@@ -603,92 +1119,471 @@ pub struct CompositeOptions {
   pub opacity: Option<f64>,
 }
 
-/// A single staged composite/overlay operation. `overlay()` and `composite()` both push one of
-/// these; the legacy `overlay()` produces the source-over, no-tile, full-opacity shape that
-/// `compute()` routes through the unchanged fast 8-bit path.
-#[derive(Clone)]
-struct CompositeItem {
-  buffer: Arc<Uint8Array>,
-  left: i64,
-  top: i64,
-  has_offset: bool, // both left & top were given (or legacy overlay())
-  gravity: Gravity, // used only when !has_offset; defaults to Center
-  blend: BlendMode,
-  tile: bool,
-  opacity: f32,         // 1.0 == no-op
-  simple_overlay: bool, // legacy overlay(): byte-identical 8-bit `image::overlay`; composite() => false
+/// A non-encoded `composite()` source: raw pixels or an SVG document.
+#[napi(object)]
+pub struct OverlayInput {
+  /// Straight (non-premultiplied) RGBA8 pixels, `width * height * 4` bytes. Requires `width` and
+  /// `height`.
+  pub raw: Option<Uint8Array>,
+  /// An SVG document, rasterized at `width`x`height`. A missing dimension follows the SVG's
+  /// aspect ratio; with neither set the SVG is rasterized at the largest size that fits the image
+  /// it is composited onto.
+  pub svg: Option<String>,
+  pub width: Option<u32>,
+  pub height: Option<u32>,
 }
 
-#[derive(Default, Clone)]
-struct ImageTransformArgs {
-  grayscale: bool,
-  invert: bool,
-  rotate: bool,
-  resize: Option<ResizeOptions>,
-  fast_resize: Option<FastResizeOptions>,
-  contrast: Option<f32>,
-  blur: Option<f32>,
-  unsharpen: Option<(f32, i32)>,
-  filter3x3: Option<[f32; 9]>,
-  brightness: Option<i32>,
-  huerotate: Option<i32>,
-  orientation: Option<Orientation>,
-  crop: Option<(u32, u32, u32, u32)>,
-  overlay: Vec<CompositeItem>,
-  /// Multiply the alpha channel by this factor (0.0..=1.0). Promotes the image to RGBA8.
-  opacity: Option<f32>,
+/// A single channel of an image, for `extractChannel()`.
+#[napi]
+#[derive(Clone, Copy, Debug)]
+pub enum Channel {
+  Red,
+  Green,
+  Blue,
+  /// Fully opaque for an image without alpha.
+  Alpha,
+  /// Rec. 709 luma of the color channels.
+  Luminance,
 }
 
-impl ImageTransformArgs {
-  /// Whether any staged transform changes the encoded image's dimensions or
-  /// color type. Pure value-filters (invert/contrast/blur/unsharpen/filter3x3/
-  /// brighten/huerotate) and the in-place overlay do not, so `metadata()` can
-  /// skip cloning + applying them. `opacity` is included: it promotes the image
-  /// to RGBA8, so `metadata().colorType` must reflect it (like `grayscale`).
-  fn changes_dimensions_or_color(&self) -> bool {
-    self.rotate
-      || self.orientation.is_some()
-      || self.resize.is_some()
-      || self.fast_resize.is_some()
-      || self.grayscale
-      || self.crop.is_some()
-      || self.opacity.is_some()
-  }
+/// Which channel of a mask image becomes the alpha of the masked image.
+#[napi]
+#[derive(Clone, Copy)]
+pub enum MaskChannel {
+  /// The mask's alpha channel.
+  Alpha,
+  /// The mask's luminance, so an opaque black-and-white image works as a mask.
+  Luminance,
+}
 
-  /// No staged transform — the encode pipeline only reads the image, so it can borrow the
-  /// cached decode instead of cloning it.
-  fn is_noop(&self) -> bool {
-    !self.rotate
-      && self.orientation.is_none()
-      && self.resize.is_none()
-      && self.fast_resize.is_none()
-      && !self.grayscale
-      && !self.invert
-      && self.contrast.is_none()
-      && self.blur.is_none()
-      && self.unsharpen.is_none()
-      && self.filter3x3.is_none()
-      && self.brightness.is_none()
-      && self.huerotate.is_none()
-      && self.crop.is_none()
-      && self.overlay.is_empty()
-      && self.opacity.is_none()
+/// How a mask combines with the alpha the image already has.
+#[napi]
+#[derive(Clone, Copy, Default)]
+pub enum MaskMode {
+  /// `alpha * mask`: existing transparency is kept.
+  #[default]
+  Multiply,
+  /// `mask`: existing transparency is discarded.
+  Replace,
+}
+
+#[napi(object)]
+#[derive(Clone, Default)]
+pub struct MaskOptions {
+  /// Defaults to `Alpha` when the mask has an alpha channel and `Luminance` otherwise.
+  pub channel: Option<MaskChannel>,
+  /// Use `1 - mask`, hiding the image where the mask is opaque/bright.
+  pub invert: Option<bool>,
+  /// Stretch the mask to the size of the image (default `true`). When `false` the mask is placed
+  /// at the top-left corner at its own size, and the image outside it is hidden.
+  pub resize_to_fit: Option<bool>,
+  /// Defaults to `Multiply`.
+  pub mode: Option<MaskMode>,
+}
+
+/// Where a staged overlay's pixels come from. Every variant holds its decode/render cache behind
+/// an `Arc`, so the clones of `ImageTransformArgs` taken by each encode share it and an overlay is
+/// decoded once per `Transformer` rather than once per encode.
+#[derive(Clone)]
+enum OverlaySource {
+  /// Encoded image bytes; `ThreadsafeDynamicImage` caches the decode.
+  Encoded(Arc<ThreadsafeDynamicImage>),
+  /// Another `Transformer`'s image with the pipeline it had staged at `composite()` time.
+  Transformer {
+    image: Arc<ThreadsafeDynamicImage>,
+    args: Box<ImageTransformArgs>,
+    rendered: Arc<once_cell::sync::OnceCell<DynamicImage>>,
+  },
+  /// Raw RGBA8 pixels, validated when staged.
+  Pixels(Arc<DynamicImage>),
+  /// An SVG document. The raster size can depend on the base image, so the cache remembers the
+  /// size it was rendered at.
+  Svg {
+    tree: Arc<usvg::Tree>,
+    width: Option<u32>,
+    height: Option<u32>,
+    rendered: Arc<RasterCache>,
+  },
+  /// A `text()` layer, rendered on first use.
+  Text {
+    options: Arc<TextOptions>,
+    rendered: Arc<once_cell::sync::OnceCell<DynamicImage>>,
+  },
+  /// `draw()` shapes, rasterized at the size of the base image.
+  Shapes {
+    shapes: Arc<Vec<PreparedShape>>,
+    rendered: Arc<RasterCache>,
+  },
+}
+
+/// The last raster of a size-dependent overlay (SVG, shapes) and the size it was rendered at.
+type RasterCache = Mutex<Option<((u32, u32), Arc<DynamicImage>)>>;
+
+/// Reuse the raster in `cache` if it was rendered at `size`, otherwise `render` and keep it.
+fn cached_raster(
+  cache: &RasterCache,
+  size: (u32, u32),
+  render: impl FnOnce() -> Result<RgbaImage>,
+) -> Result<OverlayPixels<'static>> {
+  let mut cache = cache
+    .lock()
+    .map_err(|_| Error::from_reason("Overlay raster cache is poisoned"))?;
+  if let Some((cached_size, image)) = cache.as_ref()
+    && *cached_size == size
+  {
+    return Ok(OverlayPixels::Shared(image.clone()));
   }
+  let image = Arc::new(DynamicImage::ImageRgba8(render()?));
+  *cache = Some((size, image.clone()));
+  Ok(OverlayPixels::Shared(image))
 }
 
-/// Apply the staged pipeline to `image` in pipeline order:
-/// rotate/orientation -> resize -> fast_resize -> grayscale ->
-/// [value filters, encode-only] -> crop. The overlay step is NOT handled here
-/// (it needs the surrounding task's lifetime/cache); callers apply it after.
-///
+/// A resolved overlay: borrowed from a source's cache, or shared with a raster cache.
+enum OverlayPixels<'a> {
+  Borrowed(&'a DynamicImage),
+  Shared(Arc<DynamicImage>),
+}
+
+impl std::ops::Deref for OverlayPixels<'_> {
+  type Target = DynamicImage;
+
+  fn deref(&self) -> &DynamicImage {
+    match self {
+      OverlayPixels::Borrowed(image) => image,
+      OverlayPixels::Shared(image) => image,
+    }
+  }
+}
+
+impl OverlaySource {
+  fn encoded(buffer: Uint8Array) -> Self {
+    OverlaySource::Encoded(Arc::new(ThreadsafeDynamicImage::new(Arc::new(buffer))))
+  }
+
+  /// An encoded image, another `Transformer` with the pipeline it has staged now, or an
+  /// `OverlayInput`.
+  fn from_js(
+    image: Either3<Uint8Array, ClassInstance<'_, Transformer>, OverlayInput>,
+  ) -> Result<Self> {
+    match image {
+      Either3::A(buffer) => Ok(OverlaySource::encoded(buffer)),
+      Either3::B(transformer) => Ok(OverlaySource::Transformer {
+        image: transformer.dynamic_image.clone(),
+        args: Box::new(transformer.image_transform_args.clone()),
+        rendered: Default::default(),
+      }),
+      Either3::C(input) => OverlaySource::from_input(input),
+    }
+  }
+
+  fn from_input(input: OverlayInput) -> Result<Self> {
+    match (input.raw, input.svg) {
+      (Some(raw), None) => {
+        let (Some(width), Some(height)) = (input.width, input.height) else {
+          return Err(Error::new(
+            Status::InvalidArg,
+            "composite: raw pixels need both `width` and `height`".to_owned(),
+          ));
+        };
+        let image = RgbaImage::from_vec(width, height, raw.to_vec()).ok_or_else(|| {
+          Error::new(
+            Status::InvalidArg,
+            format!("composite: raw pixels are not {width}x{height} RGBA8"),
+          )
+        })?;
+        Ok(OverlaySource::Pixels(Arc::new(DynamicImage::ImageRgba8(
+          image,
+        ))))
+      }
+      (None, Some(svg)) => {
+        let tree = load_svg(Either::A(svg.as_str()))?;
+        if input.width.is_some() || input.height.is_some() {
+          let (width, height) = svg_overlay_size(tree.size(), input.width, input.height, (0, 0));
+          if (width as u64) * (height as u64) > MAX_SVG_PIXELS {
+            return Err(Error::new(
+              Status::InvalidArg,
+              format!("composite: SVG raster size {width}x{height} is out of range"),
+            ));
+          }
+        }
+        Ok(OverlaySource::Svg {
+          tree: Arc::new(tree),
+          width: input.width,
+          height: input.height,
+          rendered: Default::default(),
+        })
+      }
+      _ => Err(Error::new(
+        Status::InvalidArg,
+        "composite: provide exactly one of `raw` or `svg`".to_owned(),
+      )),
+    }
+  }
+
+  /// Decode/render the overlay for a `base_width`x`base_height` base, reusing the cache.
+  fn pixels(&self, base_width: u32, base_height: u32) -> Result<OverlayPixels<'_>> {
+    match self {
+      OverlaySource::Encoded(image) => Ok(OverlayPixels::Borrowed(&image.get(true)?.image)),
+      OverlaySource::Pixels(image) => Ok(OverlayPixels::Borrowed(image)),
+      OverlaySource::Transformer { image, args, .. } if args.is_noop() => {
+        Ok(OverlayPixels::Borrowed(&image.get(false)?.image))
+      }
+      OverlaySource::Transformer {
+        image,
+        args,
+        rendered,
+      } => rendered
+        .get_or_try_init(|| render_pipeline(image, &mut (**args).clone()))
+        .map(OverlayPixels::Borrowed),
+      OverlaySource::Text { options, rendered } => rendered
+        .get_or_try_init(|| render_text(options).map(DynamicImage::ImageRgba8))
+        .map(OverlayPixels::Borrowed),
+      OverlaySource::Svg {
+        tree,
+        width,
+        height,
+        rendered,
+      } => {
+        let size = svg_overlay_size(tree.size(), *width, *height, (base_width, base_height));
+        cached_raster(rendered, size, || {
+          let scale_x = size.0 as f32 / tree.size().width();
+          let scale_y = size.1 as f32 / tree.size().height();
+          rasterize_svg(
+            tree,
+            size.0,
+            size.1,
+            tiny_skia::Transform::from_scale(scale_x, scale_y),
+            None,
+          )
+        })
+      }
+      OverlaySource::Shapes { shapes, rendered } => {
+        cached_raster(rendered, (base_width, base_height), || {
+          rasterize_shapes(shapes, base_width, base_height)
+        })
+      }
+    }
+  }
+}
+
+/// The raster size of an SVG overlay: `width`/`height` when given (a missing one follows the
+/// SVG's aspect ratio), otherwise the largest size that fits inside the `base` image.
+fn svg_overlay_size(
+  size: usvg::Size,
+  width: Option<u32>,
+  height: Option<u32>,
+  base: (u32, u32),
+) -> (u32, u32) {
+  let (svg_width, svg_height) = (size.width() as f64, size.height() as f64);
+  let (width, height) = match (width, height) {
+    (Some(width), Some(height)) => (width as f64, height as f64),
+    (Some(width), None) => (width as f64, width as f64 * svg_height / svg_width),
+    (None, Some(height)) => (height as f64 * svg_width / svg_height, height as f64),
+    (None, None) => {
+      let scale = f64::min(base.0 as f64 / svg_width, base.1 as f64 / svg_height);
+      (svg_width * scale, svg_height * scale)
+    }
+  };
+  (
+    (width.round().min(u32::MAX as f64) as u32).max(1),
+    (height.round().min(u32::MAX as f64) as u32).max(1),
+  )
+}
+
+/// The system font database, loaded on first use and shared by every SVG/text render.
+pub(crate) fn font_db() -> Arc<Database> {
+  FONT_DB
+    .get_or_init(|| {
+      let mut fontdb = Database::new();
+      fontdb.load_system_fonts();
+      Arc::new(fontdb)
+    })
+    .clone()
+}
+
+/// Parse an SVG document, with the system fonts loaded for `<text>`.
+fn load_svg(input: Either<&str, &[u8]>) -> Result<usvg::Tree> {
+  let options = Options::<'_> {
+    fontdb: font_db(),
+    ..Default::default()
+  };
+  match input {
+    Either::A(a) => usvg::Tree::from_str(a, &options),
+    Either::B(b) => usvg::Tree::from_data(b, &options),
+  }
+  .map_err(|err| Error::from_reason(format!("{err}")))
+}
+
+/// Render `tree` through `transform` into a `target_width`x`target_height` straight-alpha RGBA8
+/// image, over `background` when given.
+pub(crate) fn rasterize_svg(
+  tree: &usvg::Tree,
+  target_width: u32,
+  target_height: u32,
+  transform: tiny_skia::Transform,
+  background: Option<svgtypes::Color>,
+) -> Result<RgbaImage> {
+  let mut pix_map = tiny_skia::Pixmap::new(target_width, target_height).ok_or_else(|| {
+    Error::from_reason(format!(
+      "Failed to rasterize SVG at {target_width}x{target_height}"
+    ))
+  })?;
+
+  // Inspired by [resvg-js/src/options.rs/fn create_pixmap](https://github.com/yisibl/resvg-js/blob/475ed45c091ef101f62f274b8a30883440bdfd89/src/options.rs#L185)
+  if let Some(bg) = background {
+    let color = tiny_skia::Color::from_rgba8(bg.red, bg.green, bg.blue, bg.alpha);
+    pix_map.fill(color);
+  }
+  resvg::render(tree, transform, &mut pix_map.as_mut());
+
+  let width = pix_map.width();
+  let height = pix_map.height();
+  // tiny_skia stores premultiplied RGBA; demultiply to straight RGBA before treating the buffer as
+  // an `RgbaImage`, otherwise semi-transparent pixels (rgba backgrounds and antialiased edges) are
+  // darkened. `take_demultiplied` still returns the owned buffer, so the handoff stays copy-free.
+  let data = pix_map.take_demultiplied();
+  RgbaImage::from_vec(width, height, data).ok_or_else(|| {
+    Error::new(
+      Status::InvalidArg,
+      "Rendered SVG pixel buffer does not match its dimensions".to_owned(),
+    )
+  })
+}
+
+/// A single staged composite/overlay operation. `overlay()` and `composite()` both push one of
+/// these; the legacy `overlay()` produces the source-over, no-tile, full-opacity shape that
+/// `compute()` routes through the unchanged fast 8-bit path.
+#[derive(Clone)]
+struct CompositeItem {
+  source: OverlaySource,
+  left: i64,
+  top: i64,
+  has_offset: bool, // both left & top were given (or legacy overlay())
+  gravity: Gravity, // used only when !has_offset; defaults to Center
+  blend: BlendMode,
+  tile: bool,
+  opacity: f32,         // 1.0 == no-op
+  simple_overlay: bool, // legacy overlay(): byte-identical 8-bit `image::overlay`; composite() => false
+}
+
+/// Where a staged mask's pixels come from.
+#[derive(Clone)]
+enum MaskSource {
+  Image(OverlaySource),
+  /// `roundCorners()` (`Some(radius)`) or `circle()` (`None`), rendered at the size of the image.
+  Preset {
+    radius: Option<f32>,
+    rendered: Arc<RasterCache>,
+  },
+}
+
+/// A staged `mask()`, `roundCorners()` or `circle()`.
+#[derive(Clone)]
+struct MaskItem {
+  source: MaskSource,
+  channel: Option<MaskChannel>,
+  invert: bool,
+  resize_to_fit: bool,
+  mode: MaskMode,
+}
+
+#[derive(Default, Clone)]
+struct ImageTransformArgs {
+  grayscale: bool,
+  invert: bool,
+  rotate: bool,
+  resize: Option<ResizeOptions>,
+  fast_resize: Option<FastResizeOptions>,
+  contrast: Option<f32>,
+  blur: Option<(f32, BlurPrecision)>,
+  unsharpen: Option<(f32, i32)>,
+  filter3x3: Option<[f32; 9]>,
+  convolve: Option<Convolution>,
+  brightness: Option<i32>,
+  huerotate: Option<i32>,
+  /// Gamma/levels/curves/saturation/vibrance/temperature/recomb, applied in call order after
+  /// huerotate. A color matrix promotes gray images to RGB.
+  tone: Vec<ToneAdjustment>,
+  orientation: Option<Orientation>,
+  crop: Option<(u32, u32, u32, u32)>,
+  overlay: Vec<CompositeItem>,
+  /// Multiply the alpha channel by this factor (0.0..=1.0). Promotes the image to RGBA8.
+  opacity: Option<f32>,
+  /// Applied in order after crop. Promotes the image to an alpha-capable type.
+  masks: Vec<MaskItem>,
+  /// Shadows, glows and borders, drawn behind the image in order after the masks. Grows the
+  /// canvas and promotes the image to RGBA.
+  effects: Vec<AlphaEffect>,
+  /// Reduce the image to this channel as `L8`/`L16`, after the masks.
+  extract_channel: Option<Channel>,
+  /// Each source's luminance is appended as a channel, last.
+  join_channels: Vec<OverlaySource>,
+}
+
+impl ImageTransformArgs {
+  /// Whether any staged transform changes the encoded image's dimensions or
+  /// color type. Pure value-filters (invert/contrast/blur/unsharpen/filter3x3/
+  /// convolve/brighten/huerotate/tone) and the in-place overlay do not, so `metadata()` can
+  /// skip cloning + applying them. `opacity` and masks are included: they promote
+  /// the image to RGBA, so `metadata().colorType` must reflect it (like `grayscale`).
+  fn changes_dimensions_or_color(&self) -> bool {
+    self.rotate
+      || self.orientation.is_some()
+      || self.resize.is_some()
+      || self.fast_resize.is_some()
+      || self.grayscale
+      || self.crop.is_some()
+      || self.opacity.is_some()
+      || !self.masks.is_empty()
+      || !self.effects.is_empty()
+      || !self.join_channels.is_empty()
+      || self.extract_channel.is_some()
+      || self.tone.iter().any(ToneAdjustment::changes_color_type)
+  }
+
+  /// No staged transform — the encode pipeline only reads the image, so it can borrow the
+  /// cached decode instead of cloning it.
+  fn is_noop(&self) -> bool {
+    !self.rotate
+      && self.orientation.is_none()
+      && self.resize.is_none()
+      && self.fast_resize.is_none()
+      && !self.grayscale
+      && !self.invert
+      && self.contrast.is_none()
+      && self.blur.is_none()
+      && self.unsharpen.is_none()
+      && self.filter3x3.is_none()
+      && self.convolve.is_none()
+      && self.brightness.is_none()
+      && self.huerotate.is_none()
+      && self.tone.is_empty()
+      && self.crop.is_none()
+      && self.overlay.is_empty()
+      && self.opacity.is_none()
+      && self.masks.is_empty()
+      && self.effects.is_empty()
+      && self.join_channels.is_empty()
+      && self.extract_channel.is_none()
+  }
+}
+
+/// Apply the staged pipeline to `image` in pipeline order:
+/// rotate/orientation -> resize -> fast_resize -> grayscale ->
+/// [value filters, encode-only] -> opacity -> crop -> masks -> effects ->
+/// extractChannel -> joinChannel. The overlay step is NOT handled here
+/// (it needs the surrounding task's lifetime/cache); callers apply it after.
+///
 /// `for_encode == false` (metadata) skips the pure value-filters, which never
 /// change dimensions or color type.
+///
+/// Returns the source region kept by the last staged `ResizeFit::Cover` resize, if any.
 fn apply_transforms(
   image: &mut DynamicImage,
   args: &ImageTransformArgs,
   base_orientation: Option<u16>,
   for_encode: bool,
-) -> Result<()> {
+) -> Result<Option<CropBox>> {
   let orientation = args
     .orientation
     .map(Ok)
@@ -709,53 +1604,76 @@ fn apply_transforms(
   }
   let raw_width = image.width();
   let raw_height = image.height();
+  let mut crop_box = None;
   if let Some(ResizeOptions {
     width,
     height,
     filter,
     fit,
+    position,
+    strategy,
+    ref background,
+    without_enlargement,
+    without_reduction,
+    linear_light,
   }) = args.resize
   {
-    match fit.unwrap_or_default() {
-      // the `resize_to_fill` is behavior like cover
-      ResizeFit::Cover => {
-        *image = image.resize_to_fill(
-          width,
-          height.unwrap_or(((width as f32 / raw_width as f32) * (raw_height as f32)) as u32),
-          filter.unwrap_or_default().into(),
-        )
-      }
-      ResizeFit::Fill => {
-        *image = image.resize_exact(
-          width,
-          height.unwrap_or(((width as f32 / raw_width as f32) * (raw_height as f32)) as u32),
-          filter.unwrap_or_default().into(),
-        )
+    let height = height.unwrap_or(((width as f32 / raw_width as f32) * (raw_height as f32)) as u32);
+    let fit = fit.unwrap_or_default();
+    let filter: FilterType = filter.unwrap_or_default().into();
+    let position = position.unwrap_or_default();
+    if !resize_is_blocked(
+      (raw_width, raw_height),
+      fit_dimensions(raw_width, raw_height, width, height, fit),
+      without_enlargement.unwrap_or(false),
+      without_reduction.unwrap_or(false),
+    ) {
+      let background = match fit {
+        ResizeFit::Contain => Some(parse_background(background.as_deref())?),
+        _ => None,
+      };
+      // Score the cover window on the sRGB pixels, before any linear-light conversion.
+      let kept = matches!(fit, ResizeFit::Cover).then(|| {
+        let window = cover_window(image, width, height, position, strategy);
+        crop_box_from_window(window, raw_width, raw_height)
+      });
+      let original_color = image.color();
+      let linear = linear_light
+        .unwrap_or(false)
+        .then(|| to_linear_light(image))
+        .flatten();
+      let source = linear.as_ref().unwrap_or(image);
+      let mut resized = match (fit, kept) {
+        (ResizeFit::Cover, Some(kept))
+          if strategy.is_some() || !matches!(position, Gravity::Center) =>
+        {
+          source
+            .crop_imm(kept.left, kept.top, kept.width, kept.height)
+            .resize_exact(width, height, filter)
+        }
+        // the `resize_to_fill` is behavior like cover
+        (ResizeFit::Cover, _) => source.resize_to_fill(width, height, filter),
+        (ResizeFit::Fill, _) => source.resize_exact(width, height, filter),
+        (ResizeFit::Inside | ResizeFit::Contain, _) => source.resize(width, height, filter),
+        (ResizeFit::Outside, _) => {
+          let (width, height) = fit_dimensions(raw_width, raw_height, width, height, fit);
+          source.resize_exact(width, height, filter)
+        }
+      };
+      if linear.is_some() {
+        resized = from_linear_light(&resized, original_color);
       }
-      ResizeFit::Inside => {
-        *image = image.resize(
-          width,
-          height.unwrap_or(((width as f32 / raw_width as f32) * (raw_height as f32)) as u32),
-          filter.unwrap_or_default().into(),
-        )
+      if let Some(background) = background {
+        resized = embed(&resized, width, height, position, background);
       }
+      *image = resized;
+      crop_box = kept;
     }
   }
-  if let Some(options) = args.fast_resize {
-    let resized_image = fast_resize(&*image, options)?;
-    *image = DynamicImage::ImageRgba8(
-      RgbaImage::from_raw(
-        resized_image.width(),
-        resized_image.height(),
-        resized_image.into_vec(),
-      )
-      .ok_or_else(|| {
-        Error::new(
-          Status::GenericFailure,
-          "Resized image is not valid".to_owned(),
-        )
-      })?,
-    );
+  if let Some(options) = &args.fast_resize
+    && let Some(kept) = fast_resize(image, options)?
+  {
+    crop_box = Some(kept);
   }
 
   if args.grayscale {
@@ -764,19 +1682,19 @@ fn apply_transforms(
   if for_encode {
     // Alpha invariant (#42): the value/color filters must leave alpha untouched so opacity
     // scales the real transparency, while the SPATIAL filters (`blur`/`unsharpen`/
-    // `filter3x3`) feather alpha on purpose. `invert` (passes `rgba[3]` through) and
-    // `brighten` (`map_with_alpha(|a| a)`) already do; `apply_contrast` and
-    // `apply_huerotate` are depth-aware, alpha-preserving re-implementations of the crate
-    // filters (which otherwise scale/crush alpha as a side effect). Nothing here needs an
-    // alpha snapshot.
+    // `filter3x3`) feather alpha on purpose (`convolve` keeps it and weights by it instead).
+    // `invert` (passes `rgba[3]` through) and `brighten` (`map_with_alpha(|a| a)`) already do;
+    // `apply_contrast` and `apply_huerotate` are depth-aware, alpha-preserving
+    // re-implementations of the crate filters (which otherwise scale/crush alpha as a side
+    // effect). Nothing here needs an alpha snapshot.
     if args.invert {
       image.invert();
     }
     if let Some(contrast) = args.contrast {
       apply_contrast(image, contrast);
     }
-    if let Some(blur) = args.blur {
-      *image = image.blur(blur);
+    if let Some((sigma, precision)) = args.blur {
+      *image = gaussian_blur(image, sigma, precision);
     }
     if let Some((sigma, threshold)) = args.unsharpen {
       *image = image.unsharpen(sigma, threshold);
@@ -784,12 +1702,23 @@ fn apply_transforms(
     if let Some(filter) = args.filter3x3 {
       *image = image.filter3x3(filter.as_ref());
     }
+    if let Some(convolution) = &args.convolve {
+      *image = convolve(image, convolution);
+    }
     if let Some(brighten) = args.brightness {
       *image = image.brighten(brighten);
     }
     if let Some(hue) = args.huerotate {
       apply_huerotate(image, hue);
     }
+    for adjustment in &args.tone {
+      apply_tone(image, adjustment);
+    }
+  } else {
+    // Metadata skips the value filters but must still see a color matrix's promotion.
+    for adjustment in &args.tone {
+      adjustment.promote_color_type(image);
+    }
   }
   // Multiply the alpha channel (issue #42), applied AFTER the value filters. The value
   // filters leave alpha untouched and the spatial filters feather it on purpose, so the
@@ -801,7 +1730,93 @@ fn apply_transforms(
   if let Some((x, y, width, height)) = args.crop {
     *image = image.crop_imm(x, y, width, height);
   }
-  Ok(())
+  // Masks fit the final (cropped) frame. Metadata only needs the promoted color type, so it skips
+  // decoding the masks: an identity opacity promotes exactly like a mask does.
+  if for_encode {
+    for mask in &args.masks {
+      apply_mask(image, mask)?;
+    }
+  } else if !args.masks.is_empty() {
+    apply_opacity(image, 1.0);
+  }
+  for effect in &args.effects {
+    apply_alpha_effect(image, effect, for_encode)?;
+  }
+  if let Some(channel) = args.extract_channel {
+    *image = extract_channel(image, channel);
+  }
+  if !args.join_channels.is_empty() {
+    join_channels(image, &args.join_channels, for_encode)?;
+  }
+  Ok(crop_box)
+}
+
+/// Run the staged pipeline — `apply_transforms`, then the composite chain — on a private copy of
+/// `image`'s pixels (or a shrunk-on-load decode), leaving the shared decode cache untouched.
+/// Consumes `args.overlay`.
+fn render_pipeline(
+  image: &ThreadsafeDynamicImage,
+  args: &mut ImageTransformArgs,
+) -> Result<DynamicImage> {
+  let (mut img, orientation) = match image.shrink_on_load(args)? {
+    Some(shrunk) => shrunk,
+    None => {
+      let meta = image.get(args.rotate)?;
+      (meta.image.clone(), meta.orientation)
+    }
+  };
+  apply_transforms(&mut img, args, orientation, true)?;
+  // Defer the composite flatten/restore to the end of the chain so intermediate alpha (e.g. a
+  // `DestOut` hole) survives for later items (#138). Capture the pre-composite color type.
+  let pre_composite_color = img.color();
+  let items = std::mem::take(&mut args.overlay);
+  // If the chain contains any composite(), run the WHOLE chain through the depth-aware path so a
+  // chained legacy overlay() also blends at the working depth (16/32-bit) instead of being crushed
+  // to 8-bit by image::imageops::overlay. A pure overlay()-only chain (no composite present) keeps
+  // the byte-identical legacy fast path.
+  let has_composite = items.iter().any(|item| !item.simple_overlay);
+  for item in items.into_iter() {
+    let top = item.source.pixels(img.width(), img.height())?;
+    // Fix D: composite() rejects an overlay larger than the base in either dimension (sharp
+    // parity). Legacy overlay() keeps its historical silent clipping.
+    if !item.simple_overlay {
+      let (tw, th) = (top.width(), top.height());
+      if tw > img.width() || th > img.height() {
+        return Err(Error::new(
+          Status::InvalidArg,
+          "Image to composite must have same dimensions or smaller".to_owned(),
+        ));
+      }
+    }
+    let (x, y) = resolve_position(
+      item.has_offset,
+      item.left,
+      item.top,
+      item.gravity,
+      img.width(),
+      img.height(),
+      top.width(),
+      top.height(),
+    );
+    if item.simple_overlay && !has_composite {
+      // Legacy overlay(): byte-identical 8-bit source-over (clips oversized overlays).
+      overlay(&mut img, &*top, x, y);
+    } else {
+      // Depth-aware at the base's native depth (8/16-bit or f32). An interleaved legacy overlay()
+      // (Over, opacity 1, no tile) is promoted here too so 16/32-bit precision is preserved across
+      // the chain. The RGBA working buffer is kept; flatten/restore happens once after the loop (#138).
+      composite_step(&mut img, &top, x, y, item.blend, item.opacity, item.tile);
+    }
+  }
+  // Collapse the RGBA working buffer back to the pre-composite color type once, after the whole
+  // chain (flatten onto black first if that type had no alpha). Legacy overlay() is unaffected.
+  // Any legacy overlay() interleaved among composite() items also draws onto this same live RGBA
+  // buffer, so alpha from an earlier composite is visible to later overlay()/composite() items —
+  // matching sharp's flatten-at-encode model.
+  if has_composite {
+    finalize_composite(&mut img, pre_composite_color);
+  }
+  Ok(img)
 }
 
 pub struct EncodeTask {
@@ -838,6 +1853,23 @@ impl EncodeOutput {
   }
 }
 
+impl EncodeOutput {
+  /// Take ownership of the encoded bytes, copying WebP and AVIF output out of the buffers their
+  /// encoders own.
+  fn into_vec(self) -> Vec<u8> {
+    match self {
+      EncodeOutput::Raw(ptr, len) => unsafe {
+        // Allocated by libwebp's `WebPMalloc`, so it has to go back through `WebPFree`.
+        let bytes = std::slice::from_raw_parts(ptr, len).to_vec();
+        libwebp_sys::WebPFree(ptr as *mut _);
+        bytes
+      },
+      EncodeOutput::Buffer(buf) => buf,
+      EncodeOutput::Avif(avif_data) => avif_data.as_slice().to_vec(),
+    }
+  }
+}
+
 unsafe impl Send for EncodeOutput {}
 
 #[napi]
@@ -846,172 +1878,519 @@ impl Task for EncodeTask {
   type JsValue = Buffer;
 
   fn compute(&mut self) -> Result<Self::Output> {
-    let meta = self.image.get(self.image_transform_args.rotate)?;
     // Only clone when the pipeline will mutate the pixels. A plain encode with nothing staged
     // borrows the cached decode read-only — no memory doubling (PR #218). When transforms/overlay
     // ARE staged we clone so the shared cache stays pristine and reuse stays idempotent (#158, Task 4).
+    // A staged shrinking resize may instead decode a reduced copy that bypasses the cache.
     let owned;
     let dynamic_image: &DynamicImage = if self.image_transform_args.is_noop() {
-      &meta.image
+      &self.image.get(self.image_transform_args.rotate)?.image
     } else {
-      let mut img = meta.image.clone();
-      apply_transforms(&mut img, &self.image_transform_args, meta.orientation, true)?;
-      // Defer the composite flatten/restore to the end of the chain so intermediate alpha (e.g. a
-      // `DestOut` hole) survives for later items (#138). Capture the pre-composite color type.
-      let pre_composite_color = img.color();
-      let items = std::mem::take(&mut self.image_transform_args.overlay);
-      // If the chain contains any composite(), run the WHOLE chain through the depth-aware path so a
-      // chained legacy overlay() also blends at the working depth (16/32-bit) instead of being crushed
-      // to 8-bit by image::imageops::overlay. A pure overlay()-only chain (no composite present) keeps
-      // the byte-identical legacy fast path.
-      let has_composite = items.iter().any(|item| !item.simple_overlay);
-      for item in items.into_iter() {
-        let top = ThreadsafeDynamicImage::new(item.buffer.clone());
-        let top_image_meta = top.get(true)?;
-        // Fix D: composite() rejects an overlay larger than the base in either dimension (sharp
-        // parity). Legacy overlay() keeps its historical silent clipping.
-        if !item.simple_overlay {
-          let (tw, th) = (top_image_meta.image.width(), top_image_meta.image.height());
-          if tw > img.width() || th > img.height() {
-            return Err(Error::new(
-              Status::InvalidArg,
-              "Image to composite must have same dimensions or smaller".to_owned(),
-            ));
-          }
-        }
-        let (x, y) = resolve_position(
-          item.has_offset,
-          item.left,
-          item.top,
-          item.gravity,
-          img.width(),
-          img.height(),
-          top_image_meta.image.width(),
-          top_image_meta.image.height(),
-        );
-        if item.simple_overlay && !has_composite {
-          // Legacy overlay(): byte-identical 8-bit source-over (clips oversized overlays).
-          overlay(&mut img, &top_image_meta.image, x, y);
-        } else {
-          // Depth-aware at the base's native depth (8/16-bit or f32). An interleaved legacy overlay()
-          // (Over, opacity 1, no tile) is promoted here too so 16/32-bit precision is preserved across
-          // the chain. The RGBA working buffer is kept; flatten/restore happens once after the loop (#138).
-          composite_step(
-            &mut img,
-            &top_image_meta.image,
-            x,
-            y,
-            item.blend,
-            item.opacity,
-            item.tile,
-          );
-        }
-      }
-      // Collapse the RGBA working buffer back to the pre-composite color type once, after the whole
-      // chain (flatten onto black first if that type had no alpha). Legacy overlay() is unaffected.
-      // Any legacy overlay() interleaved among composite() items also draws onto this same live RGBA
-      // buffer, so alpha from an earlier composite is visible to later overlay()/composite() items —
-      // matching sharp's flatten-at-encode model.
-      if has_composite {
-        finalize_composite(&mut img, pre_composite_color);
-      }
-      owned = img;
+      owned = render_pipeline(&self.image, &mut self.image_transform_args)?;
       &owned
     };
-    let width = dynamic_image.width();
-    let height = dynamic_image.height();
-    let format = match self.options {
-      EncodeOptions::Webp(quality_factor) => {
-        let (output_buf, size) =
-          unsafe { crate::webp::encode_webp_inner(dynamic_image, quality_factor, width, height) }?;
-        return Ok(EncodeOutput::Raw(output_buf, size));
-      }
-      EncodeOptions::WebpLossless => {
-        let (output_buf, size) =
-          unsafe { crate::webp::lossless_encode_webp_inner(dynamic_image, width, height) }?;
-        if output_buf.is_null() {
-          return Err(Error::new(
-            Status::GenericFailure,
-            format!(
-              "Encode lossless webp failed, {}",
-              dynamic_image.as_bytes().len()
-            ),
-          ));
-        }
-        return Ok(EncodeOutput::Raw(output_buf, size));
-      }
-      EncodeOptions::Avif(ref options) => {
-        let output = encode_avif_inner(options.clone(), dynamic_image)?;
-        return Ok(EncodeOutput::Avif(output));
-      }
-      EncodeOptions::Heic(ref options) => {
-        let buf = crate::heic::encode_heic(dynamic_image, options.clone())?;
-        return Ok(EncodeOutput::Buffer(buf));
-      }
-      EncodeOptions::Png(ref options) => {
-        let mut output: Cursor<Vec<u8>> = Cursor::new(Vec::with_capacity(
-          (dynamic_image.width() * dynamic_image.height() * 4) as usize,
+    encode_image(dynamic_image, &self.options)
+  }
+
+  fn resolve(&mut self, env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    output
+      .into_buffer_slice(&env)
+      .and_then(|slice| slice.into_buffer(&env))
+  }
+}
+
+/// Encode already-rendered pixels with `options`.
+fn encode_image(dynamic_image: &DynamicImage, options: &EncodeOptions) -> Result<EncodeOutput> {
+  let width = dynamic_image.width();
+  let height = dynamic_image.height();
+  let format = match *options {
+    EncodeOptions::Webp(quality_factor) => {
+      let (output_buf, size) =
+        unsafe { crate::webp::encode_webp_inner(dynamic_image, quality_factor, width, height) }?;
+      if output_buf.is_null() || size == 0 {
+        return Err(Error::new(
+          Status::GenericFailure,
+          format!("Encode webp failed, {}", dynamic_image.as_bytes().len()),
         ));
-        let png_encoder = image::codecs::png::PngEncoder::new_with_quality(
-          &mut output,
-          options.compression_type.unwrap_or_default().into(),
-          options.filter_type.unwrap_or_default().into(),
-        );
-        png_encoder
-          .write_image(
-            dynamic_image.as_bytes(),
-            dynamic_image.width(),
-            dynamic_image.height(),
-            dynamic_image.color().into(),
-          )
-          .map_err(|err| {
-            Error::new(
-              Status::GenericFailure,
-              format!("Encode output png failed {err}"),
-            )
-          })?;
-        return Ok(EncodeOutput::Buffer(output.into_inner()));
       }
-      EncodeOptions::Jpeg(quality) => {
-        let mut output: Cursor<Vec<u8>> = Cursor::new(Vec::with_capacity(
-          (dynamic_image.width() * dynamic_image.height() * 4) as usize,
+      return Ok(EncodeOutput::Raw(output_buf, size));
+    }
+    EncodeOptions::WebpLossless => {
+      let (output_buf, size) =
+        unsafe { crate::webp::lossless_encode_webp_inner(dynamic_image, width, height) }?;
+      if output_buf.is_null() {
+        return Err(Error::new(
+          Status::GenericFailure,
+          format!(
+            "Encode lossless webp failed, {}",
+            dynamic_image.as_bytes().len()
+          ),
         ));
-        let mut encoder =
-          image::codecs::jpeg::JpegEncoder::new_with_quality(&mut output, quality as u8);
-        encoder.encode_image(dynamic_image).map_err(|err| {
+      }
+      return Ok(EncodeOutput::Raw(output_buf, size));
+    }
+    EncodeOptions::Avif(ref options) => {
+      let output = encode_avif_inner(options.clone(), dynamic_image)?;
+      return Ok(EncodeOutput::Avif(output));
+    }
+    EncodeOptions::Heic(ref options) => {
+      let buf = crate::heic::encode_heic(dynamic_image, options.clone())?;
+      return Ok(EncodeOutput::Buffer(buf));
+    }
+    EncodeOptions::Png(ref options) => {
+      let mut output: Cursor<Vec<u8>> = Cursor::new(Vec::with_capacity(
+        (dynamic_image.width() * dynamic_image.height() * 4) as usize,
+      ));
+      let png_encoder = image::codecs::png::PngEncoder::new_with_quality(
+        &mut output,
+        options.compression_type.unwrap_or_default().into(),
+        options.filter_type.unwrap_or_default().into(),
+      );
+      png_encoder
+        .write_image(
+          dynamic_image.as_bytes(),
+          dynamic_image.width(),
+          dynamic_image.height(),
+          dynamic_image.color().into(),
+        )
+        .map_err(|err| {
           Error::new(
             Status::GenericFailure,
-            format!("Encode output jpeg failed {err}"),
+            format!("Encode output png failed {err}"),
           )
         })?;
-        return Ok(EncodeOutput::Buffer(output.into_inner()));
+      return Ok(EncodeOutput::Buffer(output.into_inner()));
+    }
+    EncodeOptions::Jpeg(quality) => {
+      let mut output: Cursor<Vec<u8>> = Cursor::new(Vec::with_capacity(
+        (dynamic_image.width() * dynamic_image.height() * 4) as usize,
+      ));
+      let mut encoder =
+        image::codecs::jpeg::JpegEncoder::new_with_quality(&mut output, quality as u8);
+      encoder.encode_image(dynamic_image).map_err(|err| {
+        Error::new(
+          Status::GenericFailure,
+          format!("Encode output jpeg failed {err}"),
+        )
+      })?;
+      return Ok(EncodeOutput::Buffer(output.into_inner()));
+    }
+    EncodeOptions::Bmp => ImageFormat::Bmp,
+    EncodeOptions::Ico => ImageFormat::Ico,
+    EncodeOptions::Tiff => ImageFormat::Tiff,
+    EncodeOptions::Pnm => ImageFormat::Pnm,
+    EncodeOptions::Tga => ImageFormat::Tga,
+    EncodeOptions::Farbfeld => ImageFormat::Farbfeld,
+    EncodeOptions::RawPixels => {
+      return Ok(EncodeOutput::Buffer(dynamic_image.as_bytes().to_vec()));
+    }
+  };
+  let mut output: Cursor<Vec<u8>> = Cursor::new(Vec::with_capacity(
+    (dynamic_image.width() * dynamic_image.height() * 4) as usize,
+  ));
+  dynamic_image.write_to(&mut output, format).map_err(|err| {
+    Error::new(
+      Status::InvalidArg,
+      format!("Encode to [{:?}] error {}", &format, err),
+    )
+  })?;
+  Ok(EncodeOutput::Buffer(output.into_inner()))
+}
+
+pub struct GenerateVariantsTask {
+  image: Arc<ThreadsafeDynamicImage>,
+  image_transform_args: ImageTransformArgs,
+  plan: VariantPlan,
+  formats: Vec<(OutputFormat, EncodeOptions)>,
+}
+
+impl GenerateVariantsTask {
+  fn new(transformer: &Transformer, options: VariantsOptions) -> Result<Self> {
+    let plan = VariantPlan::new(&options)?;
+    Ok(GenerateVariantsTask {
+      image: transformer.dynamic_image.clone(),
+      image_transform_args: transformer.image_transform_args.clone(),
+      plan,
+      formats: options
+        .formats
+        .into_iter()
+        .enumerate()
+        .map(|(index, spec)| Ok((spec.format, spec.into_options(index)?)))
+        .collect::<Result<_>>()?,
+    })
+  }
+}
+
+#[napi]
+impl Task for GenerateVariantsTask {
+  type Output = Vec<VariantOutput>;
+  type JsValue = Vec<Variant>;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    let (plan, formats) = (&self.plan, &self.formats);
+    with_rendered_image(&self.image, &mut self.image_transform_args, |source| {
+      if source.width() == 0 || source.height() == 0 {
+        return Err(Error::new(
+          Status::InvalidArg,
+          "generateVariants: the image is empty".to_owned(),
+        ));
       }
-      EncodeOptions::Bmp => ImageFormat::Bmp,
-      EncodeOptions::Ico => ImageFormat::Ico,
-      EncodeOptions::Tiff => ImageFormat::Tiff,
-      EncodeOptions::Pnm => ImageFormat::Pnm,
-      EncodeOptions::Tga => ImageFormat::Tga,
-      EncodeOptions::Farbfeld => ImageFormat::Farbfeld,
-      EncodeOptions::RawPixels => {
-        return Ok(EncodeOutput::Buffer(dynamic_image.as_bytes().to_vec()));
+      let boxes = plan.boxes(source.width(), source.height());
+      let levels = &plan.pyramid(source, &boxes)?;
+      // Every kept width x format, in request order.
+      let jobs: Vec<_> = plan
+        .widths
+        .iter()
+        .filter_map(|width| {
+          boxes
+            .iter()
+            .position(|(level_width, _)| level_width == width)
+        })
+        .flat_map(|level| formats.iter().map(move |format| (&levels[level], format)))
+        .collect();
+      encode_in_parallel(&jobs, |(level, (format, options))| {
+        Ok(VariantOutput {
+          width: level.width(),
+          height: level.height(),
+          format: *format,
+          bytes: encode_image(level, options)?.into_vec(),
+        })
+      })
+    })?
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output.into_iter().map(Variant::from).collect())
+  }
+}
+
+pub struct TileTask {
+  image: Arc<ThreadsafeDynamicImage>,
+  image_transform_args: ImageTransformArgs,
+  plan: TilePlan,
+  encode: EncodeOptions,
+}
+
+impl TileTask {
+  fn new(transformer: &Transformer, options: Option<TileOptions>) -> Result<Self> {
+    let options = options.unwrap_or(TileOptions {
+      tile_size: None,
+      overlap: None,
+      layout: None,
+      format: None,
+      name: None,
+      id: None,
+      background: None,
+      directory: None,
+    });
+    let plan = TilePlan::new(&options)?;
+    let encode = match options.format {
+      Some(spec) => spec.into_options(0)?,
+      None => EncodeOptions::Jpeg(90),
+    };
+    Ok(TileTask {
+      image: transformer.dynamic_image.clone(),
+      image_transform_args: transformer.image_transform_args.clone(),
+      plan,
+      encode,
+    })
+  }
+}
+
+#[napi]
+impl Task for TileTask {
+  type Output = TileSetOutput;
+  type JsValue = TileSet;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    let (plan, encode) = (&self.plan, &self.encode);
+    with_rendered_image(&self.image, &mut self.image_transform_args, |source| {
+      if source.width() == 0 || source.height() == 0 {
+        return Err(Error::new(
+          Status::InvalidArg,
+          "tile: the image is empty".to_owned(),
+        ));
       }
+      let level_sizes = plan.level_sizes(source.width(), source.height());
+      let levels = plan.pyramid(source, &level_sizes)?;
+      let rects = plan.tiles(&level_sizes);
+      let (manifest_path, manifest) = plan.manifest(&level_sizes, rects.len());
+      let tiles = encode_in_parallel(&rects, |rect| {
+        let tile = plan.slice(&levels[rect.level as usize], rect);
+        let bytes = encode_image(&tile, encode)?.into_vec();
+        // Tiles written to disk are dropped right away instead of piling up in memory.
+        let bytes = match &plan.directory {
+          Some(directory) => {
+            TilePlan::write(directory, &rect.path, &bytes)?;
+            None
+          }
+          None => Some(bytes),
+        };
+        Ok(TileOutput {
+          rect: rect.clone(),
+          bytes,
+        })
+      })?;
+      if let Some(directory) = &plan.directory {
+        TilePlan::write(directory, &manifest_path, manifest.as_bytes())?;
+      }
+      Ok(TileSetOutput {
+        manifest_path,
+        manifest,
+        levels: level_sizes.len() as u32,
+        tiles,
+      })
+    })?
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output.into())
+  }
+}
+
+pub struct EncodeTargetTask {
+  image: Arc<ThreadsafeDynamicImage>,
+  image_transform_args: ImageTransformArgs,
+  search: TargetSearch,
+}
+
+#[napi]
+impl Task for EncodeTargetTask {
+  type Output = TargetOutput;
+  type JsValue = EncodeTargetResult;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    let search = &self.search;
+    with_rendered_image(&self.image, &mut self.image_transform_args, |source| {
+      search_quality(source, search)
+    })?
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output.into())
+  }
+}
+
+/// Run `search` against `source`, decoding every candidate to score it.
+fn search_quality(source: &DynamicImage, search: &TargetSearch) -> Result<TargetOutput> {
+  search.run(
+    |quality| {
+      let options = match search.format {
+        TargetFormat::Jpeg => EncodeOptions::Jpeg(quality),
+        TargetFormat::Webp => EncodeOptions::Webp(quality),
+        TargetFormat::Avif => EncodeOptions::Avif(Some(AvifConfig {
+          quality: Some(quality),
+          ..search.avif.clone().unwrap_or_default()
+        })),
+      };
+      Ok(encode_image(source, &options)?.into_vec())
+    },
+    |bytes| {
+      let candidate = ThreadsafeDynamicImage::new(Arc::new(bytes.to_vec().into()));
+      let decoded = &candidate.get(false)?.image;
+      let comparison = compare_images(source, decoded, &CompareOptions::default())?;
+      Ok(Score {
+        ssim: comparison.ssim,
+        max_delta_e: comparison.max_delta_e,
+      })
+    },
+  )
+}
+
+pub struct EncodeAutoTask {
+  image: Arc<ThreadsafeDynamicImage>,
+  image_transform_args: ImageTransformArgs,
+  options: EncodeAutoOptions,
+}
+
+#[napi]
+impl Task for EncodeAutoTask {
+  type Output = AutoOutput;
+  type JsValue = EncodeAutoResult;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    let options = &self.options;
+    with_rendered_image(&self.image, &mut self.image_transform_args, |source| {
+      // Opaque RGBA (most PNG decodes) still gets a JPEG candidate.
+      let candidates = options.candidates(!is_opaque(source))?;
+      let outputs =
+        encode_in_parallel(&candidates, |candidate| encode_candidate(source, candidate))?;
+      pick_smallest(outputs, options.prefer_lossless.unwrap_or(false)).ok_or_else(|| {
+        Error::new(
+          Status::InvalidArg,
+          "encodeAuto: no candidate format".to_owned(),
+        )
+      })
+    })?
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output.into())
+  }
+}
+
+/// Run `encode` on every item, all reading the same rendered pixels. The calling thread takes the
+/// first share and hands the rest to whatever [`HelperThreads`] are free, so with every helper
+/// busy the items simply run one after another here.
+/// Results keep the order of `items`; a panicking encoder becomes an error.
+fn encode_in_parallel<T: Sync, R: Send>(
+  items: &[T],
+  encode: impl Fn(&T) -> Result<R> + Sync,
+) -> Result<Vec<R>> {
+  let helpers = HelperThreads::take(items.len().saturating_sub(1));
+  let per_thread = items.len().div_ceil(helpers.count() + 1).max(1);
+  let run = |chunk: &[T]| {
+    std::panic::catch_unwind(AssertUnwindSafe(|| {
+      chunk.iter().map(&encode).collect::<Result<Vec<_>>>()
+    }))
+  };
+  let chunks = std::thread::scope(|scope| {
+    let mut chunks = items.chunks(per_thread);
+    let own = chunks.next().unwrap_or_default();
+    let handles: Vec<_> = chunks
+      .map(|chunk| {
+        let run = &run;
+        scope.spawn(move || run(chunk))
+      })
+      .collect();
+    let mut results = vec![run(own)];
+    results.extend(
+      handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap_or_else(Err)),
+    );
+    results
+  });
+  drop(helpers);
+  let mut outputs = Vec::with_capacity(items.len());
+  for chunk in chunks {
+    let chunk =
+      chunk.map_err(|_| Error::new(Status::GenericFailure, "Encode worker panicked".to_owned()))?;
+    outputs.extend(chunk?);
+  }
+  Ok(outputs)
+}
+
+fn encode_candidate(source: &DynamicImage, candidate: &AutoCandidate) -> Result<AutoOutput> {
+  let format = candidate.format;
+  let options = match (&candidate.encode, format) {
+    (AutoEncode::Search(search), _) => {
+      let output = search_quality(source, search)?;
+      return Ok(AutoOutput {
+        format,
+        lossless: false,
+        quality: Some(output.quality),
+        ssim: output.score.map(|score| score.ssim),
+        bytes: output.bytes,
+      });
+    }
+    (AutoEncode::Lossless, OutputFormat::Webp) => EncodeOptions::WebpLossless,
+    (AutoEncode::Lossless, _) => EncodeOptions::Png(Default::default()),
+    (AutoEncode::Quality(quality), OutputFormat::Jpeg) => {
+      EncodeOptions::Jpeg(quality.unwrap_or(90))
+    }
+    (AutoEncode::Quality(quality), OutputFormat::Webp) => {
+      EncodeOptions::Webp(quality.unwrap_or(90))
+    }
+    (AutoEncode::Quality(quality), _) => EncodeOptions::Avif(Some(AvifConfig {
+      quality: *quality,
+      ..Default::default()
+    })),
+  };
+  let lossless = matches!(candidate.encode, AutoEncode::Lossless);
+  Ok(AutoOutput {
+    format,
+    lossless,
+    quality: match candidate.encode {
+      AutoEncode::Quality(quality) => quality,
+      _ => None,
+    },
+    ssim: None,
+    bytes: encode_image(source, &options)?.into_vec(),
+  })
+}
+
+impl EncodeSpec {
+  fn into_options(self, index: usize) -> Result<EncodeOptions> {
+    let invalid = |message: &str| {
+      Err(Error::new(
+        Status::InvalidArg,
+        format!("encodeMany: output {index}: {message}"),
+      ))
     };
-    let mut output: Cursor<Vec<u8>> = Cursor::new(Vec::with_capacity(
-      (dynamic_image.width() * dynamic_image.height() * 4) as usize,
-    ));
-    dynamic_image.write_to(&mut output, format).map_err(|err| {
-      Error::new(
+    if let Some(quality) = self.quality
+      && !(1..=100).contains(&quality)
+    {
+      return invalid(&format!("`quality` must be in 1..=100, got {quality}"));
+    }
+    let lossless = self.lossless.unwrap_or(false);
+    if lossless && self.format != OutputFormat::Webp {
+      return invalid("`lossless` is only for `OutputFormat.Webp`");
+    }
+    if self.png.is_some() && self.format != OutputFormat::Png {
+      return invalid("`png` settings need `OutputFormat.Png`");
+    }
+    if self.avif.is_some() && self.format != OutputFormat::Avif {
+      return invalid("`avif` settings need `OutputFormat.Avif`");
+    }
+    Ok(match self.format {
+      OutputFormat::Jpeg => EncodeOptions::Jpeg(self.quality.unwrap_or(90)),
+      OutputFormat::Webp if lossless => EncodeOptions::WebpLossless,
+      OutputFormat::Webp => EncodeOptions::Webp(self.quality.unwrap_or(90)),
+      OutputFormat::Png => EncodeOptions::Png(self.png.unwrap_or_default()),
+      OutputFormat::Avif => match (self.avif, self.quality) {
+        (config, None) => EncodeOptions::Avif(config),
+        (config, quality) => EncodeOptions::Avif(Some(AvifConfig {
+          quality,
+          ..config.unwrap_or_default()
+        })),
+      },
+    })
+  }
+}
+
+pub struct EncodeManyTask {
+  image: Arc<ThreadsafeDynamicImage>,
+  image_transform_args: ImageTransformArgs,
+  outputs: Vec<EncodeOptions>,
+}
+
+impl EncodeManyTask {
+  fn new(transformer: &Transformer, outputs: Vec<EncodeSpec>) -> Result<Self> {
+    if outputs.is_empty() {
+      return Err(Error::new(
         Status::InvalidArg,
-        format!("Encode to [{:?}] error {}", &format, err),
-      )
-    })?;
-    Ok(EncodeOutput::Buffer(output.into_inner()))
+        "encodeMany: `outputs` must not be empty".to_owned(),
+      ));
+    }
+    Ok(EncodeManyTask {
+      image: transformer.dynamic_image.clone(),
+      image_transform_args: transformer.image_transform_args.clone(),
+      outputs: outputs
+        .into_iter()
+        .enumerate()
+        .map(|(index, spec)| spec.into_options(index))
+        .collect::<Result<_>>()?,
+    })
   }
+}
 
-  fn resolve(&mut self, env: Env, output: Self::Output) -> Result<Self::JsValue> {
-    output
-      .into_buffer_slice(&env)
-      .and_then(|slice| slice.into_buffer(&env))
+#[napi]
+impl Task for EncodeManyTask {
+  type Output = Vec<Vec<u8>>;
+  type JsValue = Vec<Buffer>;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    let outputs = &self.outputs;
+    with_rendered_image(&self.image, &mut self.image_transform_args, |source| {
+      encode_in_parallel(outputs, |options| {
+        Ok(encode_image(source, options)?.into_vec())
+      })
+    })?
+  }
+
+  fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output.into_iter().map(Buffer::from).collect())
   }
 }
 
@@ -1052,29 +2431,14 @@ impl Transformer {
   #[napi]
   /// Support CSS3 color, e.g. rgba(255, 255, 255, .8)
   pub fn from_svg(input: Either<String, &[u8]>, background: Option<String>) -> Result<Transformer> {
-    let font_db = FONT_DB
-      .get_or_init(|| {
-        let mut fontdb = Database::new();
-        fontdb.load_system_fonts();
-        Arc::new(fontdb)
-      })
-      .clone();
-    let options = Options::<'_> {
-      fontdb: font_db,
-      ..Default::default()
-    };
-    let tree = match input {
-      Either::A(a) => usvg::Tree::from_str(a.as_str(), &options),
-      Either::B(b) => usvg::Tree::from_data(b, &options),
-    }
-    .map_err(|err| Error::from_reason(format!("{err}")))?;
+    let tree = load_svg(match &input {
+      Either::A(a) => Either::A(a.as_str()),
+      Either::B(b) => Either::B(b),
+    })?;
     let svg_width = tree.size().width();
     let svg_height = tree.size().height();
     // (usvg's `Size` is `NonZeroPositiveF32`, so both are > 0 and finite here.)
     const MIN_SVG_SIZE: f32 = 1000.0;
-    // Upper bound on the rasterized SVG area (~1 GiB of RGBA). Bounds memory for adversarial or
-    // degenerate intrinsic SVG sizes; tune if you need larger native-size SVG rasters.
-    const MAX_SVG_PIXELS: u64 = 1 << 28; // 268_435_456 px
     // Smallest uniform power-of-two scale whose ROUNDED longer axis reaches `MIN_SVG_SIZE`, for
     // resize quality. Thresholding on the rounded dimension (not the raw float) avoids needlessly
     // doubling a value like 999.6 -> 1000. Targeting only the longer axis keeps a thin, high-aspect
@@ -1108,41 +2472,17 @@ impl Transformer {
         "SVG raster size out of range: source {svg_width}x{svg_height} scaled by {scale}"
       )));
     }
-    let target_width = target_width as u32;
-    let target_height = target_height as u32;
-    let mut pix_map = tiny_skia::Pixmap::new(target_width, target_height).ok_or_else(|| {
-      Error::from_reason(format!(
-        "Failed to rasterize SVG at {target_width}x{target_height}"
-      ))
-    })?;
-
-    // Inspired by [resvg-js/src/options.rs/fn create_pixmap](https://github.com/yisibl/resvg-js/blob/475ed45c091ef101f62f274b8a30883440bdfd89/src/options.rs#L185)
     let background = background
       .map(|bg| bg.parse::<svgtypes::Color>())
       .transpose()
       .map_err(|err| Error::from_reason(format!("{err}")))?;
-    if let Some(bg) = background {
-      let color = tiny_skia::Color::from_rgba8(bg.red, bg.green, bg.blue, bg.alpha);
-      pix_map.fill(color);
-    }
-    resvg::render(
+    let image = rasterize_svg(
       &tree,
+      target_width as u32,
+      target_height as u32,
       tiny_skia::Transform::from_scale(scale, scale),
-      &mut pix_map.as_mut(),
-    );
-
-    let width = pix_map.width();
-    let height = pix_map.height();
-    // tiny_skia stores premultiplied RGBA; demultiply to straight RGBA before treating the buffer as
-    // an `RgbaImage`, otherwise semi-transparent pixels (rgba backgrounds and antialiased edges) are
-    // darkened. `take_demultiplied` still returns the owned buffer, so the handoff stays copy-free.
-    let data = pix_map.take_demultiplied();
-    let image = RgbaImage::from_vec(width, height, data).ok_or_else(|| {
-      Error::new(
-        Status::InvalidArg,
-        "Rendered SVG pixel buffer does not match its dimensions".to_owned(),
-      )
-    })?;
+      background,
+    )?;
     Ok(transformer_from_rgba8(image, DetectedFormat::Svg))
   }
 
@@ -1173,27 +2513,159 @@ impl Transformer {
   }
 
   #[napi]
-  pub fn metadata(
-    &mut self,
-    with_exif: Option<bool>,
+  /// Render a [BlurHash](https://blurha.sh) string into an opaque `width`x`height` image.
+  pub fn from_blurhash(hash: String, width: u32, height: u32) -> Result<Transformer> {
+    if width == 0 || height == 0 || (width as u64) * (height as u64) > MAX_SVG_PIXELS {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("fromBlurhash: {width}x{height} is out of range"),
+      ));
+    }
+    Ok(transformer_from_rgba8(
+      decode_blurhash(&hash, width, height)?,
+      DetectedFormat::Standard(ImageFormat::Png),
+    ))
+  }
+
+  #[napi]
+  pub fn metadata(
+    &mut self,
+    with_exif: Option<bool>,
+    signal: Option<AbortSignal>,
+  ) -> AsyncTask<MetadataTask> {
+    AsyncTask::with_optional_signal(
+      MetadataTask {
+        dynamic_image: self.dynamic_image.clone(),
+        with_exif: with_exif.unwrap_or(false),
+        image_transform_args: self.image_transform_args.clone(),
+      },
+      signal,
+    )
+  }
+
+  #[napi]
+  pub fn metadata_sync(&mut self, env: Env, with_exif: Option<bool>) -> Result<Metadata> {
+    let mut task = MetadataTask {
+      dynamic_image: self.dynamic_image.clone(),
+      with_exif: with_exif.unwrap_or(false),
+      image_transform_args: self.image_transform_args.clone(),
+    };
+    let output = task.compute()?;
+    task.resolve(env, output)
+  }
+
+  #[napi]
+  /// Per-channel min/max/mean/stdev and histograms, opacity, luminance entropy, a sharpness
+  /// estimate and the dominant color of the image as it would be encoded (staged transforms
+  /// applied). Handy for rejecting blank or blurry uploads.
+  pub fn stats(&self, signal: Option<AbortSignal>) -> AsyncTask<StatsTask> {
+    AsyncTask::with_optional_signal(
+      StatsTask {
+        image: self.dynamic_image.clone(),
+        image_transform_args: self.image_transform_args.clone(),
+      },
+      signal,
+    )
+  }
+
+  #[napi]
+  /// Synchronous version of `stats`.
+  pub fn stats_sync(&self, env: Env) -> Result<Stats> {
+    let mut task = StatsTask {
+      image: self.dynamic_image.clone(),
+      image_transform_args: self.image_transform_args.clone(),
+    };
+    let output = task.compute()?;
+    task.resolve(env, output)
+  }
+
+  #[napi]
+  /// A [BlurHash](https://blurha.sh) placeholder string for the image as it would be encoded
+  /// (staged transforms applied). Alpha is ignored.
+  pub fn blurhash(
+    &self,
+    options: Option<BlurhashOptions>,
+    signal: Option<AbortSignal>,
+  ) -> Result<AsyncTask<BlurhashTask>> {
+    Ok(AsyncTask::with_optional_signal(
+      BlurhashTask {
+        image: self.dynamic_image.clone(),
+        image_transform_args: self.image_transform_args.clone(),
+        components: options.unwrap_or_default().components()?,
+      },
+      signal,
+    ))
+  }
+
+  #[napi]
+  /// Synchronous version of `blurhash`.
+  pub fn blurhash_sync(&self, env: Env, options: Option<BlurhashOptions>) -> Result<String> {
+    let mut task = BlurhashTask {
+      image: self.dynamic_image.clone(),
+      image_transform_args: self.image_transform_args.clone(),
+      components: options.unwrap_or_default().components()?,
+    };
+    let output = task.compute()?;
+    task.resolve(env, output)
+  }
+
+  #[napi]
+  /// A [ThumbHash](https://evanw.github.io/thumbhash/) placeholder for the image as it would be
+  /// encoded (staged transforms applied). Unlike BlurHash it keeps alpha and the aspect ratio.
+  /// Images larger than 100x100 are downscaled before hashing.
+  pub fn thumbhash(&self, signal: Option<AbortSignal>) -> AsyncTask<ThumbhashTask> {
+    AsyncTask::with_optional_signal(
+      ThumbhashTask {
+        image: self.dynamic_image.clone(),
+        image_transform_args: self.image_transform_args.clone(),
+      },
+      signal,
+    )
+  }
+
+  #[napi]
+  /// Synchronous version of `thumbhash`.
+  pub fn thumbhash_sync(&self, env: Env) -> Result<Buffer> {
+    let mut task = ThumbhashTask {
+      image: self.dynamic_image.clone(),
+      image_transform_args: self.image_transform_args.clone(),
+    };
+    let output = task.compute()?;
+    task.resolve(env, output)
+  }
+
+  #[napi]
+  /// A perceptual hash of the image as it would be encoded (staged transforms applied), as a
+  /// string of `size * size` `'0'`/`'1'` characters, top-left bit first. Near-duplicates have a
+  /// small `hammingDistance()`; `BigInt('0b' + hash)` gives the numeric form.
+  pub fn perceptual_hash(
+    &self,
+    options: Option<PerceptualHashOptions>,
     signal: Option<AbortSignal>,
-  ) -> AsyncTask<MetadataTask> {
-    AsyncTask::with_optional_signal(
-      MetadataTask {
-        dynamic_image: self.dynamic_image.clone(),
-        with_exif: with_exif.unwrap_or(false),
+  ) -> Result<AsyncTask<PerceptualHashTask>> {
+    let options = options.unwrap_or_default();
+    options.resolve()?;
+    Ok(AsyncTask::with_optional_signal(
+      PerceptualHashTask {
+        image: self.dynamic_image.clone(),
         image_transform_args: self.image_transform_args.clone(),
+        options,
       },
       signal,
-    )
+    ))
   }
 
   #[napi]
-  pub fn metadata_sync(&mut self, env: Env, with_exif: Option<bool>) -> Result<Metadata> {
-    let mut task = MetadataTask {
-      dynamic_image: self.dynamic_image.clone(),
-      with_exif: with_exif.unwrap_or(false),
+  /// Synchronous version of `perceptualHash`.
+  pub fn perceptual_hash_sync(
+    &self,
+    env: Env,
+    options: Option<PerceptualHashOptions>,
+  ) -> Result<String> {
+    let mut task = PerceptualHashTask {
+      image: self.dynamic_image.clone(),
       image_transform_args: self.image_transform_args.clone(),
+      options: options.unwrap_or_default(),
     };
     let output = task.compute()?;
     task.resolve(env, output)
@@ -1243,6 +2715,12 @@ impl Transformer {
           height,
           filter,
           fit,
+          position: None,
+          strategy: None,
+          background: None,
+          without_enlargement: None,
+          without_reduction: None,
+          linear_light: None,
         });
       }
       Either::B(options) => self.image_transform_args.resize = Some(options),
@@ -1264,10 +2742,20 @@ impl Transformer {
 
   #[napi]
   /// Performs a Gaussian blur on this image.
-  /// `sigma` is a measure of how much to blur by.
-  pub fn blur(&mut self, sigma: f64) -> &Self {
-    self.image_transform_args.blur = Some(sigma as f32);
-    self
+  /// `sigma` is a measure of how much to blur by; `0` blurs by `0.8`.
+  /// `precision` picks the exact kernel or a faster three-box approximation (default `Auto`, by
+  /// sigma). Color is premultiplied by alpha while blurring, so transparent areas don't darken
+  /// the edges, and large images are blurred on several threads.
+  pub fn blur(&mut self, sigma: f64, precision: Option<BlurPrecision>) -> Result<&Self> {
+    if !sigma.is_finite() || sigma < 0.0 {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("blur: sigma must be a non-negative number, got {sigma}"),
+      ));
+    }
+    let sigma = if sigma == 0.0 { 0.8 } else { sigma as f32 };
+    self.image_transform_args.blur = Some((sigma, precision.unwrap_or_default()));
+    Ok(self)
   }
 
   #[napi]
@@ -1304,6 +2792,18 @@ impl Transformer {
     Ok(self)
   }
 
+  #[napi]
+  /// Convolves this image with a kernel of any size, or a preset.
+  /// Works at the image's native depth and leaves alpha as is; next to transparency the kernel is
+  /// weighted by alpha, so edges don't darken.
+  pub fn convolve(&mut self, kernel: Either<ConvolveOptions, ConvolvePreset>) -> Result<&Self> {
+    self.image_transform_args.convolve = Some(match kernel {
+      Either::A(options) => Convolution::from_options(options)?,
+      Either::B(preset) => Convolution::preset(preset),
+    });
+    Ok(self)
+  }
+
   #[napi]
   /// Adjust the contrast of this image.
   /// `contrast` is the amount to adjust the contrast by.
@@ -1332,6 +2832,79 @@ impl Transformer {
     self
   }
 
+  #[napi]
+  /// Gamma-correct the color channels: each normalized sample becomes `sample ^ (1 / gamma)`.
+  /// Values above `1` brighten the midtones, values below darken them.
+  pub fn gamma(&mut self, gamma: f64) -> Result<&Self> {
+    self.push_tone(ToneAdjustment::gamma(gamma)?)
+  }
+
+  #[napi]
+  /// Remap the tonal range like a levels dialog: `inBlack..=inWhite` is stretched to
+  /// `outBlack..=outWhite` (defaults `0..=255`) through a midtone `gamma` (default `1`).
+  /// All levels are in 8-bit units whatever the image's depth.
+  pub fn levels(
+    &mut self,
+    in_black: f64,
+    in_white: f64,
+    gamma: Option<f64>,
+    out_black: Option<f64>,
+    out_white: Option<f64>,
+  ) -> Result<&Self> {
+    self.push_tone(ToneAdjustment::levels(
+      in_black, in_white, gamma, out_black, out_white,
+    )?)
+  }
+
+  #[napi]
+  /// Map the color channels through tone curves given as control points. The curves are
+  /// compiled to lookup tables at the image's depth.
+  pub fn curves(&mut self, options: CurvesOptions) -> Result<&Self> {
+    self.push_tone(ToneAdjustment::curves(options)?)
+  }
+
+  #[napi]
+  /// Scale the saturation: `0` is grayscale, `1` leaves the image unchanged, `2` doubles it.
+  pub fn saturation(&mut self, amount: f64) -> Result<&Self> {
+    self.push_tone(ToneAdjustment::saturation(amount)?)
+  }
+
+  #[napi]
+  /// Like `saturation`, but weighted towards muted colors, so skin tones and already vivid
+  /// colors are left mostly alone. `amount` is in `-1..=1`; `0` leaves the image unchanged.
+  pub fn vibrance(&mut self, amount: f64) -> Result<&Self> {
+    self.push_tone(ToneAdjustment::vibrance(amount)?)
+  }
+
+  #[napi]
+  /// Shift the white balance. `temperature` in `-100..=100` cools (negative) or warms (positive)
+  /// the image; `tint` in `-100..=100` shifts it towards green (negative) or magenta (positive).
+  pub fn temperature(&mut self, temperature: f64, tint: Option<f64>) -> Result<&Self> {
+    self.push_tone(ToneAdjustment::temperature(temperature, tint)?)
+  }
+
+  #[napi]
+  /// Recombine the channels through a color matrix, like sharp's `recomb`: each output channel is
+  /// the row's weighted sum of the input channels. A 3x3 matrix mixes RGB and leaves alpha alone;
+  /// a 4x4 one mixes RGBA and adds alpha to images without it. Gray images become RGB.
+  pub fn recomb(&mut self, matrix: Vec<Vec<f64>>) -> Result<&Self> {
+    self.push_tone(ToneAdjustment::recomb(matrix)?)
+  }
+
+  #[napi]
+  /// Tint the image with `color`'s hue while keeping its luminance.
+  /// Support CSS3 color, e.g. rgba(255, 255, 255, .8); the color's alpha is ignored.
+  pub fn tint(&mut self, color: String) -> Result<&Self> {
+    self.push_tone(ToneAdjustment::tint(&color)?)
+  }
+
+  #[napi]
+  /// Apply the classic sepia color matrix.
+  pub fn sepia(&mut self) -> &Self {
+    self.image_transform_args.tone.push(ToneAdjustment::sepia());
+    self
+  }
+
   #[napi]
   /// Multiply the image's alpha channel by `factor` (clamped to `0.0..=1.0`),
   /// like CSS `opacity`. `1.0` leaves the image unchanged; `0.0` makes it fully
@@ -1360,7 +2933,7 @@ impl Transformer {
   /// Overlay an image at a given coordinate (x, y) using source-over blending.
   pub fn overlay(&mut self, on_top: Uint8Array, x: i64, y: i64) -> Result<&Self> {
     self.image_transform_args.overlay.push(CompositeItem {
-      buffer: Arc::new(on_top),
+      source: OverlaySource::encoded(on_top),
       left: x,
       top: y,
       has_offset: true,         // legacy overlay() is always an explicit offset
@@ -1401,11 +2974,186 @@ impl Transformer {
   /// `Over` at any alpha; for a translucent source or backdrop combined with a separable blend mode
   /// (Multiply, Screen, HardLight, etc.), per-pixel values differ from sharp, which uses
   /// premultiplied-alpha math.
+  ///
+  /// `on_top` is an encoded image, another `Transformer` (composited with the pipeline it has
+  /// staged at call time), or an `OverlayInput` with raw RGBA pixels or an SVG document. The
+  /// overlay is decoded/rendered once and reused by every later encode of this `Transformer`.
   pub fn composite(
     &mut self,
-    on_top: Uint8Array,
+    on_top: Either3<Uint8Array, ClassInstance<'_, Transformer>, OverlayInput>,
     options: Option<CompositeOptions>,
   ) -> Result<&Self> {
+    self.push_composite(OverlaySource::from_js(on_top)?, options)?;
+    Ok(self)
+  }
+
+  #[napi]
+  /// Draw text onto this image: render `text` with the system fonts (or `fontFile`) into an RGBA
+  /// layer and composite it like `composite()`, with any `BlendMode`, `Gravity` or offset. The
+  /// layer must fit inside the image; set `width` to wrap long text.
+  pub fn text(&mut self, text: TextOptions, options: Option<CompositeOptions>) -> Result<&Self> {
+    validate_text(&text)?;
+    self.push_composite(
+      OverlaySource::Text {
+        options: Arc::new(text),
+        rendered: Default::default(),
+      },
+      options,
+    )?;
+    Ok(self)
+  }
+
+  #[napi]
+  /// Draw `shapes` in order onto a transparent layer the size of this image (after the staged
+  /// resize/crop), then composite that layer over the image with `blend` (default `Over`).
+  /// Coordinates are pixels of the image being drawn on. Like `composite()`, drawing happens at
+  /// the image's native depth and keeps its color type.
+  pub fn draw(&mut self, shapes: Vec<Shape>, blend: Option<BlendMode>) -> Result<&Self> {
+    let source = OverlaySource::Shapes {
+      shapes: Arc::new(prepare_shapes(shapes)?),
+      rendered: Default::default(),
+    };
+    self.push_composite(
+      source,
+      Some(CompositeOptions {
+        left: Some(0),
+        top: Some(0),
+        blend,
+        ..Default::default()
+      }),
+    )?;
+    Ok(self)
+  }
+
+  #[napi]
+  /// Mask this image with `image`'s alpha or luminance (see `MaskOptions`): the mask is stretched
+  /// over the image after the staged resize/crop and multiplies (or replaces) its alpha. The image
+  /// is promoted to an alpha-capable type at its own bit depth. `image` accepts the same sources as
+  /// `composite()`.
+  pub fn mask(
+    &mut self,
+    image: Either3<Uint8Array, ClassInstance<'_, Transformer>, OverlayInput>,
+    options: Option<MaskOptions>,
+  ) -> Result<&Self> {
+    let options = options.unwrap_or_default();
+    self.image_transform_args.masks.push(MaskItem {
+      source: MaskSource::Image(OverlaySource::from_js(image)?),
+      channel: options.channel,
+      invert: options.invert.unwrap_or(false),
+      resize_to_fit: options.resize_to_fit.unwrap_or(true),
+      mode: options.mode.unwrap_or_default(),
+    });
+    Ok(self)
+  }
+
+  #[napi]
+  /// Round the corners of this image by `radius` pixels, making them transparent. Applied after
+  /// the staged resize/crop, like `mask()`.
+  pub fn round_corners(&mut self, radius: f64) -> Result<&Self> {
+    if !radius.is_finite() || radius < 0.0 {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!("roundCorners: radius must be a non-negative number, got {radius}"),
+      ));
+    }
+    self.push_mask_preset(Some(radius as f32));
+    Ok(self)
+  }
+
+  #[napi]
+  /// Cut this image to the largest centered circle, making everything outside it transparent,
+  /// e.g. for avatars. Applied after the staged resize/crop, like `mask()`.
+  pub fn circle(&mut self) -> &Self {
+    self.push_mask_preset(None);
+    self
+  }
+
+  #[napi]
+  /// Reduce this image to a single `channel`, as an `L8` image for 8-bit sources and `L16`
+  /// otherwise. Applied after `mask()` and before `joinChannel()`, so a single channel can be
+  /// given a new alpha.
+  pub fn extract_channel(&mut self, channel: Channel) -> &Self {
+    self.image_transform_args.extract_channel = Some(channel);
+    self
+  }
+
+  #[napi]
+  /// Append one channel per image (its luminance; alpha is ignored) to this image, e.g. to attach
+  /// an alpha channel from a separate grayscale image. Joined channels must have the same size as
+  /// this image after the staged resize/crop, and the result can have at most 4 channels:
+  /// gray + 1 gives gray-alpha, gray + 2 RGB and RGB + 1 RGBA. Bit depth follows this image, with
+  /// a float image joined into fewer than 3 channels stored as 16-bit.
+  pub fn join_channel(
+    &mut self,
+    images: Vec<Either3<Uint8Array, ClassInstance<'_, Transformer>, OverlayInput>>,
+  ) -> Result<&Self> {
+    for image in images {
+      let source = OverlaySource::from_js(image)?;
+      self.image_transform_args.join_channels.push(source);
+    }
+    Ok(self)
+  }
+
+  #[napi]
+  /// Draw a drop shadow of this image's shape (its alpha) behind it, growing the canvas to fit.
+  /// Applied after the staged resize/crop and masks, so `circle().shadow()` shadows the circle.
+  /// The result is RGBA at the image's bit depth. See `ShadowOptions`.
+  pub fn shadow(&mut self, options: Option<ShadowOptions>) -> Result<&Self> {
+    let effect = AlphaEffect::shadow(options.unwrap_or_default())?;
+    self.image_transform_args.effects.push(effect);
+    Ok(self)
+  }
+
+  #[napi]
+  /// Draw an outer glow around this image's shape, like an unshifted `shadow()`. See
+  /// `GlowOptions`.
+  pub fn glow(&mut self, options: Option<GlowOptions>) -> Result<&Self> {
+    let effect = AlphaEffect::glow(options.unwrap_or_default())?;
+    self.image_transform_args.effects.push(effect);
+    Ok(self)
+  }
+
+  #[napi]
+  /// Stroke the outline of this image's shape (its alpha) with a solid border of `width` pixels
+  /// drawn outside it, growing the canvas to fit. Corners of the shape are rounded by the stroke.
+  pub fn border(&mut self, options: BorderOptions) -> Result<&Self> {
+    let effect = AlphaEffect::border(options)?;
+    self.image_transform_args.effects.push(effect);
+    Ok(self)
+  }
+
+  fn push_tone(&mut self, adjustment: ToneAdjustment) -> Result<&Self> {
+    self.image_transform_args.tone.push(adjustment);
+    Ok(self)
+  }
+
+  fn push_mask_preset(&mut self, radius: Option<f32>) {
+    self.image_transform_args.masks.push(MaskItem {
+      source: MaskSource::Preset {
+        radius,
+        rendered: Default::default(),
+      },
+      channel: Some(MaskChannel::Alpha),
+      invert: false,
+      resize_to_fit: true,
+      mode: MaskMode::Multiply,
+    });
+  }
+
+  #[napi]
+  /// Render text into a new transparent image sized to fit it. See `TextOptions`.
+  pub fn from_text(text: TextOptions) -> Result<Transformer> {
+    Ok(transformer_from_rgba8(
+      render_text(&text)?,
+      DetectedFormat::Standard(ImageFormat::Png),
+    ))
+  }
+
+  fn push_composite(
+    &mut self,
+    source: OverlaySource,
+    options: Option<CompositeOptions>,
+  ) -> Result<()> {
     let o = options.unwrap_or_default();
     let has_offset = match (o.left, o.top) {
       (Some(_), Some(_)) => true,
@@ -1424,7 +3172,7 @@ impl Transformer {
       1.0
     };
     self.image_transform_args.overlay.push(CompositeItem {
-      buffer: Arc::new(on_top),
+      source,
       left: o.left.unwrap_or(0),
       top: o.top.unwrap_or(0),
       has_offset,
@@ -1434,7 +3182,7 @@ impl Transformer {
       opacity,
       simple_overlay: false, // composite() is always depth-aware (see compute() dispatch)
     });
-    Ok(self)
+    Ok(())
   }
 
   #[napi]
@@ -1777,6 +3525,150 @@ impl Transformer {
     let output = encoder.compute()?;
     output.into_buffer_slice(env)
   }
+
+  #[napi]
+  /// Encode as JPEG, WebP or AVIF at the quality a binary search picks for the targets: the
+  /// lowest quality meeting `minSsim`/`maxDeltaE` against the image as staged, capped by
+  /// `maxBytes`. Each candidate is decoded and scored, so this costs several encodes.
+  pub fn encode_to_target(
+    &self,
+    options: EncodeTargetOptions,
+    signal: Option<AbortSignal>,
+  ) -> Result<AsyncTask<EncodeTargetTask>> {
+    Ok(AsyncTask::with_optional_signal(
+      EncodeTargetTask {
+        image: self.dynamic_image.clone(),
+        image_transform_args: self.image_transform_args.clone(),
+        search: TargetSearch::new(options)?,
+      },
+      signal,
+    ))
+  }
+
+  #[napi]
+  /// Synchronous version of `encodeToTarget`.
+  pub fn encode_to_target_sync(
+    &self,
+    env: Env,
+    options: EncodeTargetOptions,
+  ) -> Result<EncodeTargetResult> {
+    let mut task = EncodeTargetTask {
+      image: self.dynamic_image.clone(),
+      image_transform_args: self.image_transform_args.clone(),
+      search: TargetSearch::new(options)?,
+    };
+    let output = task.compute()?;
+    task.resolve(env, output)
+  }
+
+  #[napi]
+  /// Encode to each candidate format in parallel from one render of the staged pipeline and
+  /// return the smallest result, e.g. to serve the best format an `Accept` header allows.
+  /// The libuv worker encodes candidates itself, helped by extra threads from a budget of one per
+  /// CPU that all concurrent calls share, so a busy pool is not oversubscribed; AVIF candidates
+  /// still use the encoder's own `threads` on top.
+  pub fn encode_auto(
+    &self,
+    options: Option<EncodeAutoOptions>,
+    signal: Option<AbortSignal>,
+  ) -> Result<AsyncTask<EncodeAutoTask>> {
+    let options = options.unwrap_or_default();
+    options.validate()?;
+    Ok(AsyncTask::with_optional_signal(
+      EncodeAutoTask {
+        image: self.dynamic_image.clone(),
+        image_transform_args: self.image_transform_args.clone(),
+        options,
+      },
+      signal,
+    ))
+  }
+
+  #[napi]
+  /// Synchronous version of `encodeAuto`.
+  pub fn encode_auto_sync(
+    &self,
+    env: Env,
+    options: Option<EncodeAutoOptions>,
+  ) -> Result<EncodeAutoResult> {
+    let options = options.unwrap_or_default();
+    options.validate()?;
+    let mut task = EncodeAutoTask {
+      image: self.dynamic_image.clone(),
+      image_transform_args: self.image_transform_args.clone(),
+      options,
+    };
+    let output = task.compute()?;
+    task.resolve(env, output)
+  }
+
+  #[napi]
+  /// Apply the staged pipeline once and encode the result to every entry of `outputs` in
+  /// parallel. The buffers come back in `outputs` order.
+  pub fn encode_many(
+    &self,
+    outputs: Vec<EncodeSpec>,
+    signal: Option<AbortSignal>,
+  ) -> Result<AsyncTask<EncodeManyTask>> {
+    Ok(AsyncTask::with_optional_signal(
+      EncodeManyTask::new(self, outputs)?,
+      signal,
+    ))
+  }
+
+  #[napi]
+  /// Synchronous version of `encodeMany`.
+  pub fn encode_many_sync(&self, env: Env, outputs: Vec<EncodeSpec>) -> Result<Vec<Buffer>> {
+    let mut task = EncodeManyTask::new(self, outputs)?;
+    let output = task.compute()?;
+    task.resolve(env, output)
+  }
+
+  #[napi]
+  /// Build a responsive image set (e.g. for `<picture>`/`srcset`) from one render of the staged
+  /// pipeline: every width is downsampled from the next-wider one, then every width x format is
+  /// encoded in parallel. Variants come back in `widths` order, then `formats` order.
+  pub fn generate_variants(
+    &self,
+    options: VariantsOptions,
+    signal: Option<AbortSignal>,
+  ) -> Result<AsyncTask<GenerateVariantsTask>> {
+    Ok(AsyncTask::with_optional_signal(
+      GenerateVariantsTask::new(self, options)?,
+      signal,
+    ))
+  }
+
+  #[napi]
+  /// Synchronous version of `generateVariants`.
+  pub fn generate_variants_sync(&self, env: Env, options: VariantsOptions) -> Result<Vec<Variant>> {
+    let mut task = GenerateVariantsTask::new(self, options)?;
+    let output = task.compute()?;
+    task.resolve(env, output)
+  }
+
+  #[napi]
+  /// Cut the staged pipeline's output into a DeepZoom, IIIF, XYZ or Zoomify tile pyramid, each
+  /// level downsampled from the previous one, and return the tiles plus the layout's manifest (or
+  /// write them all to `directory`).
+  pub fn tile(
+    &self,
+    options: Option<TileOptions>,
+    signal: Option<AbortSignal>,
+  ) -> Result<AsyncTask<TileTask>> {
+    Ok(AsyncTask::with_optional_signal(
+      TileTask::new(self, options)?,
+      signal,
+    ))
+  }
+
+  #[napi]
+  /// Synchronous version of `tile`.
+  pub fn tile_sync(&self, env: Env, options: Option<TileOptions>) -> Result<TileSet> {
+    let mut task = TileTask::new(self, options)?;
+    let output = task.compute()?;
+    task.resolve(env, output)
+  }
 }
 /// Adjust the contrast of `image`'s color/luma channels by `contrast`, preserving bit
 /// depth and the alpha channel.
@@ -1928,6 +3820,105 @@ fn apply_huerotate(image: &mut DynamicImage, degrees: i32) {
   }
 }
 
+/// Parse a CSS3 color (e.g. `rgba(255, 255, 255, .8)`) for a letterbox/canvas background.
+/// `None` is opaque black.
+pub(crate) fn parse_background(background: Option<&str>) -> Result<svgtypes::Color> {
+  background
+    .map(|bg| bg.parse::<svgtypes::Color>())
+    .transpose()
+    .map_err(|err| Error::from_reason(format!("{err}")))
+    .map(|bg| bg.unwrap_or_else(svgtypes::Color::black))
+}
+
+/// Place `image` on a `width`x`height` canvas filled with `background`, anchored by `position`.
+///
+/// Depth-aware: the canvas keeps the image's bit depth (8/16-bit or f32). It only gains an alpha
+/// channel when the background is translucent, and only becomes RGB when a grayscale image gets
+/// a colored background, so an opaque letterbox of an opaque image stays opaque.
+pub(crate) fn embed(
+  image: &DynamicImage,
+  width: u32,
+  height: u32,
+  position: Gravity,
+  background: svgtypes::Color,
+) -> DynamicImage {
+  let color = image.color();
+  let alpha = color.has_alpha() || background.alpha < u8::MAX;
+  let rgb =
+    color.has_color() || background.red != background.green || background.green != background.blue;
+  let (fx, fy) = gravity_anchor(position);
+  let x = ((width as f64 - image.width() as f64) * fx).round() as i64;
+  let y = ((height as f64 - image.height() as f64) * fy).round() as i64;
+  let [r, g, b, a] = [
+    background.red,
+    background.green,
+    background.blue,
+    background.alpha,
+  ];
+  let wide = |v: u8| v as u16 * 257;
+  let float = |v: u8| v as f32 / 255.0;
+  let mut canvas = match (color.bytes_per_pixel() / color.channel_count(), rgb, alpha) {
+    (1, false, false) => {
+      DynamicImage::ImageLuma8(ImageBuffer::from_pixel(width, height, image::Luma([r])))
+    }
+    (1, false, true) => {
+      DynamicImage::ImageLumaA8(ImageBuffer::from_pixel(width, height, image::LumaA([r, a])))
+    }
+    (1, true, false) => DynamicImage::ImageRgb8(ImageBuffer::from_pixel(
+      width,
+      height,
+      image::Rgb([r, g, b]),
+    )),
+    (1, true, true) => {
+      DynamicImage::ImageRgba8(ImageBuffer::from_pixel(width, height, Rgba([r, g, b, a])))
+    }
+    (2, false, false) => DynamicImage::ImageLuma16(ImageBuffer::from_pixel(
+      width,
+      height,
+      image::Luma([wide(r)]),
+    )),
+    (2, false, true) => DynamicImage::ImageLumaA16(ImageBuffer::from_pixel(
+      width,
+      height,
+      image::LumaA([wide(r), wide(a)]),
+    )),
+    (2, true, false) => DynamicImage::ImageRgb16(ImageBuffer::from_pixel(
+      width,
+      height,
+      image::Rgb([wide(r), wide(g), wide(b)]),
+    )),
+    (2, true, true) => DynamicImage::ImageRgba16(ImageBuffer::from_pixel(
+      width,
+      height,
+      Rgba([wide(r), wide(g), wide(b), wide(a)]),
+    )),
+    (_, _, false) => DynamicImage::ImageRgb32F(ImageBuffer::from_pixel(
+      width,
+      height,
+      image::Rgb([float(r), float(g), float(b)]),
+    )),
+    (_, _, true) => DynamicImage::ImageRgba32F(ImageBuffer::from_pixel(
+      width,
+      height,
+      Rgba([float(r), float(g), float(b), float(a)]),
+    )),
+  };
+  let placed = match canvas.color() {
+    ColorType::L8 => DynamicImage::ImageLuma8(image.to_luma8()),
+    ColorType::La8 => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+    ColorType::Rgb8 => DynamicImage::ImageRgb8(image.to_rgb8()),
+    ColorType::Rgba8 => DynamicImage::ImageRgba8(image.to_rgba8()),
+    ColorType::L16 => DynamicImage::ImageLuma16(image.to_luma16()),
+    ColorType::La16 => DynamicImage::ImageLumaA16(image.to_luma_alpha16()),
+    ColorType::Rgb16 => DynamicImage::ImageRgb16(image.to_rgb16()),
+    ColorType::Rgba16 => DynamicImage::ImageRgba16(image.to_rgba16()),
+    ColorType::Rgb32F => DynamicImage::ImageRgb32F(image.to_rgb32f()),
+    _ => DynamicImage::ImageRgba32F(image.to_rgba32f()),
+  };
+  image::imageops::replace(&mut canvas, &placed, x, y);
+  canvas
+}
+
 /// Multiply the alpha channel by `factor`, preserving the source bit depth.
 ///
 /// `factor` is clamped to `0.0..=1.0`; a non-finite `factor` (NaN / ∞) is treated
@@ -1974,12 +3965,245 @@ fn apply_opacity(image: &mut DynamicImage, factor: f32) {
     }
     _ => {
       let mut buf = image.to_rgba8();
-      for pixel in buf.pixels_mut() {
-        pixel[3] = (pixel[3] as f32 * factor).round().clamp(0.0, 255.0) as u8;
+      for pixel in buf.pixels_mut() {
+        pixel[3] = (pixel[3] as f32 * factor).round().clamp(0.0, 255.0) as u8;
+      }
+      *image = DynamicImage::ImageRgba8(buf);
+    }
+  }
+}
+
+/// Combine `mask`'s coverage into `image`'s alpha at the image's depth, promoting it to RGBA like
+/// `apply_opacity`. Coverage is normalized to `0.0..=1.0` whatever the mask's depth.
+fn apply_mask(image: &mut DynamicImage, mask: &MaskItem) -> Result<()> {
+  let (width, height) = (image.width(), image.height());
+  let pixels = match &mask.source {
+    MaskSource::Image(source) => source.pixels(width, height)?,
+    MaskSource::Preset { radius, rendered } => cached_raster(rendered, (width, height), || {
+      rasterize_mask_preset(width, height, *radius)
+    })?,
+  };
+  let stretched;
+  let pixels: &DynamicImage =
+    if mask.resize_to_fit && (pixels.width(), pixels.height()) != (width, height) {
+      stretched = pixels.resize_exact(width, height, FilterType::Triangle);
+      &stretched
+    } else {
+      &pixels
+    };
+  let channel = mask.channel.unwrap_or(if pixels.color().has_alpha() {
+    MaskChannel::Alpha
+  } else {
+    MaskChannel::Luminance
+  });
+  let values: Vec<f32> = match channel {
+    MaskChannel::Alpha => pixels.to_luma_alpha32f().pixels().map(|p| p[1]).collect(),
+    MaskChannel::Luminance => pixels.to_luma32f().into_raw(),
+  };
+  let mask_width = pixels.width();
+  // Pixels the mask does not reach (a smaller, unstretched mask) are hidden.
+  let coverage = |x: u32, y: u32| -> f32 {
+    if x >= mask_width || y >= pixels.height() {
+      return 0.0;
+    }
+    let value = norm_f32(values[(y * mask_width + x) as usize]);
+    if mask.invert { 1.0 - value } else { value }
+  };
+  let combine = |alpha: f32, coverage: f32| match mask.mode {
+    MaskMode::Multiply => alpha * coverage,
+    MaskMode::Replace => coverage,
+  };
+  match image.color() {
+    ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+      let mut buf = image.to_rgba16();
+      for (x, y, pixel) in buf.enumerate_pixels_mut() {
+        let alpha = combine(pixel[3] as f32 / 65535.0, coverage(x, y));
+        pixel[3] = (alpha * 65535.0).round().clamp(0.0, 65535.0) as u16;
+      }
+      *image = DynamicImage::ImageRgba16(buf);
+    }
+    ColorType::Rgb32F | ColorType::Rgba32F => {
+      let mut buf = image.to_rgba32f();
+      for (x, y, pixel) in buf.enumerate_pixels_mut() {
+        // Normalize the source alpha first, as `apply_opacity` does.
+        pixel[3] = combine(norm_f32(pixel[3]), coverage(x, y));
+      }
+      *image = DynamicImage::ImageRgba32F(buf);
+    }
+    _ => {
+      let mut buf = image.to_rgba8();
+      for (x, y, pixel) in buf.enumerate_pixels_mut() {
+        let alpha = combine(pixel[3] as f32 / 255.0, coverage(x, y));
+        pixel[3] = (alpha * 255.0).round().clamp(0.0, 255.0) as u8;
       }
       *image = DynamicImage::ImageRgba8(buf);
     }
   }
+  Ok(())
+}
+
+/// Draw `effect`'s layer behind `image` on a transparent canvas grown to fit both, compositing at
+/// the image's depth (RGBA8/RGBA16/RGBA32F). Metadata (`!for_encode`) only needs the canvas size
+/// and color type, so it skips rendering.
+fn apply_alpha_effect(
+  image: &mut DynamicImage,
+  effect: &AlphaEffect,
+  for_encode: bool,
+) -> Result<()> {
+  let extent = effect.extent() as i64;
+  let (width, height) = (image.width() as i64, image.height() as i64);
+  let (layer_x, layer_y) = (effect.offset_x - extent, effect.offset_y - extent);
+  let (left, top) = (layer_x.min(0), layer_y.min(0));
+  let right = (layer_x + width + 2 * extent).max(width);
+  let bottom = (layer_y + height + 2 * extent).max(height);
+  let (canvas_width, canvas_height) = (right - left, bottom - top);
+  if canvas_width > u32::MAX as i64
+    || canvas_height > u32::MAX as i64
+    || (canvas_width as u64) * (canvas_height as u64) > MAX_SVG_PIXELS
+  {
+    return Err(Error::new(
+      Status::InvalidArg,
+      format!("shadow/glow/border: canvas size {canvas_width}x{canvas_height} is out of range"),
+    ));
+  }
+  let color = match image.color() {
+    ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => ColorType::Rgba16,
+    ColorType::Rgb32F | ColorType::Rgba32F => ColorType::Rgba32F,
+    _ => ColorType::Rgba8,
+  };
+  let mut canvas = DynamicImage::new(canvas_width as u32, canvas_height as u32, color);
+  if for_encode {
+    let layer = DynamicImage::ImageRgba32F(effect.layer(image));
+    let over = BlendMode::Over;
+    composite_step(
+      &mut canvas,
+      &layer,
+      layer_x - left,
+      layer_y - top,
+      over,
+      1.0,
+      false,
+    );
+    composite_step(&mut canvas, image, -left, -top, over, 1.0, false);
+  }
+  *image = canvas;
+  Ok(())
+}
+
+/// Append the luminance of each of `sources` to `image` as a new channel. The channels are joined
+/// as normalized f32 planes, which hold 8- and 16-bit samples exactly. Metadata (`!for_encode`)
+/// only needs the resulting color type, so it joins empty planes instead of decoding the sources.
+fn join_channels(
+  image: &mut DynamicImage,
+  sources: &[OverlaySource],
+  for_encode: bool,
+) -> Result<()> {
+  let color = image.color();
+  let channels = color.channel_count() as usize;
+  let total = channels + sources.len();
+  if total > 4 {
+    return Err(Error::new(
+      Status::InvalidArg,
+      format!(
+        "joinChannel: an image has at most 4 channels, got {channels} + {} joined",
+        sources.len()
+      ),
+    ));
+  }
+  let (width, height) = (image.width(), image.height());
+  let base = image.to_rgba32f();
+  let mut planes: Vec<Vec<f32>> = (0..channels)
+    .map(|channel| {
+      // Gray-alpha keeps its alpha in the fourth RGBA slot.
+      let index = if channels == 2 && channel == 1 {
+        3
+      } else {
+        channel
+      };
+      base.pixels().map(|pixel| pixel[index]).collect()
+    })
+    .collect();
+  for source in sources {
+    if !for_encode {
+      planes.push(vec![0.0; width as usize * height as usize]);
+      continue;
+    }
+    let pixels = source.pixels(width, height)?;
+    if (pixels.width(), pixels.height()) != (width, height) {
+      return Err(Error::new(
+        Status::InvalidArg,
+        format!(
+          "joinChannel: a {}x{} channel cannot be joined to a {width}x{height} image",
+          pixels.width(),
+          pixels.height()
+        ),
+      ));
+    }
+    planes.push(pixels.to_luma32f().into_raw());
+  }
+  let samples = |scale: f32| -> Vec<f32> {
+    (0..width as usize * height as usize)
+      .flat_map(|i| planes.iter().map(move |plane| plane[i] * scale))
+      .collect()
+  };
+  let to_u8 = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+  let to_u16 = |v: f32| v.round().clamp(0.0, 65535.0) as u16;
+  let joined = match color {
+    ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8 => {
+      let raw = samples(255.0).into_iter().map(to_u8).collect::<Vec<_>>();
+      match total {
+        2 => ImageBuffer::from_raw(width, height, raw).map(DynamicImage::ImageLumaA8),
+        3 => ImageBuffer::from_raw(width, height, raw).map(DynamicImage::ImageRgb8),
+        _ => ImageBuffer::from_raw(width, height, raw).map(DynamicImage::ImageRgba8),
+      }
+    }
+    ColorType::Rgb32F | ColorType::Rgba32F if total >= 3 => {
+      let raw = samples(1.0);
+      match total {
+        3 => ImageBuffer::from_raw(width, height, raw).map(DynamicImage::ImageRgb32F),
+        _ => ImageBuffer::from_raw(width, height, raw).map(DynamicImage::ImageRgba32F),
+      }
+    }
+    _ => {
+      let raw = samples(65535.0).into_iter().map(to_u16).collect::<Vec<_>>();
+      match total {
+        2 => ImageBuffer::from_raw(width, height, raw).map(DynamicImage::ImageLumaA16),
+        3 => ImageBuffer::from_raw(width, height, raw).map(DynamicImage::ImageRgb16),
+        _ => ImageBuffer::from_raw(width, height, raw).map(DynamicImage::ImageRgba16),
+      }
+    }
+  };
+  *image = joined.ok_or_else(|| {
+    Error::from_reason("joinChannel: joined pixel buffer does not match its dimensions")
+  })?;
+  Ok(())
+}
+
+/// `channel` of `image` as a grayscale image: `L8` for 8-bit sources, `L16` for 16-bit and float.
+fn extract_channel(image: &DynamicImage, channel: Channel) -> DynamicImage {
+  let eight_bit = matches!(
+    image.color(),
+    ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8
+  );
+  let index = match channel {
+    Channel::Red => 0,
+    Channel::Green => 1,
+    Channel::Blue => 2,
+    Channel::Alpha => 3,
+    Channel::Luminance if eight_bit => return DynamicImage::ImageLuma8(image.to_luma8()),
+    Channel::Luminance => return DynamicImage::ImageLuma16(image.to_luma16()),
+  };
+  if eight_bit {
+    let rgba = image.to_rgba8();
+    DynamicImage::ImageLuma8(ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+      image::Luma([rgba.get_pixel(x, y)[index]])
+    }))
+  } else {
+    let rgba = image.to_rgba16();
+    DynamicImage::ImageLuma16(ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+      image::Luma([rgba.get_pixel(x, y)[index]])
+    }))
+  }
 }
 
 /// Resolve the top-left placement of an overlay (sharp semantics). When an explicit offset was
@@ -2672,11 +4896,20 @@ mod tests {
   }
 
   use super::{
-    BlendMode, Gravity, ImageTransformArgs, apply_composite, apply_contrast, apply_huerotate,
-    apply_opacity, apply_transforms, composite_into_u8, composite_into_u16, composite_step,
-    finalize_composite, for_each_placement, resolve_position,
+    BlendMode, Channel, Gravity, ImageTransformArgs, MaskChannel, MaskItem, MaskMode, MaskSource,
+    OverlayPixels, OverlaySource, apply_alpha_effect, apply_composite, apply_contrast,
+    apply_huerotate, apply_mask, apply_opacity, apply_transforms, composite_into_u8,
+    composite_into_u16, composite_step, embed, extract_channel, finalize_composite,
+    for_each_placement, join_channels, load_svg, pin_resize_to_source, resolve_position,
+    shrink_target, svg_overlay_size,
   };
+  use crate::effects::{AlphaEffect, ShadowOptions};
+  use crate::fast_resize::{FastResizeOptions, ResizeFit};
+  use crate::tone::ToneAdjustment;
+  use crate::transformer::ResizeOptions;
   use image::{ColorType, DynamicImage, ImageBuffer, RgbImage, Rgba, RgbaImage};
+  use napi::bindgen_prelude::Either;
+  use std::sync::Arc;
 
   #[test]
   fn huerotate_preserves_16bit_color_through_pipeline() {
@@ -3754,4 +5987,621 @@ mod tests {
     ys.dedup();
     assert_eq!(ys, vec![-2, 0, 2], "tile y origins must be phased by y=0");
   }
+
+  /// A 300x100 image whose left, middle and right thirds are red, green and blue.
+  fn three_bands() -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(300, 100, |x, _| match x / 100 {
+      0 => image::Rgb([255, 0, 0]),
+      1 => image::Rgb([0, 255, 0]),
+      _ => image::Rgb([0, 0, 255]),
+    }))
+  }
+
+  #[test]
+  fn cover_resize_position_picks_the_kept_region() {
+    let mut img = three_bands();
+    let args = ImageTransformArgs {
+      resize: Some(ResizeOptions {
+        width: 10,
+        height: Some(10),
+        filter: None,
+        fit: Some(ResizeFit::Cover),
+        position: Some(Gravity::East),
+        strategy: None,
+        background: None,
+        without_enlargement: None,
+        without_reduction: None,
+        linear_light: None,
+      }),
+      ..Default::default()
+    };
+    let crop_box = apply_transforms(&mut img, &args, None, true)
+      .unwrap()
+      .unwrap();
+    assert_eq!(
+      (crop_box.left, crop_box.width, crop_box.height),
+      (200, 100, 100)
+    );
+    assert_eq!((img.width(), img.height()), (10, 10));
+    assert_eq!(img.to_rgb8().get_pixel(5, 5).0, [0, 0, 255]);
+
+    // fast_resize used to pass pixel coordinates as the 0..1 centering fraction, which clamped to
+    // the bottom-right corner; the default must keep the middle band.
+    let mut img = three_bands();
+    let args = ImageTransformArgs {
+      fast_resize: Some(FastResizeOptions {
+        width: 10,
+        height: Some(10),
+        filter: None,
+        fit: Some(ResizeFit::Cover),
+        position: None,
+        strategy: None,
+        background: None,
+        without_enlargement: None,
+        without_reduction: None,
+        linear_light: None,
+      }),
+      ..Default::default()
+    };
+    let crop_box = apply_transforms(&mut img, &args, None, true)
+      .unwrap()
+      .unwrap();
+    assert_eq!(crop_box.left, 100);
+    assert_eq!(img.to_rgb8().get_pixel(5, 5).0, [0, 255, 0]);
+  }
+
+  fn resize_options(width: u32, height: u32, fit: ResizeFit) -> ResizeOptions {
+    ResizeOptions {
+      width,
+      height: Some(height),
+      filter: None,
+      fit: Some(fit),
+      position: None,
+      strategy: None,
+      background: None,
+      without_enlargement: None,
+      without_reduction: None,
+      linear_light: None,
+    }
+  }
+
+  #[test]
+  fn contain_letterboxes_into_the_exact_box() {
+    let mut img = three_bands();
+    let args = ImageTransformArgs {
+      resize: Some(ResizeOptions {
+        background: Some("#fff".to_owned()),
+        position: Some(Gravity::North),
+        ..resize_options(60, 60, ResizeFit::Contain)
+      }),
+      ..Default::default()
+    };
+    apply_transforms(&mut img, &args, None, true).unwrap();
+    assert_eq!((img.width(), img.height()), (60, 60));
+    assert_eq!(img.color(), ColorType::Rgb8, "opaque letterbox keeps RGB");
+    let rgb = img.to_rgb8();
+    assert_eq!(
+      rgb.get_pixel(5, 5).0,
+      [255, 0, 0],
+      "image placed at the top"
+    );
+    assert_eq!(rgb.get_pixel(30, 50).0, [255, 255, 255], "white bars below");
+  }
+
+  #[test]
+  fn outside_covers_both_dimensions_without_cropping() {
+    let mut img = three_bands();
+    let args = ImageTransformArgs {
+      resize: Some(resize_options(60, 60, ResizeFit::Outside)),
+      ..Default::default()
+    };
+    apply_transforms(&mut img, &args, None, true).unwrap();
+    assert_eq!((img.width(), img.height()), (180, 60));
+
+    let mut img = three_bands();
+    let args = ImageTransformArgs {
+      fast_resize: Some(fast_resize_options(60, 60, ResizeFit::Outside)),
+      ..Default::default()
+    };
+    apply_transforms(&mut img, &args, None, true).unwrap();
+    assert_eq!((img.width(), img.height()), (180, 60));
+  }
+
+  #[test]
+  fn without_enlargement_and_reduction_skip_the_resize() {
+    for (width, flags) in [(600, (true, false)), (30, (false, true))] {
+      let mut img = three_bands();
+      let args = ImageTransformArgs {
+        resize: Some(ResizeOptions {
+          without_enlargement: Some(flags.0),
+          without_reduction: Some(flags.1),
+          ..resize_options(width, 200, ResizeFit::Fill)
+        }),
+        ..Default::default()
+      };
+      apply_transforms(&mut img, &args, None, true).unwrap();
+      assert_eq!((img.width(), img.height()), (300, 100));
+    }
+    // A shrink is still allowed under `withoutEnlargement`.
+    let mut img = three_bands();
+    let args = ImageTransformArgs {
+      resize: Some(ResizeOptions {
+        without_enlargement: Some(true),
+        ..resize_options(30, 10, ResizeFit::Fill)
+      }),
+      ..Default::default()
+    };
+    apply_transforms(&mut img, &args, None, true).unwrap();
+    assert_eq!((img.width(), img.height()), (30, 10));
+  }
+
+  #[test]
+  fn embed_keeps_depth_and_only_adds_alpha_for_translucent_backgrounds() {
+    let img = DynamicImage::ImageLuma16(ImageBuffer::from_pixel(2, 2, image::Luma([1234u16])));
+    let opaque = embed(&img, 4, 2, Gravity::West, svgtypes::Color::black());
+    assert_eq!(opaque.color(), ColorType::L16);
+    assert_eq!(opaque.to_luma16().get_pixel(1, 0).0, [1234]);
+    assert_eq!(opaque.to_luma16().get_pixel(3, 0).0, [0]);
+
+    let clear = embed(
+      &img,
+      4,
+      2,
+      Gravity::East,
+      svgtypes::Color::new_rgba(0, 0, 0, 0),
+    );
+    assert_eq!(clear.color(), ColorType::La16);
+    assert_eq!(clear.to_luma_alpha16().get_pixel(0, 0).0, [0, 0]);
+    assert_eq!(clear.to_luma_alpha16().get_pixel(3, 1).0, [1234, 65535]);
+
+    let red = embed(
+      &img,
+      4,
+      2,
+      Gravity::Center,
+      svgtypes::Color::new_rgb(255, 0, 0),
+    );
+    assert_eq!(red.color(), ColorType::Rgb16);
+    assert_eq!(red.to_rgb16().get_pixel(0, 0).0, [65535, 0, 0]);
+  }
+
+  fn fast_resize_options(width: u32, height: u32, fit: ResizeFit) -> FastResizeOptions {
+    FastResizeOptions {
+      width,
+      height: Some(height),
+      filter: None,
+      fit: Some(fit),
+      position: None,
+      strategy: None,
+      background: None,
+      without_enlargement: None,
+      without_reduction: None,
+      linear_light: None,
+    }
+  }
+
+  #[test]
+  fn fast_resize_preserves_color_type_and_depth() {
+    // fastResize used to go through `to_rgba8()`, crushing 16-bit/float input to 8-bit and giving
+    // opaque RGB an alpha channel.
+    let sources = [
+      DynamicImage::ImageRgb8(RgbImage::from_pixel(8, 8, image::Rgb([10, 20, 30]))),
+      DynamicImage::ImageLuma16(ImageBuffer::from_pixel(8, 8, image::Luma([40000u16]))),
+      DynamicImage::ImageRgb16(ImageBuffer::from_pixel(
+        8,
+        8,
+        image::Rgb([40000u16, 20001, 3]),
+      )),
+      DynamicImage::ImageRgba16(ImageBuffer::from_pixel(
+        8,
+        8,
+        Rgba([1000u16, 2000, 3000, 65535]),
+      )),
+      DynamicImage::ImageRgb32F(ImageBuffer::from_pixel(
+        8,
+        8,
+        image::Rgb([2.5f32, 0.25, 0.0]),
+      )),
+    ];
+    for source in sources {
+      let mut img = source.clone();
+      let args = ImageTransformArgs {
+        fast_resize: Some(fast_resize_options(4, 4, ResizeFit::Fill)),
+        ..Default::default()
+      };
+      apply_transforms(&mut img, &args, None, true).unwrap();
+      assert_eq!(
+        img.color(),
+        source.color(),
+        "fastResize must keep the color type"
+      );
+      assert_eq!((img.width(), img.height()), (4, 4));
+      assert_eq!(
+        &img.as_bytes()[..source.color().bytes_per_pixel() as usize],
+        &source.as_bytes()[..source.color().bytes_per_pixel() as usize],
+        "a flat image must resample to the same value at native depth"
+      );
+    }
+  }
+
+  #[test]
+  fn linear_light_downscale_keeps_checkerboard_brightness() {
+    // A 1px black/white checkerboard averages to 50% LINEAR light, i.e. sRGB ~188. Filtering the
+    // gamma-encoded values instead gives ~128, visibly darker.
+    let checker = DynamicImage::ImageRgb8(RgbImage::from_fn(16, 16, |x, y| {
+      if (x + y) % 2 == 0 {
+        image::Rgb([255, 255, 255])
+      } else {
+        image::Rgb([0, 0, 0])
+      }
+    }));
+    let mut gamma = checker.clone();
+    let args = ImageTransformArgs {
+      resize: Some(resize_options(4, 4, ResizeFit::Fill)),
+      ..Default::default()
+    };
+    apply_transforms(&mut gamma, &args, None, true).unwrap();
+    let gamma = gamma.to_rgb8().get_pixel(1, 1).0[0];
+    assert!(
+      (120..=136).contains(&gamma),
+      "gamma-space average, got {gamma}"
+    );
+
+    let mut linear = checker.clone();
+    let args = ImageTransformArgs {
+      resize: Some(ResizeOptions {
+        linear_light: Some(true),
+        ..resize_options(4, 4, ResizeFit::Fill)
+      }),
+      ..Default::default()
+    };
+    apply_transforms(&mut linear, &args, None, true).unwrap();
+    assert_eq!(linear.color(), ColorType::Rgb8);
+    let value = linear.to_rgb8().get_pixel(1, 1).0[0];
+    assert!(
+      (180..=196).contains(&value),
+      "linear-light average, got {value}"
+    );
+
+    let mut fast = checker;
+    let args = ImageTransformArgs {
+      fast_resize: Some(FastResizeOptions {
+        linear_light: Some(true),
+        ..fast_resize_options(4, 4, ResizeFit::Fill)
+      }),
+      ..Default::default()
+    };
+    apply_transforms(&mut fast, &args, None, true).unwrap();
+    assert_eq!(fast.color(), ColorType::Rgb8);
+    let value = fast.to_rgb8().get_pixel(1, 1).0[0];
+    assert!(
+      (180..=196).contains(&value),
+      "linear-light average, got {value}"
+    );
+  }
+
+  #[test]
+  fn linear_light_round_trips_16bit_and_alpha() {
+    let source = DynamicImage::ImageLumaA16(ImageBuffer::from_pixel(
+      4,
+      4,
+      image::LumaA([30000u16, 40000]),
+    ));
+    for fast in [false, true] {
+      let mut img = source.clone();
+      let args = if fast {
+        ImageTransformArgs {
+          fast_resize: Some(FastResizeOptions {
+            linear_light: Some(true),
+            ..fast_resize_options(2, 2, ResizeFit::Fill)
+          }),
+          ..Default::default()
+        }
+      } else {
+        ImageTransformArgs {
+          resize: Some(ResizeOptions {
+            linear_light: Some(true),
+            ..resize_options(2, 2, ResizeFit::Fill)
+          }),
+          ..Default::default()
+        }
+      };
+      apply_transforms(&mut img, &args, None, true).unwrap();
+      assert_eq!(img.color(), ColorType::La16);
+      let [luma, alpha] = img.to_luma_alpha16().get_pixel(0, 0).0;
+      assert!(
+        luma.abs_diff(30000) <= 2,
+        "16-bit luma survives, got {luma}"
+      );
+      assert!(
+        alpha.abs_diff(40000) <= 2,
+        "alpha is not linearized, got {alpha}"
+      );
+    }
+  }
+
+  #[test]
+  fn shrink_target_follows_the_first_staged_resize() {
+    let mut args = ImageTransformArgs {
+      resize: Some(ResizeOptions {
+        height: None,
+        ..resize_options(100, 0, ResizeFit::Inside)
+      }),
+      ..Default::default()
+    };
+    assert_eq!(shrink_target(&args, 800, 400), Some((100, 50)));
+    // Enlarging is never worth a reduced decode, and `withoutReduction` forbids shrinking.
+    args.resize.as_mut().unwrap().without_enlargement = Some(true);
+    assert_eq!(shrink_target(&args, 80, 40), None);
+    args.resize.as_mut().unwrap().without_reduction = Some(true);
+    assert_eq!(shrink_target(&args, 800, 400), None);
+    assert_eq!(
+      shrink_target(&ImageTransformArgs::default(), 800, 400),
+      None
+    );
+  }
+
+  #[test]
+  fn pinned_resize_yields_source_sized_output_from_a_shrunk_decode() {
+    let mut args = ImageTransformArgs {
+      resize: Some(resize_options(60, 60, ResizeFit::Inside)),
+      ..Default::default()
+    };
+    pin_resize_to_source(&mut args, 800, 400);
+    let resize = args.resize.as_ref().unwrap();
+    assert_eq!((resize.width, resize.height), (60, Some(30)));
+    assert!(matches!(resize.fit, Some(ResizeFit::Fill)));
+    // A 1/8 DCT decode rounds up to 101x50; the pinned resize still lands on 60x30.
+    let mut shrunk = DynamicImage::new_rgb8(101, 50);
+    apply_transforms(&mut shrunk, &args, None, true).unwrap();
+    assert_eq!((shrunk.width(), shrunk.height()), (60, 30));
+  }
+
+  #[test]
+  fn svg_overlay_size_follows_explicit_size_then_aspect_then_base() {
+    let tree = load_svg(Either::A(
+      r#"<svg xmlns="http://www.w3.org/2000/svg" width="40" height="20"/>"#,
+    ))
+    .unwrap();
+    let size = tree.size();
+    assert_eq!(svg_overlay_size(size, Some(8), Some(8), (0, 0)), (8, 8));
+    assert_eq!(svg_overlay_size(size, Some(8), None, (0, 0)), (8, 4));
+    assert_eq!(svg_overlay_size(size, None, Some(8), (0, 0)), (16, 8));
+    assert_eq!(svg_overlay_size(size, None, None, (100, 100)), (100, 50));
+  }
+
+  #[test]
+  fn svg_overlay_raster_is_cached_per_size() {
+    let source = OverlaySource::Svg {
+      tree: Arc::new(
+        load_svg(Either::A(
+          r#"<svg xmlns="http://www.w3.org/2000/svg" width="2" height="2"><rect width="2" height="2" fill="red"/></svg>"#,
+        ))
+        .unwrap(),
+      ),
+      width: None,
+      height: None,
+      rendered: Default::default(),
+    };
+    let clone = source.clone();
+    let OverlayPixels::Shared(first) = source.pixels(4, 4).unwrap() else {
+      panic!("SVG overlays are shared from the raster cache");
+    };
+    let OverlayPixels::Shared(again) = clone.pixels(4, 4).unwrap() else {
+      panic!("SVG overlays are shared from the raster cache");
+    };
+    assert!(
+      Arc::ptr_eq(&first, &again),
+      "clones share the cached raster"
+    );
+    assert_eq!(first.to_rgba8().get_pixel(3, 3).0, [255, 0, 0, 255]);
+    let resized = clone.pixels(8, 8).unwrap();
+    assert_eq!((resized.width(), resized.height()), (8, 8));
+  }
+
+  fn pixel_mask(image: DynamicImage) -> MaskItem {
+    MaskItem {
+      source: MaskSource::Image(OverlaySource::Pixels(Arc::new(image))),
+      channel: None,
+      invert: false,
+      resize_to_fit: true,
+      mode: MaskMode::Multiply,
+    }
+  }
+
+  #[test]
+  fn opaque_mask_defaults_to_luminance_and_multiplies_alpha() {
+    let mut img = DynamicImage::ImageRgba8(
+      RgbaImage::from_raw(2, 1, vec![10, 20, 30, 200, 10, 20, 30, 200]).unwrap(),
+    );
+    let mask = pixel_mask(DynamicImage::ImageLuma8(
+      ImageBuffer::from_raw(2, 1, vec![255u8, 0]).unwrap(),
+    ));
+    apply_mask(&mut img, &mask).unwrap();
+    let out = img.to_rgba8();
+    assert_eq!(
+      out.get_pixel(0, 0).0,
+      [10, 20, 30, 200],
+      "white keeps alpha"
+    );
+    assert_eq!(
+      out.get_pixel(1, 0).0,
+      [10, 20, 30, 0],
+      "black hides the pixel"
+    );
+  }
+
+  #[test]
+  fn replace_and_invert_keep_16bit_depth() {
+    let mut img =
+      DynamicImage::ImageRgb16(ImageBuffer::from_raw(1, 1, vec![40000u16, 20000, 10000]).unwrap());
+    let mut mask = pixel_mask(DynamicImage::ImageRgba16(
+      ImageBuffer::from_raw(1, 1, vec![0u16, 0, 0, 16384]).unwrap(),
+    ));
+    mask.invert = true;
+    mask.mode = MaskMode::Replace;
+    apply_mask(&mut img, &mask).unwrap();
+    assert_eq!(img.color(), ColorType::Rgba16, "promoted at 16-bit depth");
+    assert_eq!(
+      img.to_rgba16().get_pixel(0, 0).0,
+      [40000, 20000, 10000, 65535 - 16384]
+    );
+  }
+
+  #[test]
+  fn unstretched_mask_hides_the_image_outside_it() {
+    let mut img = DynamicImage::ImageRgb8(RgbImage::from_raw(2, 2, vec![100; 12]).unwrap());
+    let mut mask = pixel_mask(DynamicImage::ImageRgba8(
+      RgbaImage::from_raw(1, 1, vec![0, 0, 0, 255]).unwrap(),
+    ));
+    mask.resize_to_fit = false;
+    mask.channel = Some(MaskChannel::Alpha);
+    apply_mask(&mut img, &mask).unwrap();
+    let out = img.to_rgba8();
+    assert_eq!(out.get_pixel(0, 0).0[3], 255);
+    assert_eq!(out.get_pixel(1, 0).0[3], 0);
+    assert_eq!(out.get_pixel(1, 1).0[3], 0);
+  }
+
+  #[test]
+  fn circle_preset_fits_the_cropped_frame() {
+    let circle = MaskItem {
+      source: MaskSource::Preset {
+        radius: None,
+        rendered: Default::default(),
+      },
+      channel: Some(MaskChannel::Alpha),
+      invert: false,
+      resize_to_fit: true,
+      mode: MaskMode::Multiply,
+    };
+    let args = ImageTransformArgs {
+      crop: Some((0, 0, 10, 10)),
+      masks: vec![circle],
+      ..Default::default()
+    };
+    let source = DynamicImage::ImageRgb8(RgbImage::from_raw(20, 10, vec![255; 600]).unwrap());
+    let mut img = source.clone();
+    apply_transforms(&mut img, &args, None, true).unwrap();
+    let out = img.to_rgba8();
+    assert_eq!(out.dimensions(), (10, 10));
+    assert_eq!(out.get_pixel(0, 0).0[3], 0);
+    assert_eq!(out.get_pixel(5, 5).0, [255, 255, 255, 255]);
+
+    // Metadata reports the promoted color type without rendering the mask.
+    let mut meta = source;
+    apply_transforms(&mut meta, &args, None, false).unwrap();
+    assert_eq!(meta.color(), ColorType::Rgba8);
+  }
+
+  #[test]
+  fn sepia_promotes_gray_for_encode_and_metadata() {
+    let args = ImageTransformArgs {
+      tone: vec![ToneAdjustment::sepia()],
+      ..Default::default()
+    };
+    assert!(args.changes_dimensions_or_color());
+    let source = DynamicImage::ImageLuma16(ImageBuffer::from_pixel(2, 2, image::Luma([30000u16])));
+    let mut img = source.clone();
+    apply_transforms(&mut img, &args, None, true).unwrap();
+    let pixel = img.as_rgb16().expect("promoted to RGB16").get_pixel(1, 1).0;
+    assert!(pixel[0] > pixel[1] && pixel[1] > pixel[2]);
+
+    let mut meta = source;
+    apply_transforms(&mut meta, &args, None, false).unwrap();
+    assert_eq!(meta.color(), ColorType::Rgb16);
+  }
+
+  #[test]
+  fn extract_channel_keeps_the_source_depth() {
+    let img = DynamicImage::ImageRgba16(
+      ImageBuffer::from_raw(1, 1, vec![40000u16, 20000, 10000, 30000]).unwrap(),
+    );
+    let red = extract_channel(&img, Channel::Red);
+    assert_eq!(red.color(), ColorType::L16);
+    assert_eq!(red.to_luma16().into_raw(), vec![40000]);
+    assert_eq!(
+      extract_channel(&img, Channel::Alpha).to_luma16().into_raw(),
+      vec![30000]
+    );
+
+    let rgb = DynamicImage::ImageRgb8(RgbImage::from_raw(1, 1, vec![200, 100, 50]).unwrap());
+    let alpha = extract_channel(&rgb, Channel::Alpha);
+    assert_eq!(alpha.color(), ColorType::L8);
+    assert_eq!(
+      alpha.to_luma8().into_raw(),
+      vec![255],
+      "no alpha reads as opaque"
+    );
+    assert_eq!(
+      extract_channel(&rgb, Channel::Luminance).color(),
+      ColorType::L8
+    );
+  }
+
+  #[test]
+  fn join_channels_appends_luminance_at_the_base_depth() {
+    let alpha = OverlaySource::Pixels(Arc::new(DynamicImage::ImageLuma8(
+      ImageBuffer::from_raw(2, 1, vec![0u8, 128]).unwrap(),
+    )));
+    let mut rgb =
+      DynamicImage::ImageRgb8(RgbImage::from_raw(2, 1, vec![1, 2, 3, 4, 5, 6]).unwrap());
+    join_channels(&mut rgb, std::slice::from_ref(&alpha), true).unwrap();
+    assert_eq!(rgb.color(), ColorType::Rgba8);
+    assert_eq!(rgb.to_rgba8().into_raw(), vec![1, 2, 3, 0, 4, 5, 6, 128]);
+
+    let mut gray16 =
+      DynamicImage::ImageLuma16(ImageBuffer::from_raw(2, 1, vec![1000u16, 2000]).unwrap());
+    join_channels(&mut gray16, std::slice::from_ref(&alpha), true).unwrap();
+    assert_eq!(gray16.color(), ColorType::La16);
+    assert_eq!(
+      gray16.to_luma_alpha16().into_raw(),
+      vec![1000, 0, 2000, 128 * 257]
+    );
+  }
+
+  #[test]
+  fn join_channels_rejects_extra_channels_and_size_mismatches() {
+    let gray = |width| {
+      OverlaySource::Pixels(Arc::new(DynamicImage::ImageLuma8(
+        ImageBuffer::from_raw(width, 1, vec![0u8; width as usize]).unwrap(),
+      )))
+    };
+    let mut rgba = DynamicImage::ImageRgba8(RgbaImage::from_raw(2, 1, vec![0; 8]).unwrap());
+    assert!(join_channels(&mut rgba, &[gray(2)], true).is_err());
+    let mut rgb = DynamicImage::ImageRgb8(RgbImage::from_raw(2, 1, vec![0; 6]).unwrap());
+    assert!(join_channels(&mut rgb, &[gray(1)], true).is_err());
+    // Metadata gets the joined color type without reading the sources.
+    join_channels(&mut rgb, &[gray(1)], false).unwrap();
+    assert_eq!(rgb.color(), ColorType::Rgba8);
+  }
+
+  #[test]
+  fn shadow_grows_the_canvas_at_the_image_depth() {
+    let effect = AlphaEffect::shadow(ShadowOptions {
+      offset_x: Some(-3.0),
+      offset_y: Some(2.0),
+      blur: Some(0.0),
+      color: Some("black".to_owned()),
+      ..Default::default()
+    })
+    .unwrap();
+    let source = DynamicImage::ImageRgb16(ImageBuffer::from_raw(2, 2, vec![40000u16; 12]).unwrap());
+    let mut img = source.clone();
+    apply_alpha_effect(&mut img, &effect, true).unwrap();
+    assert_eq!(img.color(), ColorType::Rgba16);
+    assert_eq!((img.width(), img.height()), (5, 4));
+    let out = img.to_rgba16();
+    // The image moved right by the shadow's leftward reach, and is drawn over its shadow.
+    assert_eq!(out.get_pixel(3, 0).0, [40000, 40000, 40000, 65535]);
+    assert_eq!(out.get_pixel(0, 3).0, [0, 0, 0, 65535]);
+    assert_eq!(out.get_pixel(0, 0).0[3], 0);
+
+    let mut meta = source;
+    apply_alpha_effect(&mut meta, &effect, false).unwrap();
+    assert_eq!(
+      (meta.width(), meta.height(), meta.color()),
+      (5, 4, ColorType::Rgba16)
+    );
+  }
 }
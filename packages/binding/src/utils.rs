@@ -1,5 +1,12 @@
 /// https://github.com/image-rs/image/blob/v0.24.5/src/math/utils.rs
 use std::cmp::max;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use image::{ColorType, DynamicImage, ImageBuffer, Luma, LumaA, Rgb, Rgba};
+use once_cell::sync::Lazy;
+
+use crate::fast_resize::ResizeFit;
+use crate::lab::{linear_to_srgb8, srgb_to_linear_f};
 
 /// Calculates the width and height an image should be resized to.
 /// This preserves aspect ratio, and based on the `fill` parameter
@@ -37,3 +44,199 @@ pub(crate) fn resize_dimensions(
     (nw as u32, nh as u32)
   }
 }
+
+/// The size the image is resampled to for `fit`, before any cover crop or contain letterbox:
+/// `Cover`/`Outside` fill the box, `Inside`/`Contain` fit inside it and `Fill` stretches to it.
+pub(crate) fn fit_dimensions(
+  width: u32,
+  height: u32,
+  nwidth: u32,
+  nheight: u32,
+  fit: ResizeFit,
+) -> (u32, u32) {
+  match fit {
+    ResizeFit::Cover | ResizeFit::Outside => {
+      resize_dimensions(width, height, nwidth, nheight, true)
+    }
+    ResizeFit::Inside | ResizeFit::Contain => {
+      resize_dimensions(width, height, nwidth, nheight, false)
+    }
+    ResizeFit::Fill => (nwidth, nheight),
+  }
+}
+
+/// Whether `withoutEnlargement`/`withoutReduction` forbid resampling a `width`x`height` image to
+/// `nwidth`x`nheight` (as returned by [`fit_dimensions`]). A forbidden resize is skipped entirely.
+pub(crate) fn resize_is_blocked(
+  (width, height): (u32, u32),
+  (nwidth, nheight): (u32, u32),
+  without_enlargement: bool,
+  without_reduction: bool,
+) -> bool {
+  (without_enlargement && (nwidth > width || nheight > height))
+    || (without_reduction && (nwidth < width || nheight < height))
+}
+
+/// sRGB 16-bit code -> linear light in `[0.0, 1.0]`.
+static SRGB16_TO_LINEAR: Lazy<Vec<f32>> = Lazy::new(|| {
+  (0..=u16::MAX)
+    .map(|code| {
+      let v = code as f32 / 65535.0;
+      if v <= 0.04045 {
+        v / 12.92
+      } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+      }
+    })
+    .collect()
+});
+
+fn linear_to_srgb16(lin: f32) -> u16 {
+  let v = lin.clamp(0.0, 1.0);
+  let encoded = if v <= 0.003_130_8 {
+    v * 12.92
+  } else {
+    1.055 * v.powf(1.0 / 2.4) - 0.055
+  };
+  (encoded * 65535.0).round() as u16
+}
+
+/// Decode an integer sRGB image to linear light as `Rgb32F`/`Rgba32F` (grayscale is spread
+/// over the three color channels; alpha is only normalized, never linearized), so a resampling
+/// filter averages physical light instead of gamma-encoded values. Returns `None` for float
+/// images, which are already linear.
+pub(crate) fn to_linear_light(image: &DynamicImage) -> Option<DynamicImage> {
+  let color = image.color();
+  let (width, height) = (image.width(), image.height());
+  let channels = if color.has_alpha() { 4 } else { 3 };
+  let mut data = Vec::with_capacity(width as usize * height as usize * channels);
+  match color {
+    ColorType::L8 | ColorType::La8 | ColorType::Rgb8 | ColorType::Rgba8 => {
+      for pixel in image.to_rgba8().pixels() {
+        let [r, g, b, a] = pixel.0;
+        data.extend([
+          srgb_to_linear_f(r),
+          srgb_to_linear_f(g),
+          srgb_to_linear_f(b),
+        ]);
+        if channels == 4 {
+          data.push(a as f32 / 255.0);
+        }
+      }
+    }
+    ColorType::L16 | ColorType::La16 | ColorType::Rgb16 | ColorType::Rgba16 => {
+      for pixel in image.to_rgba16().pixels() {
+        let [r, g, b, a] = pixel.0;
+        data.extend([
+          SRGB16_TO_LINEAR[r as usize],
+          SRGB16_TO_LINEAR[g as usize],
+          SRGB16_TO_LINEAR[b as usize],
+        ]);
+        if channels == 4 {
+          data.push(a as f32 / 65535.0);
+        }
+      }
+    }
+    _ => return None,
+  }
+  if channels == 4 {
+    ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba32F)
+  } else {
+    ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb32F)
+  }
+}
+
+/// Re-encode a linear-light image produced by [`to_linear_light`] (and then resampled) back to
+/// the sRGB `original` color type.
+pub(crate) fn from_linear_light(image: &DynamicImage, original: ColorType) -> DynamicImage {
+  let (width, height) = (image.width(), image.height());
+  let linear = image.to_rgba32f();
+  let color8 = |x: u32, y: u32, c: usize| linear_to_srgb8(linear.get_pixel(x, y).0[c]);
+  let color16 = |x: u32, y: u32, c: usize| linear_to_srgb16(linear.get_pixel(x, y).0[c]);
+  let alpha8 = |x: u32, y: u32| (linear.get_pixel(x, y).0[3].clamp(0.0, 1.0) * 255.0).round() as u8;
+  let alpha16 =
+    |x: u32, y: u32| (linear.get_pixel(x, y).0[3].clamp(0.0, 1.0) * 65535.0).round() as u16;
+  match original {
+    ColorType::L8 => DynamicImage::ImageLuma8(ImageBuffer::from_fn(width, height, |x, y| {
+      Luma([color8(x, y, 0)])
+    })),
+    ColorType::La8 => DynamicImage::ImageLumaA8(ImageBuffer::from_fn(width, height, |x, y| {
+      LumaA([color8(x, y, 0), alpha8(x, y)])
+    })),
+    ColorType::Rgb8 => DynamicImage::ImageRgb8(ImageBuffer::from_fn(width, height, |x, y| {
+      Rgb([color8(x, y, 0), color8(x, y, 1), color8(x, y, 2)])
+    })),
+    ColorType::L16 => DynamicImage::ImageLuma16(ImageBuffer::from_fn(width, height, |x, y| {
+      Luma([color16(x, y, 0)])
+    })),
+    ColorType::La16 => DynamicImage::ImageLumaA16(ImageBuffer::from_fn(width, height, |x, y| {
+      LumaA([color16(x, y, 0), alpha16(x, y)])
+    })),
+    ColorType::Rgb16 => DynamicImage::ImageRgb16(ImageBuffer::from_fn(width, height, |x, y| {
+      Rgb([color16(x, y, 0), color16(x, y, 1), color16(x, y, 2)])
+    })),
+    ColorType::Rgba16 => DynamicImage::ImageRgba16(ImageBuffer::from_fn(width, height, |x, y| {
+      Rgba([
+        color16(x, y, 0),
+        color16(x, y, 1),
+        color16(x, y, 2),
+        alpha16(x, y),
+      ])
+    })),
+    _ => DynamicImage::ImageRgba8(ImageBuffer::from_fn(width, height, |x, y| {
+      Rgba([
+        color8(x, y, 0),
+        color8(x, y, 1),
+        color8(x, y, 2),
+        alpha8(x, y),
+      ])
+    })),
+  }
+}
+
+/// Helper threads running across every parallel pass (encodes, blurs, convolutions). Those run on
+/// libuv workers, so concurrent calls share one budget instead of each spawning a thread per CPU.
+static HELPER_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Helper threads taken from the process-wide budget of `num_cpus - 1`, given back on drop. The
+/// calling thread always works too, so with none free the pass just runs there.
+pub(crate) struct HelperThreads(usize);
+
+impl HelperThreads {
+  /// Take up to `wanted` helpers, however many are free (possibly none).
+  pub(crate) fn take(wanted: usize) -> Self {
+    let budget = num_cpus::get().saturating_sub(1);
+    let mut taken = 0;
+    let _ = HELPER_THREADS.fetch_update(Ordering::AcqRel, Ordering::Acquire, |running| {
+      taken = wanted.min(budget.saturating_sub(running));
+      Some(running + taken)
+    });
+    HelperThreads(taken)
+  }
+
+  pub(crate) fn count(&self) -> usize {
+    self.0
+  }
+}
+
+impl Drop for HelperThreads {
+  fn drop(&mut self) {
+    HELPER_THREADS.fetch_sub(self.0, Ordering::AcqRel);
+  }
+}
+
+/// `value` as a JSON string literal, quotes included.
+pub(crate) fn json_string(value: &str) -> String {
+  let mut quoted = String::with_capacity(value.len() + 2);
+  quoted.push('"');
+  for c in value.chars() {
+    match c {
+      '"' => quoted.push_str("\\\""),
+      '\\' => quoted.push_str("\\\\"),
+      c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+      c => quoted.push(c),
+    }
+  }
+  quoted.push('"');
+  quoted
+}
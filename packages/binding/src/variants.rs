@@ -0,0 +1,236 @@
+use image::DynamicImage;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+use crate::encode_auto::{EncodeSpec, OutputFormat};
+use crate::fast_resize::{FastResizeOptions, ResizeFit, fast_resize};
+use crate::utils::{fit_dimensions, resize_is_blocked};
+
+#[napi(object)]
+pub struct VariantsOptions {
+  /// Output widths, in any order; duplicates are dropped.
+  pub widths: Vec<u32>,
+  /// Every width is encoded once per entry.
+  pub formats: Vec<EncodeSpec>,
+  /// Width / height of every variant, e.g. `16 / 9`. Defaults to the image's own aspect ratio.
+  pub aspect_ratio: Option<f64>,
+  /// How the image fills the `aspectRatio` box. Only with `aspectRatio`; defaults to `Cover`.
+  pub fit: Option<ResizeFit>,
+  /// Skip widths that would enlarge the image; when every width would, the result is an empty
+  /// array. Defaults to `false`.
+  pub without_enlargement: Option<bool>,
+}
+
+#[napi(object)]
+pub struct Variant {
+  pub width: u32,
+  pub height: u32,
+  pub format: OutputFormat,
+  /// `buffer.length`, for srcset manifests that drop the buffers.
+  pub size: u32,
+  pub buffer: Buffer,
+}
+
+pub struct VariantOutput {
+  pub(crate) width: u32,
+  pub(crate) height: u32,
+  pub(crate) format: OutputFormat,
+  pub(crate) bytes: Vec<u8>,
+}
+
+impl From<VariantOutput> for Variant {
+  fn from(output: VariantOutput) -> Self {
+    Variant {
+      width: output.width,
+      height: output.height,
+      format: output.format,
+      size: output.bytes.len() as u32,
+      buffer: output.bytes.into(),
+    }
+  }
+}
+
+/// The resize half of `VariantsOptions`: everything but the encoders.
+#[derive(Clone)]
+pub(crate) struct VariantPlan {
+  /// Requested widths, deduplicated, in request order.
+  pub(crate) widths: Vec<u32>,
+  aspect_ratio: Option<f64>,
+  fit: ResizeFit,
+  without_enlargement: bool,
+}
+
+impl VariantPlan {
+  pub(crate) fn new(options: &VariantsOptions) -> Result<Self> {
+    let invalid = |message: String| Err(Error::new(Status::InvalidArg, message));
+    if options.widths.is_empty() || options.widths.contains(&0) {
+      return invalid("generateVariants: `widths` must be non-empty and positive".to_owned());
+    }
+    if options.formats.is_empty() {
+      return invalid("generateVariants: `formats` must not be empty".to_owned());
+    }
+    if let Some(ratio) = options.aspect_ratio
+      && !(ratio.is_finite() && ratio > 0.0)
+    {
+      return invalid(format!(
+        "generateVariants: `aspectRatio` must be a positive number, got {ratio}"
+      ));
+    }
+    if options.fit.is_some() && options.aspect_ratio.is_none() {
+      return invalid("generateVariants: `fit` needs `aspectRatio`".to_owned());
+    }
+    let mut widths = options.widths.clone();
+    let mut seen = Vec::with_capacity(widths.len());
+    widths.retain(|width| {
+      let first = !seen.contains(width);
+      seen.push(*width);
+      first
+    });
+    Ok(VariantPlan {
+      widths,
+      aspect_ratio: options.aspect_ratio,
+      fit: options.fit.unwrap_or_default(),
+      without_enlargement: options.without_enlargement.unwrap_or(false),
+    })
+  }
+
+  /// The `width`x`height` box each kept width resizes to for a `source_width`x`source_height`
+  /// image, widest first.
+  pub(crate) fn boxes(&self, source_width: u32, source_height: u32) -> Vec<(u32, u32)> {
+    let mut widths = self.widths.clone();
+    widths.sort_unstable_by(|a, b| b.cmp(a));
+    widths
+      .into_iter()
+      .map(|width| {
+        let height = match self.aspect_ratio {
+          Some(ratio) => width as f64 / ratio,
+          None => source_height as f64 * width as f64 / source_width as f64,
+        };
+        (width, (height.round() as u32).max(1))
+      })
+      .filter(|&(width, height)| {
+        !(self.without_enlargement
+          && resize_is_blocked(
+            (source_width, source_height),
+            fit_dimensions(source_width, source_height, width, height, self.fit()),
+            true,
+            false,
+          ))
+      })
+      .collect()
+  }
+
+  fn fit(&self) -> ResizeFit {
+    if self.aspect_ratio.is_some() {
+      self.fit
+    } else {
+      ResizeFit::Fill
+    }
+  }
+
+  /// Resize `source` into `boxes` (as returned by [`Self::boxes`]): the widest from `source`,
+  /// every other one downsampled from the next-wider level, which is much cheaper than going back
+  /// to a large source each time.
+  pub(crate) fn pyramid(
+    &self,
+    source: &DynamicImage,
+    boxes: &[(u32, u32)],
+  ) -> Result<Vec<DynamicImage>> {
+    let mut levels: Vec<DynamicImage> = Vec::with_capacity(boxes.len());
+    for &(width, height) in boxes {
+      let (mut level, options) = match levels.last() {
+        None => (source.clone(), resize_options(width, height, self.fit())),
+        Some(previous) => {
+          // Every level has the widest one's shape; `Inside`/`Outside` may not fill their box,
+          // so scale that shape instead of using the box.
+          let (width, height) = match self.fit() {
+            ResizeFit::Inside | ResizeFit::Outside => {
+              let (widest, widest_box) = (&levels[0], boxes[0].0 as f64);
+              let scale = |side: u32| (side as f64 * width as f64 / widest_box).round() as u32;
+              (scale(widest.width()).max(1), scale(widest.height()).max(1))
+            }
+            _ => (width, height),
+          };
+          (
+            previous.clone(),
+            resize_options(width, height, ResizeFit::Fill),
+          )
+        }
+      };
+      if (level.width(), level.height()) != (options.width, options.height.unwrap_or_default())
+        || !matches!(options.fit, Some(ResizeFit::Fill))
+      {
+        fast_resize(&mut level, &options)?;
+      }
+      levels.push(level);
+    }
+    Ok(levels)
+  }
+}
+
+fn resize_options(width: u32, height: u32, fit: ResizeFit) -> FastResizeOptions {
+  FastResizeOptions {
+    width,
+    height: Some(height),
+    filter: None,
+    fit: Some(fit),
+    position: None,
+    strategy: None,
+    background: None,
+    without_enlargement: None,
+    without_reduction: None,
+    linear_light: None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn options(widths: &[u32]) -> VariantsOptions {
+    VariantsOptions {
+      widths: widths.to_vec(),
+      formats: vec![EncodeSpec {
+        format: OutputFormat::Webp,
+        quality: None,
+        lossless: None,
+        png: None,
+        avif: None,
+      }],
+      aspect_ratio: None,
+      fit: None,
+      without_enlargement: None,
+    }
+  }
+
+  #[test]
+  fn boxes_are_widest_first_and_keep_the_aspect_ratio() {
+    let plan = VariantPlan::new(&options(&[320, 1280, 640, 320])).unwrap();
+    assert_eq!(plan.widths, [320, 1280, 640]);
+    assert_eq!(plan.boxes(1000, 500), [(1280, 640), (640, 320), (320, 160)]);
+    let square = VariantPlan::new(&VariantsOptions {
+      aspect_ratio: Some(1.0),
+      without_enlargement: Some(true),
+      ..options(&[200, 1280, 640, 480])
+    })
+    .unwrap();
+    // Covering a 640x640 box would enlarge the 1000x500 source, covering 480x480 would not.
+    assert_eq!(square.boxes(1000, 500), [(480, 480), (200, 200)]);
+  }
+
+  #[test]
+  fn rejects_invalid_options() {
+    assert!(VariantPlan::new(&options(&[])).is_err());
+    assert!(VariantPlan::new(&options(&[100, 0])).is_err());
+    let fit_alone = VariantsOptions {
+      fit: Some(ResizeFit::Inside),
+      ..options(&[100])
+    };
+    assert!(VariantPlan::new(&fit_alone).is_err());
+    let no_formats = VariantsOptions {
+      formats: vec![],
+      ..options(&[100])
+    };
+    assert!(VariantPlan::new(&no_formats).is_err());
+  }
+}
@@ -1,4 +1,4 @@
-use image::{DynamicImage, Rgb, Rgba, buffer::ConvertBuffer};
+use image::{DynamicImage, ImageBuffer, Rgb, Rgba, buffer::ConvertBuffer};
 use napi::bindgen_prelude::*;
 
 #[inline]
@@ -123,6 +123,51 @@ pub(crate) unsafe fn encode_webp_inner(
   }
 }
 
+/// Decode a still WebP scaled to `width`x`height` with libwebp's decoder-side scaling, for
+/// shrink-on-load. Produces `Rgb8`, or `Rgba8` when the bitstream has alpha, like the `image`
+/// decoder. Returns `None` for animations and on any decode error, leaving both to the regular
+/// decoder.
+pub(crate) fn decode_webp_scaled(input: &[u8], width: u32, height: u32) -> Option<DynamicImage> {
+  let mut config = libwebp_sys::WebPDecoderConfig::new().ok()?;
+  unsafe {
+    if libwebp_sys::WebPGetFeatures(input.as_ptr(), input.len(), &mut config.input)
+      != libwebp_sys::VP8StatusCode::VP8_STATUS_OK
+      || config.input.has_animation != 0
+    {
+      return None;
+    }
+    let has_alpha = config.input.has_alpha != 0;
+    config.output.colorspace = if has_alpha {
+      libwebp_sys::WEBP_CSP_MODE::MODE_RGBA
+    } else {
+      libwebp_sys::WEBP_CSP_MODE::MODE_RGB
+    };
+    config.options.use_scaling = 1;
+    config.options.scaled_width = width as i32;
+    config.options.scaled_height = height as i32;
+    if libwebp_sys::WebPDecode(input.as_ptr(), input.len(), &mut config)
+      != libwebp_sys::VP8StatusCode::VP8_STATUS_OK
+    {
+      libwebp_sys::WebPFreeDecBuffer(&mut config.output);
+      return None;
+    }
+    let channels = if has_alpha { 4 } else { 3 };
+    let row_len = width as usize * channels;
+    let rgba = config.output.u.RGBA;
+    let mut data = Vec::with_capacity(row_len * height as usize);
+    for y in 0..height as usize {
+      let row = std::slice::from_raw_parts(rgba.rgba.add(y * rgba.stride as usize), row_len);
+      data.extend_from_slice(row);
+    }
+    libwebp_sys::WebPFreeDecBuffer(&mut config.output);
+    if has_alpha {
+      ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgba8)
+    } else {
+      ImageBuffer::from_raw(width, height, data).map(DynamicImage::ImageRgb8)
+    }
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
@@ -189,4 +234,16 @@ mod tests {
     assert!(len > 0, "output length must be > 0");
     unsafe { libwebp_sys::WebPFree(ptr as *mut _) };
   }
+
+  #[test]
+  fn decode_webp_scaled_returns_the_requested_size() {
+    let image =
+      DynamicImage::ImageRgba8(ImageBuffer::from_pixel(64, 32, Rgba([200u8, 40, 90, 255])));
+    let (ptr, len) = unsafe { lossless_encode_webp_inner(&image, 64, 32) }.expect("encode ok");
+    let encoded = unsafe { std::slice::from_raw_parts(ptr, len) }.to_vec();
+    unsafe { libwebp_sys::WebPFree(ptr as *mut _) };
+    let scaled = decode_webp_scaled(&encoded, 16, 8).expect("scaled decode");
+    assert_eq!((scaled.width(), scaled.height()), (16, 8));
+    assert_eq!(scaled.to_rgba8().get_pixel(7, 3).0, [200, 40, 90, 255]);
+  }
 }